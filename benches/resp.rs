@@ -1,7 +1,8 @@
 use anyhow::Result;
 use bytes::BytesMut;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use simple_redis::RespFrame;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use simple_redis::{BulkString, RespArray, RespEncode, RespFrame};
+use std::collections::BTreeMap;
 
 // resp frames covers all kinds of real-world redis requests and responses
 // cmd 1: set key value
@@ -18,6 +19,68 @@ use simple_redis::RespFrame;
 
 const DATA: &str = "*3\r\n$4\r\necho\r\n$5\r\nhello\r\n+OK\r\n";
 
+// set key <1MB value>, the bulk string is sliced out of the buffer instead of copied
+fn large_value() -> BytesMut {
+    let value = vec![b'v'; 1024 * 1024];
+    let mut buf =
+        BytesMut::from(&format!("*3\r\n$3\r\nset\r\n$3\r\nkey\r\n${}\r\n", value.len())[..]);
+    buf.extend_from_slice(&value);
+    buf.extend_from_slice(b"\r\n");
+    buf
+}
+
+// 100 arrays nested in each other, each of them holding a 1KB value
+fn deep_array() -> BytesMut {
+    let value = vec![b'v'; 1024];
+    let mut buf = BytesMut::new();
+    for _ in 0..100 {
+        buf.extend_from_slice(format!("*2\r\n${}\r\n", value.len()).as_bytes());
+        buf.extend_from_slice(&value);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf.extend_from_slice(b"*0\r\n");
+    buf
+}
+
+// an array of 10k values received by reads of 1KB, the codec is called after each read
+fn pipelined_array() -> BytesMut {
+    let mut buf = BytesMut::from("*10000\r\n");
+    for i in 0..10000 {
+        let value = format!("value-{:05}", i);
+        buf.extend_from_slice(format!("${}\r\n{}\r\n", value.len(), value).as_bytes());
+    }
+    buf
+}
+
+const READ_SIZE: usize = 1024;
+
+// the frame of the reads, decoded by a stateless decoder from the start on each read
+fn segmented_decode(
+    data: &[u8],
+    decode: fn(&mut BytesMut) -> Result<RespFrame, simple_redis::RespError>,
+) -> Option<RespFrame> {
+    let mut buf = BytesMut::new();
+    for chunk in data.chunks(READ_SIZE) {
+        buf.extend_from_slice(chunk);
+        if let Ok(frame) = decode(&mut buf) {
+            return Some(frame);
+        }
+    }
+    None
+}
+
+fn segmented_v4_decode(data: &[u8]) -> Option<RespFrame> {
+    let mut decoder = simple_redis::RespDecoderV4::default();
+    let mut buf = BytesMut::new();
+    for chunk in data.chunks(READ_SIZE) {
+        buf.extend_from_slice(chunk);
+        if let Ok(Some(frame)) = decoder.decode(&mut buf) {
+            return Some(frame);
+        }
+    }
+    None
+}
+
 fn v1_decode(buf: &mut BytesMut) -> Result<Vec<RespFrame>> {
     use simple_redis::RespDecode;
     let mut frames = Vec::new();
@@ -48,21 +111,153 @@ fn v3_decode(buf: &mut BytesMut) -> Result<Vec<RespFrame>> {
     Ok(frames)
 }
 
+fn v4_decode(buf: &mut BytesMut) -> Result<Vec<RespFrame>> {
+    let mut decoder = simple_redis::RespDecoderV4::default();
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.decode(buf)? {
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+// the reply of HGETALL on a hash of 1k fields, a map for RESP3 clients
+fn hgetall_reply() -> RespFrame {
+    let map = (0..1000)
+        .map(|i| {
+            let field = Some(BulkString::from(format!("field-{:04}", i))).into();
+            let value = Some(BulkString::from(format!("value-{:04}", i))).into();
+            (field, value)
+        })
+        .collect::<BTreeMap<RespFrame, RespFrame>>();
+    RespFrame::Map(map)
+}
+
+// the reply of LRANGE on a list of 10k elements
+fn lrange_reply() -> RespFrame {
+    let values = (0..10000)
+        .map(|i| Some(BulkString::from(format!("value-{:05}", i))).into())
+        .collect::<Vec<RespFrame>>();
+    Some(RespArray::new(values)).into()
+}
+
+// 100 arrays nested in each other, each of them holding an integer
+fn nested_reply() -> RespFrame {
+    (0..100).fold(RespFrame::Array(None), |frame, i| {
+        Some(RespArray::new(vec![RespFrame::Integer(i), frame])).into()
+    })
+}
+
+fn encode_benchmark(c: &mut Criterion) {
+    let cases = [
+        ("hgetall", hgetall_reply()),
+        ("lrange", lrange_reply()),
+        ("nested", nested_reply()),
+    ];
+
+    for (name, frame) in cases {
+        let mut group = c.benchmark_group(format!("encode_{}", name));
+        group.throughput(Throughput::Bytes(frame.size_hint() as u64));
+
+        // a new Vec for the frame and each of its elements, copied into the output,
+        // as the codec did before writing the frames into it
+        group.bench_function("encode", |b| {
+            b.iter_batched(
+                || (frame.clone(), BytesMut::new()),
+                |(frame, mut dst)| {
+                    dst.extend_from_slice(&frame.encode());
+                    black_box(dst)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        // the frame is written into the output, reserved once, as the codec does
+        group.bench_function("encode_to", |b| {
+            b.iter_batched(
+                || (frame.clone(), BytesMut::new()),
+                |(frame, mut dst)| {
+                    dst.reserve(frame.size_hint());
+                    frame.encode_to(&mut dst);
+                    black_box(dst)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.finish();
+    }
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
-    let buf = BytesMut::from(DATA);
+    let cases = [
+        ("small", BytesMut::from(DATA)),
+        ("large_value", large_value()),
+        ("deep_array", deep_array()),
+    ];
+
+    // the buffer is cloned outside of the measurement, only the decoding is measured
+    for (name, buf) in cases {
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(buf.len() as u64));
+
+        group.bench_function("v1_decode", |b| {
+            b.iter_batched(
+                || buf.clone(),
+                |mut buf| black_box(v1_decode(&mut buf)),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_function("v2_decode", |b| {
+            b.iter_batched(
+                || buf.clone(),
+                |mut buf| black_box(v2_decode(&mut buf)),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_function("v3_decode", |b| {
+            b.iter_batched(
+                || buf.clone(),
+                |mut buf| black_box(v3_decode(&mut buf)),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_function("v4_decode", |b| {
+            b.iter_batched(
+                || buf.clone(),
+                |mut buf| black_box(v4_decode(&mut buf)),
+                BatchSize::LargeInput,
+            )
+        });
+        group.finish();
+    }
+
+    let data = pipelined_array();
+    let mut group = c.benchmark_group("segmented");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("v1_decode", |b| {
+        use simple_redis::RespDecode;
+        b.iter(|| black_box(segmented_decode(&data, RespFrame::decode)))
+    });
 
-    c.bench_function("v1_decode", |b| {
-        b.iter(|| black_box(v1_decode(&mut buf.clone())))
+    group.bench_function("v2_decode", |b| {
+        use simple_redis::RespDecodeV2;
+        b.iter(|| black_box(segmented_decode(&data, RespFrame::decode)))
     });
 
-    c.bench_function("v2_decode", |b| {
-        b.iter(|| black_box(v2_decode(&mut buf.clone())))
+    group.bench_function("v3_decode", |b| {
+        use simple_redis::RespDecodeV3;
+        b.iter(|| black_box(segmented_decode(&data, RespFrame::decode)))
     });
 
-    c.bench_function("v3_decode", |b| {
-        b.iter(|| black_box(v3_decode(&mut buf.clone())))
+    group.bench_function("v4_decode", |b| {
+        b.iter(|| black_box(segmented_v4_decode(&data)))
     });
+    group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, encode_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,152 @@
+use bytes::BytesMut;
+
+use crate::{RespDecodeV2, RespError, RespFrame};
+
+/// The result of checking an AOF file, as `redis-check-aof` reports it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AofCheck {
+    // length of the data holding complete commands and transactions
+    pub valid: usize,
+    // the commands in the valid data
+    pub commands: usize,
+    pub error: Option<String>,
+    // the error is an incomplete tail, which the server truncates when loading
+    pub truncated: bool,
+}
+
+impl AofCheck {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Checks the syntax of the commands of an AOF file without executing them.
+pub fn check_aof(data: &[u8]) -> AofCheck {
+    let mut check = AofCheck::default();
+    let mut buf = BytesMut::from(data);
+    let mut multi = None;
+    let mut pending = 0;
+
+    while !buf.is_empty() {
+        let offset = data.len() - buf.len();
+        let frame = match RespFrame::decode(&mut buf) {
+            Ok(frame) => frame,
+            Err(RespError::NotComplete) => {
+                check.error = Some(format!("Unexpected EOF at offset {}", offset));
+                check.truncated = true;
+                return check;
+            }
+            Err(e) => {
+                check.error = Some(format!("{} at offset {}", e, offset));
+                return check;
+            }
+        };
+
+        let name = match &frame {
+            RespFrame::Array(Some(args))
+                if !args.is_empty()
+                    && args
+                        .iter()
+                        .all(|arg| matches!(arg, RespFrame::BulkString(Some(_)))) =>
+            {
+                match &args[0] {
+                    RespFrame::BulkString(Some(name)) => name.to_ascii_lowercase(),
+                    _ => unreachable!("all the arguments are bulk strings"),
+                }
+            }
+            _ => {
+                check.error = Some(format!(
+                    "Expected an array of bulk strings at offset {}",
+                    offset
+                ));
+                return check;
+            }
+        };
+        match (name.as_slice(), multi) {
+            (b"multi", Some(_)) => {
+                check.error = Some(format!("Unexpected MULTI at offset {}", offset));
+                return check;
+            }
+            (b"multi", None) => multi = Some(offset),
+            (b"exec", None) => {
+                check.error = Some(format!("Unexpected EXEC at offset {}", offset));
+                return check;
+            }
+            (b"exec", Some(_)) => multi = None,
+            _ => {}
+        }
+        pending += 1;
+        if multi.is_none() {
+            check.valid = data.len() - buf.len();
+            check.commands += pending;
+            pending = 0;
+        }
+    }
+
+    if let Some(offset) = multi {
+        check.error = Some(format!(
+            "Reached EOF before reading EXEC for MULTI at offset {}",
+            offset
+        ));
+        check.truncated = true;
+    }
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::frame, RespEncode};
+
+    fn encode(args: &[&str]) -> Vec<u8> {
+        frame(args).encode()
+    }
+
+    #[test]
+    fn test_check_valid() {
+        let mut data = encode(&["sadd", "s", "a"]);
+        data.extend(encode(&["multi"]));
+        data.extend(encode(&["hset", "h", "f", "v"]));
+        data.extend(encode(&["exec"]));
+        let check = check_aof(&data);
+        assert!(check.is_valid());
+        assert_eq!(check.valid, data.len());
+        assert_eq!(check.commands, 4);
+    }
+
+    #[test]
+    fn test_check_truncated() {
+        let mut data = encode(&["sadd", "s", "a"]);
+        let complete = data.len();
+        data.extend_from_slice(b"*3\r\n$4\r\nsadd\r\n$1\r\ns");
+        let check = check_aof(&data);
+        assert!(check.truncated);
+        assert_eq!(check.valid, complete);
+
+        let mut data = encode(&["sadd", "s", "a"]);
+        data.extend(encode(&["multi"]));
+        data.extend(encode(&["sadd", "s", "b"]));
+        let check = check_aof(&data);
+        assert!(check.truncated);
+        assert_eq!(check.valid, complete);
+        assert_eq!(check.commands, 1);
+    }
+
+    #[test]
+    fn test_check_invalid() {
+        let mut data = encode(&["sadd", "s", "a"]);
+        let complete = data.len();
+        data.extend(encode(&["exec"]));
+        let check = check_aof(&data);
+        assert!(!check.truncated);
+        assert_eq!(check.valid, complete);
+        assert_eq!(
+            check.error.as_deref(),
+            Some(format!("Unexpected EXEC at offset {}", complete).as_str())
+        );
+
+        let check = check_aof(b":1\r\n");
+        assert_eq!(check.valid, 0);
+        assert!(!check.is_valid() && !check.truncated);
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use super::AofError;
+
+/// The type of a file listed in the manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofFileType {
+    // snapshot of the dataset, in the RDB or the AOF format
+    Base,
+    // write commands appended after the base was created
+    Incr,
+    // replaced by a rewrite, waiting to be deleted
+    History,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AofFile {
+    pub name: String,
+    pub seq: u64,
+    pub kind: AofFileType,
+}
+
+/// The manifest of a Redis 7 multi-part AOF, listing the base and incr files
+/// of `appenddirname` in the order they are loaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub base: Option<AofFile>,
+    pub incrs: Vec<AofFile>,
+    pub history: Vec<AofFile>,
+}
+
+impl AofFileType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AofFileType::Base => "b",
+            AofFileType::Incr => "i",
+            AofFileType::History => "h",
+        }
+    }
+}
+
+impl Manifest {
+    pub fn file_name(filename: &str) -> String {
+        format!("{}.manifest", filename)
+    }
+
+    /// Reads the manifest of `filename` in `dir`, None if there is no AOF yet.
+    pub fn load(dir: &Path, filename: &str) -> Result<Option<Self>, AofError> {
+        match fs::read_to_string(dir.join(Self::file_name(filename))) {
+            Ok(content) => Ok(Some(content.parse()?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the manifest on disk atomically.
+    pub fn persist(&self, dir: &Path, filename: &str) -> Result<(), AofError> {
+        let tmp = dir.join(format!("temp-{}", Self::file_name(filename)));
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, dir.join(Self::file_name(filename)))?;
+        Ok(())
+    }
+
+    /// The base and incr files, in loading order.
+    pub fn files(&self) -> impl Iterator<Item = &AofFile> {
+        self.base.iter().chain(self.incrs.iter())
+    }
+
+    pub fn next_base(&self, filename: &str) -> AofFile {
+        let seq = self.base.as_ref().map_or(0, |base| base.seq) + 1;
+        AofFile {
+            name: format!("{}.{}.base.rdb", filename, seq),
+            seq,
+            kind: AofFileType::Base,
+        }
+    }
+
+    pub fn next_incr(&self, filename: &str) -> AofFile {
+        let seq = self.incrs.last().map_or(0, |incr| incr.seq) + 1;
+        AofFile {
+            name: format!("{}.{}.incr.aof", filename, seq),
+            seq,
+            kind: AofFileType::Incr,
+        }
+    }
+
+    /// Switches to a new base, the previous base and the incr files older than
+    /// `first_incr` move to the history.
+    pub fn replace_base(&mut self, base: AofFile, first_incr: u64) {
+        if let Some(old) = self.base.replace(base) {
+            self.history.push(AofFile {
+                kind: AofFileType::History,
+                ..old
+            });
+        }
+        let (old, incrs) = self
+            .incrs
+            .drain(..)
+            .partition::<Vec<_>, _>(|incr| incr.seq < first_incr);
+        self.incrs = incrs;
+        self.history.extend(old.into_iter().map(|incr| AofFile {
+            kind: AofFileType::History,
+            ..incr
+        }));
+    }
+}
+
+// file appendonly.aof.1.base.rdb seq 1 type b
+impl std::str::FromStr for Manifest {
+    type Err = AofError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut manifest = Manifest::default();
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || AofError::InvalidManifest(line.to_string());
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            if parts.len() % 2 != 0 {
+                return Err(invalid());
+            }
+            let (mut name, mut seq, mut kind) = (None, None, None);
+            for pair in parts.chunks(2) {
+                match pair[0] {
+                    "file" => name = Some(pair[1].to_string()),
+                    "seq" => seq = Some(pair[1].parse::<u64>().map_err(|_| invalid())?),
+                    "type" => {
+                        kind = Some(match pair[1] {
+                            "b" => AofFileType::Base,
+                            "i" => AofFileType::Incr,
+                            "h" => AofFileType::History,
+                            _ => return Err(invalid()),
+                        })
+                    }
+                    // unknown keys are skipped for forward compatibility
+                    _ => {}
+                }
+            }
+
+            let file = match (name, seq, kind) {
+                (Some(name), Some(seq), Some(kind)) => AofFile { name, seq, kind },
+                _ => return Err(invalid()),
+            };
+            match file.kind {
+                AofFileType::Base if manifest.base.is_some() => {
+                    return Err(AofError::InvalidManifest(
+                        "found duplicate base file information".to_string(),
+                    ))
+                }
+                AofFileType::Base => manifest.base = Some(file),
+                AofFileType::Incr => {
+                    if manifest
+                        .incrs
+                        .last()
+                        .is_some_and(|last| last.seq >= file.seq)
+                    {
+                        return Err(AofError::InvalidManifest(
+                            "found a non-monotonic sequence number".to_string(),
+                        ));
+                    }
+                    manifest.incrs.push(file)
+                }
+                AofFileType::History => manifest.history.push(file),
+            }
+        }
+
+        if manifest.base.is_none() && manifest.incrs.is_empty() {
+            return Err(AofError::InvalidManifest(
+                "the manifest lists no base or incr file".to_string(),
+            ));
+        }
+        Ok(manifest)
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in self.base.iter().chain(&self.history).chain(&self.incrs) {
+            writeln!(
+                f,
+                "file {} seq {} type {}",
+                file.name,
+                file.seq,
+                file.kind.as_str()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() -> Result<(), AofError> {
+        let content = "file appendonly.aof.1.base.rdb seq 1 type b\n\
+                       file appendonly.aof.1.incr.aof seq 1 type i\n\
+                       file appendonly.aof.2.incr.aof seq 2 type i\n";
+        let manifest: Manifest = content.parse()?;
+        assert_eq!(manifest.base.as_ref().map(|b| b.seq), Some(1));
+        assert_eq!(manifest.incrs.len(), 2);
+        assert_eq!(manifest.to_string(), content);
+        assert_eq!(
+            manifest.next_incr("appendonly.aof").name,
+            "appendonly.aof.3.incr.aof"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_replace_base() -> Result<(), AofError> {
+        let mut manifest: Manifest = "file a.1.base.rdb seq 1 type b\n\
+                                      file a.1.incr.aof seq 1 type i\n\
+                                      file a.2.incr.aof seq 2 type i\n"
+            .parse()?;
+        let base = manifest.next_base("a");
+        manifest.replace_base(base, 2);
+        assert_eq!(
+            manifest.to_string(),
+            "file a.2.base.rdb seq 2 type b\n\
+             file a.1.base.rdb seq 1 type h\n\
+             file a.1.incr.aof seq 1 type h\n\
+             file a.2.incr.aof seq 2 type i\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        for content in [
+            "",
+            "file a.1.incr.aof seq 1",
+            "file a.1.incr.aof seq x type i",
+            "file a.1.base.rdb seq 1 type b\nfile a.2.base.rdb seq 2 type b",
+            "file a.2.incr.aof seq 2 type i\nfile a.1.incr.aof seq 1 type i",
+        ] {
+            assert!(content.parse::<Manifest>().is_err(), "{}", content);
+        }
+    }
+}
@@ -0,0 +1,723 @@
+mod check;
+mod manifest;
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, PoisonError, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    deserialize, is_write_command, serialize, Backend, BulkString, Command, CommandExecutor,
+    ExecError, RdbError, RespArray, RespDecodeV2, RespEncode, RespError, RespFrame,
+};
+
+pub use check::{check_aof, AofCheck};
+pub use manifest::{AofFile, AofFileType, Manifest};
+
+const DEFAULT_DIRNAME: &str = "appendonlydir";
+const DEFAULT_FILENAME: &str = "appendonly.aof";
+const FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum AofError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Rdb(#[from] RdbError),
+    #[error("Invalid AOF manifest: {0}")]
+    InvalidManifest(String),
+    #[error("Bad file format reading the append only file {0}")]
+    Invalid(String),
+    #[error("Background append only file rewriting already in progress")]
+    InProgress,
+    #[error("Append only file is disabled")]
+    Disabled,
+}
+
+/// `appendfsync`: when the AOF is flushed to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    // after every write command
+    Always,
+    // once per second, by `aof_cron`
+    EverySec,
+    // left to the operating system
+    No,
+}
+
+/// Configuration and state of the append only file.
+#[derive(Debug)]
+pub struct AofState {
+    enabled: AtomicBool,
+    fsync: RwLock<AppendFsync>,
+    dir: RwLock<PathBuf>,
+    filename: RwLock<String>,
+    writer: Mutex<Option<AofWriter>>,
+    // held from the execution of a write to its append, so the commands of
+    // concurrent connections are appended in the order they changed the dataset
+    order: Mutex<()>,
+    // commands of a transaction or a script, written at once wrapped in MULTI/EXEC
+    batch: Mutex<Batch>,
+    rewrite_in_progress: AtomicBool,
+}
+
+#[derive(Debug)]
+struct AofWriter {
+    dir: PathBuf,
+    filename: String,
+    manifest: Manifest,
+    // the last incr file of the manifest
+    file: File,
+    unsynced: bool,
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    depth: usize,
+    commands: Vec<RespArray>,
+}
+
+/// Propagates the buffered commands when the outermost batch ends.
+pub struct BatchGuard<'a> {
+    aof: &'a AofState,
+}
+
+impl Default for AofState {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            fsync: RwLock::new(AppendFsync::EverySec),
+            dir: RwLock::new(PathBuf::from(DEFAULT_DIRNAME)),
+            filename: RwLock::new(DEFAULT_FILENAME.to_string()),
+            writer: Mutex::new(None),
+            order: Mutex::new(()),
+            batch: Mutex::new(Batch::default()),
+            rewrite_in_progress: AtomicBool::new(false),
+        }
+    }
+}
+
+impl AofState {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Takes effect on the next `start_aof`.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn fsync(&self) -> AppendFsync {
+        *self.fsync.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set_fsync(&self, fsync: AppendFsync) {
+        *self.fsync.write().unwrap_or_else(PoisonError::into_inner) = fsync;
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.dir
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_dir(&self, dir: impl Into<PathBuf>) {
+        *self.dir.write().unwrap_or_else(PoisonError::into_inner) = dir.into();
+    }
+
+    pub fn filename(&self) -> String {
+        self.filename
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_filename(&self, filename: impl Into<String>) {
+        *self
+            .filename
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = filename.into();
+    }
+
+    pub fn rewrite_in_progress(&self) -> bool {
+        self.rewrite_in_progress.load(Ordering::Acquire)
+    }
+
+    /// Appends a write command, or buffers it while a batch is open.
+    pub fn propagate(&self, command: RespArray) {
+        let mut batch = self.batch.lock().unwrap_or_else(PoisonError::into_inner);
+        if batch.depth > 0 {
+            batch.commands.push(command);
+            return;
+        }
+        drop(batch);
+        self.append(vec![command]);
+    }
+
+    /// Opens a batch: the commands propagated until the guard is dropped are
+    /// written together, so a transaction or a script is never partially replayed.
+    pub fn batch(&self) -> BatchGuard<'_> {
+        self.batch
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .depth += 1;
+        BatchGuard { aof: self }
+    }
+
+    fn append(&self, mut commands: Vec<RespArray>) {
+        if commands.len() > 1 {
+            commands.insert(0, command_array(&["MULTI"]));
+            commands.push(command_array(&["EXEC"]));
+        }
+
+        let mut writer = self.lock_writer();
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => return,
+        };
+        let mut buf = Vec::new();
+        for command in commands {
+            buf.extend_from_slice(&Some(command).encode());
+        }
+        let ret = writer
+            .file
+            .write_all(&buf)
+            .and_then(|_| match self.fsync() {
+                AppendFsync::Always => writer.file.sync_data(),
+                _ => {
+                    writer.unsynced = true;
+                    Ok(())
+                }
+            });
+        if let Err(e) = ret {
+            warn!("Error writing to the AOF file: {}", e);
+        }
+    }
+
+    fn lock_order(&self) -> MutexGuard<'_, ()> {
+        self.order.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock_writer(&self) -> MutexGuard<'_, Option<AofWriter>> {
+        self.writer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn sync(&self) {
+        if let Some(writer) = self.lock_writer().as_mut() {
+            if writer.unsynced {
+                match writer.file.sync_data() {
+                    Ok(()) => writer.unsynced = false,
+                    Err(e) => warn!("Error syncing the AOF file: {}", e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for BatchGuard<'_> {
+    fn drop(&mut self) {
+        let mut batch = self
+            .aof
+            .batch
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        batch.depth -= 1;
+        if batch.depth > 0 || batch.commands.is_empty() {
+            return;
+        }
+        let commands = std::mem::take(&mut batch.commands);
+        drop(batch);
+        self.aof.append(commands);
+    }
+}
+
+impl AofWriter {
+    fn open_new_incr(&mut self) -> Result<u64, AofError> {
+        let incr = self.manifest.next_incr(&self.filename);
+        self.file = open_append(&self.dir.join(&incr.name))?;
+        self.unsynced = false;
+        let seq = incr.seq;
+        self.manifest.incrs.push(incr);
+        self.manifest.persist(&self.dir, &self.filename)?;
+        Ok(seq)
+    }
+}
+
+impl FromStr for AppendFsync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(format!(
+                "argument must be 'always', 'everysec' or 'no': {}",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppendFsync::Always => write!(f, "always"),
+            AppendFsync::EverySec => write!(f, "everysec"),
+            AppendFsync::No => write!(f, "no"),
+        }
+    }
+}
+
+/// Executes a command and appends it to the AOF when it is a write that succeeded.
+/// `argv` is the original command, only kept when the AOF is enabled.
+pub(crate) fn call(
+    backend: &Backend,
+    cmd: Command,
+    argv: Option<RespArray>,
+) -> Result<RespFrame, ExecError> {
+    let argv = match (&cmd, argv) {
+        (_, Some(argv)) if !is_write_command(&argv) => None,
+        (Command::Restore(restore), Some(_)) => Some(restore.to_absolute_argv()),
+        (_, argv) => argv,
+    };
+    let _order = argv.as_ref().map(|_| backend.aof.lock_order());
+    let reply = cmd.execute(backend)?;
+    if let Some(argv) = argv {
+        backend.aof.propagate(argv);
+    }
+    Ok(reply)
+}
+
+/// Loads the dataset from the AOF at startup, returns false if there is no AOF.
+pub fn load_aof(backend: &Backend) -> Result<bool, AofError> {
+    let (dir, filename) = (backend.aof.dir(), backend.aof.filename());
+    let manifest = match Manifest::load(&dir, &filename)? {
+        Some(manifest) => manifest,
+        None => return Ok(false),
+    };
+
+    let files = manifest.files().collect::<Vec<_>>();
+    for (i, file) in files.iter().enumerate() {
+        let path = dir.join(&file.name);
+        let data = fs::read(&path)?;
+        // a base written with the RDB preamble
+        if data.starts_with(b"REDIS") {
+            deserialize(backend, &data)?;
+            continue;
+        }
+
+        let valid = replay(backend, &data)
+            .map_err(|e| AofError::Invalid(format!("{}: {}", file.name, e)))?;
+        if valid < data.len() {
+            // aof-load-truncated: only the tail of the last file may be incomplete
+            if i + 1 < files.len() {
+                return Err(AofError::Invalid(format!(
+                    "{}: unexpected end of file",
+                    file.name
+                )));
+            }
+            warn!(
+                "!!! Warning: short read while loading the AOF file {}!!! Truncating the AOF at offset {}",
+                file.name, valid
+            );
+            OpenOptions::new()
+                .write(true)
+                .open(&path)?
+                .set_len(valid as u64)?;
+        }
+    }
+
+    // replaying the commands is not a change to save
+    backend.clear_dirty(backend.dirty());
+    Ok(true)
+}
+
+/// Executes the commands of an AOF file, returns the length of the data holding
+/// complete commands, a transaction missing its EXEC is discarded.
+pub fn replay(backend: &Backend, data: &[u8]) -> Result<usize, RespError> {
+    let mut buf = BytesMut::from(data);
+    let mut valid = 0;
+    let mut multi: Option<Vec<Command>> = None;
+
+    while !buf.is_empty() {
+        let frame = match RespFrame::decode(&mut buf) {
+            Ok(frame) => frame,
+            Err(RespError::NotComplete) => break,
+            Err(e) => return Err(e),
+        };
+        let cmd = Command::try_from(frame).map_err(|e| RespError::InvalidFrame(e.to_string()))?;
+        match (cmd, multi.as_mut()) {
+            (Command::Multi(_), _) => multi = Some(Vec::new()),
+            (Command::Exec(_), Some(_)) => {
+                for cmd in multi.take().unwrap_or_default() {
+                    let _ = cmd.execute(backend);
+                }
+            }
+            (cmd, Some(queue)) => queue.push(cmd),
+            (cmd, None) => {
+                let _ = cmd.execute(backend);
+            }
+        }
+        if multi.is_none() {
+            valid = data.len() - buf.len();
+        }
+    }
+    Ok(valid)
+}
+
+/// Opens the AOF for appending, creating the base and the manifest if the
+/// AOF does not exist yet. Called at startup, after the dataset is loaded.
+pub fn start_aof(backend: &Backend) -> Result<(), AofError> {
+    let (dir, filename) = (backend.aof.dir(), backend.aof.filename());
+    fs::create_dir_all(&dir)?;
+
+    let mut manifest = Manifest::load(&dir, &filename)?.unwrap_or_default();
+    if manifest.base.is_none() && manifest.incrs.is_empty() {
+        let base = manifest.next_base(&filename);
+        write_file(&dir.join(&base.name), &serialize(backend))?;
+        manifest.base = Some(base);
+    }
+
+    let (file, incr) = match manifest.incrs.last() {
+        Some(incr) => (open_append(&dir.join(&incr.name))?, None),
+        None => {
+            let incr = manifest.next_incr(&filename);
+            (open_append(&dir.join(&incr.name))?, Some(incr))
+        }
+    };
+    manifest.incrs.extend(incr);
+    manifest.persist(&dir, &filename)?;
+
+    *backend.aof.lock_writer() = Some(AofWriter {
+        dir,
+        filename,
+        manifest,
+        file,
+        unsynced: false,
+    });
+    Ok(())
+}
+
+/// Flushes and closes the AOF, the writes are no longer appended to it.
+pub fn stop_aof(backend: &Backend) -> Result<(), AofError> {
+    let writer = backend.aof.lock_writer().take();
+    if let Some(writer) = writer {
+        writer.file.sync_data()?;
+    }
+    Ok(())
+}
+
+/// Compacts the AOF (BGREWRITEAOF): a new base is written from a snapshot of the
+/// dataset, and replaces the files written before it. The caller must hold the
+/// exclusive lock of the backend.
+pub fn rewrite(backend: &Backend) -> Result<(), AofError> {
+    let aof = &backend.aof;
+    let mut writer = aof.lock_writer();
+    let writer = writer.as_mut().ok_or(AofError::Disabled)?;
+    if aof.rewrite_in_progress.swap(true, Ordering::AcqRel) {
+        return Err(AofError::InProgress);
+    }
+
+    // the snapshot is taken when the new incr file is opened, so the new base
+    // followed by the new incr file is the whole dataset
+    let data = serialize(backend);
+    let first_incr = match writer.open_new_incr() {
+        Ok(seq) => seq,
+        Err(e) => {
+            aof.rewrite_in_progress.store(false, Ordering::Release);
+            return Err(e);
+        }
+    };
+    let base = writer.manifest.next_base(&writer.filename);
+    let dir = writer.dir.clone();
+
+    let backend = backend.clone();
+    thread::spawn(move || {
+        let aof = &backend.aof;
+        match finish_rewrite(aof, &dir, base, &data, first_incr) {
+            Ok(()) => info!("Background AOF rewrite finished successfully"),
+            Err(e) => warn!("Background AOF rewrite failed: {}", e),
+        }
+        aof.rewrite_in_progress.store(false, Ordering::Release);
+    });
+    Ok(())
+}
+
+fn finish_rewrite(
+    aof: &AofState,
+    dir: &Path,
+    base: AofFile,
+    data: &[u8],
+    first_incr: u64,
+) -> Result<(), AofError> {
+    write_file(&dir.join(&base.name), data)?;
+
+    let mut writer = aof.lock_writer();
+    let writer = match writer.as_mut() {
+        Some(writer) if writer.dir == dir => writer,
+        // the AOF was disabled meanwhile
+        _ => return Ok(()),
+    };
+    writer.manifest.replace_base(base, first_incr);
+    writer.manifest.persist(&writer.dir, &writer.filename)?;
+
+    for file in std::mem::take(&mut writer.manifest.history) {
+        if let Err(e) = fs::remove_file(writer.dir.join(&file.name)) {
+            warn!("Error removing the history AOF file {}: {}", file.name, e);
+        }
+    }
+    writer.manifest.persist(&writer.dir, &writer.filename)?;
+    Ok(())
+}
+
+/// Flushes the AOF to disk every second with `appendfsync everysec`.
+pub async fn aof_cron(backend: Backend) {
+    let mut interval = tokio::time::interval(FSYNC_INTERVAL);
+    loop {
+        interval.tick().await;
+        if backend.aof.fsync() != AppendFsync::EverySec {
+            continue;
+        }
+        let backend = backend.clone();
+        if let Err(e) = tokio::task::spawn_blocking(move || backend.aof.sync()).await {
+            warn!("AOF fsync panicked: {}", e);
+        }
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+fn command_array(args: &[&str]) -> RespArray {
+    RespArray::new(
+        args.iter()
+            .map(|&arg| Some(BulkString::from(arg)).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, temp_path};
+    use crate::Session;
+
+    fn aof_backend(dir: &Path) -> Result<Backend, AofError> {
+        let backend = Backend::new();
+        backend.aof.set_enabled(true);
+        backend.aof.set_dir(dir);
+        backend.aof.set_fsync(AppendFsync::Always);
+        load_aof(&backend)?;
+        start_aof(&backend)?;
+        Ok(backend)
+    }
+
+    fn incr_content(dir: &Path, seq: u64) -> Vec<u8> {
+        fs::read(dir.join(format!("appendonly.aof.{}.incr.aof", seq))).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_append_and_load() -> Result<(), AofError> {
+        let dir = temp_path("test-aof-append");
+        let backend = aof_backend(&dir)?;
+        assert!(dir.join("appendonly.aof.1.base.rdb").exists());
+
+        let mut session = Session::new(backend.clone());
+        for frame in [
+            frame(&["sadd", "myset", "a", "b"]),
+            frame(&["sismember", "myset", "a"]),
+            frame(&["multi"]),
+            frame(&["hset", "myhash", "f", "v"]),
+            frame(&["sadd", "myset", "c"]),
+            frame(&["exec"]),
+        ] {
+            session
+                .process(frame)
+                .map_err(|e| AofError::Invalid(e.to_string()))?;
+        }
+
+        let content = String::from_utf8_lossy(&incr_content(&dir, 1)).to_string();
+        assert!(content.starts_with("*4\r\n$4\r\nsadd\r\n"));
+        assert!(!content.contains("sismember"));
+        assert!(content.contains("$5\r\nMULTI\r\n") && content.ends_with("$4\r\nEXEC\r\n"));
+
+        let loaded = aof_backend(&dir)?;
+        assert_eq!(loaded.hset.get("myset").map(|s| s.len()), Some(3));
+        assert!(loaded.hmap.contains_key("myhash"));
+        assert_eq!(loaded.dirty(), 0);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_writes() -> Result<(), AofError> {
+        let dir = temp_path("test-aof-concurrent");
+        let backend = aof_backend(&dir)?;
+        backend.aof.set_fsync(AppendFsync::No);
+
+        let threads = (0..4)
+            .map(|i| {
+                let mut session = Session::new(backend.clone());
+                thread::spawn(move || {
+                    for n in 0..1000 {
+                        let value = format!("{}-{}", i, n);
+                        let field = format!("f{}", n % 4);
+                        session
+                            .process(frame(&["hset", "myhash", &field, &value]))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        stop_aof(&backend)?;
+
+        let fields = |backend: &Backend| {
+            let mut fields = backend
+                .hmap
+                .get("myhash")
+                .map(|hash| {
+                    hash.iter()
+                        .map(|entry| (entry.key().clone(), entry.value().clone()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            fields
+        };
+        let loaded = aof_backend(&dir)?;
+        assert_eq!(fields(&loaded).len(), 4);
+        assert_eq!(fields(&loaded), fields(&backend));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_effects() -> Result<(), AofError> {
+        let dir = temp_path("test-aof-script");
+        let backend = aof_backend(&dir)?;
+        let mut session = Session::new(backend);
+        let script = "redis.call('sadd', KEYS[1], 'x'); redis.call('sadd', KEYS[1], 'y')";
+        session
+            .process(frame(&["eval", script, "1", "myset"]))
+            .map_err(|e| AofError::Invalid(e.to_string()))?;
+
+        let content = String::from_utf8_lossy(&incr_content(&dir, 1)).to_string();
+        assert!(!content.contains("eval"));
+        assert!(content.starts_with("*1\r\n$5\r\nMULTI\r\n*3\r\n$4\r\nsadd\r\n"));
+        assert!(content.ends_with("$1\r\ny\r\n*1\r\n$4\r\nEXEC\r\n"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_truncated() -> Result<(), AofError> {
+        let dir = temp_path("test-aof-truncated");
+        let backend = aof_backend(&dir)?;
+        backend
+            .aof
+            .propagate(command_array(&["sadd", "myset", "a"]));
+        drop(backend);
+
+        let complete = incr_content(&dir, 1).len();
+        let mut file = open_append(&dir.join("appendonly.aof.1.incr.aof"))?;
+        file.write_all(b"*3\r\n$4\r\nsadd\r\n$5\r\nmy")?;
+
+        let loaded = aof_backend(&dir)?;
+        assert_eq!(loaded.hset.get("myset").map(|s| s.len()), Some(1));
+        assert_eq!(incr_content(&dir, 1).len(), complete);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_incomplete_transaction() -> Result<(), RespError> {
+        let backend = Backend::new();
+        let mut data = Vec::new();
+        for args in [&["sadd", "s", "a"][..], &["multi"], &["sadd", "s", "b"]] {
+            data.extend_from_slice(&Some(command_array(args)).encode());
+        }
+        let valid = replay(&backend, &data)?;
+        assert_eq!(
+            valid,
+            Some(command_array(&["sadd", "s", "a"])).encode().len()
+        );
+        assert_eq!(backend.hset.get("s").map(|s| s.len()), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite() -> Result<(), AofError> {
+        let dir = temp_path("test-aof-rewrite");
+        let backend = aof_backend(&dir)?;
+        for member in ["a", "b", "c"] {
+            call(
+                &backend,
+                Command::try_from(frame(&["sadd", "myset", member]))
+                    .map_err(|e| AofError::Invalid(e.to_string()))?,
+                Some(command_array(&["sadd", "myset", member])),
+            )
+            .map_err(|e| AofError::Invalid(e.to_string()))?;
+        }
+
+        rewrite(&backend)?;
+        while backend.aof.rewrite_in_progress() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        backend
+            .aof
+            .propagate(command_array(&["sadd", "myset", "d"]));
+
+        let manifest = Manifest::load(&dir, DEFAULT_FILENAME)?.ok_or(AofError::Disabled)?;
+        assert_eq!(
+            manifest.to_string(),
+            "file appendonly.aof.2.base.rdb seq 2 type b\n\
+             file appendonly.aof.2.incr.aof seq 2 type i\n"
+        );
+        assert!(!dir.join("appendonly.aof.1.incr.aof").exists());
+        assert!(!dir.join("appendonly.aof.1.base.rdb").exists());
+
+        let loaded = aof_backend(&dir)?;
+        assert_eq!(loaded.hset.get("myset").map(|s| s.len()), Some(4));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_appendfsync_from_str() {
+        assert_eq!("everysec".parse(), Ok(AppendFsync::EverySec));
+        assert_eq!("ALWAYS".parse(), Ok(AppendFsync::Always));
+        assert!("sometimes".parse::<AppendFsync>().is_err());
+    }
+}
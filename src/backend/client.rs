@@ -0,0 +1,267 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError, RwLock};
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_util::sync::CancellationToken;
+
+use crate::util::now_ms;
+use crate::{RespFrame, TrackingOptions};
+
+/// A connection of the client registry, shared by its session and CLIENT.
+#[derive(Debug)]
+pub struct Client {
+    id: u64,
+    // the addresses of the connection, empty for a client without connection
+    addr: String,
+    laddr: String,
+    // unix time in milliseconds of the connection and of the last command
+    created: u64,
+    last_interaction: AtomicU64,
+    name: RwLock<Option<String>>,
+    // the name of the last command in the statistics
+    last_command: RwLock<Option<String>>,
+    // the number of queued commands in MULTI, -1 outside a transaction
+    multi: AtomicI64,
+    protover: AtomicI64,
+    no_evict: AtomicBool,
+    // the bytes received but not yet processed, and the free space left
+    query_buffer: AtomicU64,
+    query_buffer_free: AtomicU64,
+    // the bytes of the replies not yet sent
+    output_buffer: AtomicU64,
+    killed: CancellationToken,
+    // set by CLIENT TRACKING ON
+    tracking: RwLock<Option<TrackingOptions>>,
+    // CLIENT CACHING yes or no, for the next command only
+    caching: RwLock<Option<bool>>,
+    channels: RwLock<BTreeSet<String>>,
+    // the frames sent out of the replies: invalidation messages, messages of
+    // the channels, the connection takes the receiver
+    pushes: UnboundedSender<RespFrame>,
+    push_receiver: Mutex<Option<UnboundedReceiver<RespFrame>>>,
+}
+
+impl Client {
+    pub fn new(id: u64, addr: impl Into<String>, laddr: impl Into<String>) -> Self {
+        let now = now_ms();
+        let (pushes, push_receiver) = unbounded_channel();
+        Self {
+            id,
+            addr: addr.into(),
+            laddr: laddr.into(),
+            created: now,
+            last_interaction: AtomicU64::new(now),
+            name: RwLock::new(None),
+            last_command: RwLock::new(None),
+            multi: AtomicI64::new(-1),
+            protover: AtomicI64::new(2),
+            no_evict: AtomicBool::new(false),
+            query_buffer: AtomicU64::new(0),
+            query_buffer_free: AtomicU64::new(0),
+            output_buffer: AtomicU64::new(0),
+            killed: CancellationToken::new(),
+            tracking: RwLock::new(None),
+            caching: RwLock::new(None),
+            channels: RwLock::new(BTreeSet::new()),
+            pushes,
+            push_receiver: Mutex::new(Some(push_receiver)),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn laddr(&self) -> &str {
+        &self.laddr
+    }
+
+    /// The seconds since the connection.
+    pub fn age(&self) -> u64 {
+        now_ms().saturating_sub(self.created) / 1000
+    }
+
+    /// The seconds since the last command.
+    pub fn idle(&self) -> u64 {
+        now_ms().saturating_sub(self.last_interaction.load(Ordering::Relaxed)) / 1000
+    }
+
+    pub fn touch(&self) {
+        self.last_interaction.store(now_ms(), Ordering::Relaxed);
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.name
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_name(&self, name: Option<String>) {
+        *self.name.write().unwrap_or_else(PoisonError::into_inner) = name;
+    }
+
+    pub fn last_command(&self) -> Option<String> {
+        self.last_command
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_last_command(&self, name: impl Into<String>) {
+        *self
+            .last_command
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(name.into());
+    }
+
+    pub fn multi(&self) -> i64 {
+        self.multi.load(Ordering::Relaxed)
+    }
+
+    pub fn set_multi(&self, queued: i64) {
+        self.multi.store(queued, Ordering::Relaxed);
+    }
+
+    pub fn protover(&self) -> i64 {
+        self.protover.load(Ordering::Relaxed)
+    }
+
+    pub fn set_protover(&self, protover: i64) {
+        self.protover.store(protover, Ordering::Relaxed);
+    }
+
+    pub fn no_evict(&self) -> bool {
+        self.no_evict.load(Ordering::Relaxed)
+    }
+
+    pub fn set_no_evict(&self, no_evict: bool) {
+        self.no_evict.store(no_evict, Ordering::Relaxed);
+    }
+
+    /// The length and the free space of the query buffer, and the length of
+    /// the output buffer.
+    pub fn buffers(&self) -> (u64, u64, u64) {
+        (
+            self.query_buffer.load(Ordering::Relaxed),
+            self.query_buffer_free.load(Ordering::Relaxed),
+            self.output_buffer.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn set_query_buffer(&self, len: usize, free: usize) {
+        self.query_buffer.store(len as u64, Ordering::Relaxed);
+        self.query_buffer_free.store(free as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_output_buffer(&self, len: usize) {
+        self.output_buffer.store(len as u64, Ordering::Relaxed);
+    }
+
+    /// Asks the connection to close, once the reply being sent is written.
+    pub fn kill(&self) {
+        self.killed.cancel();
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.is_cancelled()
+    }
+
+    /// Completes when the client is killed.
+    pub async fn killed(&self) {
+        self.killed.cancelled().await
+    }
+
+    pub fn tracking(&self) -> Option<TrackingOptions> {
+        self.tracking
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn is_tracking(&self) -> bool {
+        self.tracking
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    pub(crate) fn set_tracking(&self, tracking: Option<TrackingOptions>) {
+        *self
+            .tracking
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = tracking;
+    }
+
+    pub fn set_caching(&self, caching: Option<bool>) {
+        *self.caching.write().unwrap_or_else(PoisonError::into_inner) = caching;
+    }
+
+    /// The CLIENT CACHING of the next command, reset for the following ones.
+    pub fn take_caching(&self) -> Option<bool> {
+        self.caching
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    pub fn channels(&self) -> BTreeSet<String> {
+        self.channels
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn subscriptions(&self) -> usize {
+        self.channels
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_subscribed(&self, channel: &str) -> bool {
+        self.channels
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(channel)
+    }
+
+    /// Subscribes to the channel, returns the number of channels subscribed.
+    pub fn subscribe(&self, channel: impl Into<String>) -> usize {
+        let mut channels = self
+            .channels
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        channels.insert(channel.into());
+        channels.len()
+    }
+
+    /// Unsubscribes from the channel, returns the number of channels left.
+    pub fn unsubscribe(&self, channel: &str) -> usize {
+        let mut channels = self
+            .channels
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        channels.remove(channel);
+        channels.len()
+    }
+
+    /// Queues a frame to be sent to the client ahead of its next reply.
+    pub fn push(&self, frame: RespFrame) {
+        // the receiver is gone with the connection
+        let _ = self.pushes.send(frame);
+    }
+
+    /// The receiver of the pushed frames, taken once by the connection.
+    pub fn take_pushes(&self) -> Option<UnboundedReceiver<RespFrame>> {
+        self.push_receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
@@ -1,8 +1,14 @@
+mod client;
+mod stats;
 mod store;
+mod tracking;
 
 use std::{ops::Deref, sync::Arc};
 
+pub use client::*;
+pub use stats::*;
 pub use store::*;
+pub use tracking::*;
 
 #[derive(Debug, Clone)]
 pub struct Backend(Arc<Store>);
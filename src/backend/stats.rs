@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+
+/// The counters of the server since it started, or since CONFIG RESETSTAT.
+#[derive(Debug, Default)]
+pub struct Stats {
+    connections_received: AtomicU64,
+    commands_processed: AtomicU64,
+    expired_keys: AtomicU64,
+    // error code -> number of error replies
+    errors: DashMap<String, u64>,
+    // command name, `command|subcommand` for a subcommand -> its calls
+    commands: DashMap<String, CommandStats>,
+}
+
+/// The calls of a command, as reported by the commandstats of INFO.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CommandStats {
+    pub calls: u64,
+    // the time spent executing the command in microseconds
+    pub usec: u64,
+    // rejected before being executed, as on a wrong number of arguments
+    pub rejected_calls: u64,
+    // executed with an error reply
+    pub failed_calls: u64,
+}
+
+impl Stats {
+    pub fn connections_received(&self) -> u64 {
+        self.connections_received.load(Ordering::Relaxed)
+    }
+
+    pub fn commands_processed(&self) -> u64 {
+        self.commands_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.expired_keys.load(Ordering::Relaxed)
+    }
+
+    /// The number of error replies of each error code.
+    pub fn errors(&self) -> BTreeMap<String, u64> {
+        self.errors
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect()
+    }
+
+    pub fn error_replies(&self) -> u64 {
+        self.errors.iter().map(|entry| *entry.value()).sum()
+    }
+
+    /// The calls of each command called at least once.
+    pub fn commands(&self) -> BTreeMap<String, CommandStats> {
+        self.commands
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    pub fn incr_connections_received(&self) {
+        self.connections_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn incr_commands_processed(&self) {
+        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn incr_expired_keys(&self) {
+        self.expired_keys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn incr_error_reply(&self, code: &str) {
+        *self.errors.entry(code.to_string()).or_default() += 1;
+    }
+
+    pub fn record_call(&self, name: &str, usec: u64, failed: bool) {
+        let mut stats = self.commands.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        stats.usec += usec;
+        if failed {
+            stats.failed_calls += 1;
+        }
+    }
+
+    pub fn incr_rejected_call(&self, name: &str) {
+        self.commands
+            .entry(name.to_string())
+            .or_default()
+            .rejected_calls += 1;
+    }
+
+    pub fn reset(&self) {
+        self.connections_received.store(0, Ordering::Relaxed);
+        self.commands_processed.store(0, Ordering::Relaxed);
+        self.expired_keys.store(0, Ordering::Relaxed);
+        self.errors.clear();
+        self.commands.clear();
+    }
+}
@@ -1,12 +1,49 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use dashmap::DashMap;
 use dashmap::DashSet;
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::util::{block_in_place, now_ms};
+use crate::{
+    invalidate_channel_message, invalidate_message, redirect_broken_message, used_memory, AofState,
+    Client, ConfigState, CurrentClient, FunctionRegistry, RdbState, RespFrame, ScriptRegistry,
+    Stats, TrackingOptions, TrackingTable, INVALIDATE_CHANNEL,
+};
 
-use crate::RespFrame;
+// how long a waiter of the lock parks before checking for a busy script
+const LOCK_WAIT_INTERVAL: Duration = Duration::from_millis(10);
 
 #[derive(Debug)]
 pub struct Store {
     pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
     pub(crate) hset: DashMap<String, DashSet<RespFrame>>,
+    // key -> unix time in milliseconds when the key expires
+    pub(crate) expires: DashMap<String, u64>,
+    // regular commands share the lock, MULTI/EXEC holds it exclusively so a
+    // transaction is never interleaved with commands from other connections.
+    // The lock is fair, a waiting writer is not starved by the readers.
+    exec_lock: RwLock<()>,
+    // key -> (client id -> dirty flag of that client), used by WATCH
+    pub(crate) watched_keys: DashMap<String, HashMap<u64, Arc<AtomicBool>>>,
+    pub(crate) scripts: ScriptRegistry,
+    pub(crate) functions: FunctionRegistry,
+    pub(crate) rdb: RdbState,
+    pub(crate) aof: AofState,
+    pub(crate) config: ConfigState,
+    pub(crate) stats: Stats,
+    // number of changes since the last save
+    dirty: AtomicU64,
+    next_client_id: AtomicU64,
+    // client id -> the connected client
+    clients: DashMap<u64, Arc<Client>>,
+    tracking: TrackingTable,
+    // a random identifier of this run of the server
+    run_id: String,
+    started: Instant,
 }
 
 impl Default for Store {
@@ -14,6 +51,21 @@ impl Default for Store {
         Self {
             hmap: DashMap::<String, DashMap<String, RespFrame>>::new(),
             hset: DashMap::<String, DashSet<RespFrame>>::new(),
+            expires: DashMap::new(),
+            exec_lock: RwLock::new(()),
+            watched_keys: DashMap::new(),
+            scripts: ScriptRegistry::default(),
+            functions: FunctionRegistry::default(),
+            rdb: RdbState::default(),
+            aof: AofState::default(),
+            config: ConfigState::default(),
+            stats: Stats::default(),
+            dirty: AtomicU64::new(0),
+            next_client_id: AtomicU64::new(1),
+            clients: DashMap::new(),
+            tracking: TrackingTable::default(),
+            run_id: new_run_id(),
+            started: Instant::now(),
         }
     }
 }
@@ -22,4 +74,243 @@ impl Store {
     pub fn new() -> Self {
         Store::default()
     }
+
+    /// Waits for the shared lock, gives up if a script runs past its time limit.
+    pub fn lock_shared(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        self.exec_lock
+            .try_read()
+            .or_else(|| self.wait_lock(|timeout| self.exec_lock.try_read_for(timeout)))
+    }
+
+    /// Waits for the exclusive lock, gives up if a script runs past its time limit.
+    pub fn lock_exclusive(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        self.exec_lock
+            .try_write()
+            .or_else(|| self.wait_lock(|timeout| self.exec_lock.try_write_for(timeout)))
+    }
+
+    // the other connections keep running on the runtime while this one waits
+    fn wait_lock<G>(&self, try_lock_for: impl Fn(Duration) -> Option<G>) -> Option<G> {
+        block_in_place(|| loop {
+            if let Some(guard) = try_lock_for(LOCK_WAIT_INTERVAL) {
+                return Some(guard);
+            }
+            if self.scripts.is_busy() {
+                return None;
+            }
+        })
+    }
+
+    pub fn rdb(&self) -> &RdbState {
+        &self.rdb
+    }
+
+    pub fn aof(&self) -> &AofState {
+        &self.aof
+    }
+
+    pub fn config(&self) -> &ConfigState {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Whether the memory used is over maxmemory, the commands which may add
+    /// data are rejected then.
+    pub fn is_oom(&self) -> bool {
+        let maxmemory = self.config.maxmemory();
+        maxmemory > 0 && used_memory() as u64 > maxmemory
+    }
+
+    pub fn next_client_id(&self) -> u64 {
+        self.next_client_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn register_client(&self, client: Arc<Client>) {
+        self.clients.insert(client.id(), client);
+    }
+
+    pub fn unregister_client(&self, id: u64) {
+        if let Some((_, client)) = self.clients.remove(&id) {
+            self.disable_tracking(&client);
+        }
+    }
+
+    pub fn client(&self, id: u64) -> Option<Arc<Client>> {
+        self.clients.get(&id).map(|client| client.clone())
+    }
+
+    /// The connected clients by id.
+    pub fn clients(&self) -> Vec<Arc<Client>> {
+        let mut clients = self
+            .clients
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect::<Vec<_>>();
+        clients.sort_by_key(|client| client.id());
+        clients
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn tracking(&self) -> &TrackingTable {
+        &self.tracking
+    }
+
+    /// Turns the tracking of the client on, or changes its options.
+    pub fn enable_tracking(&self, client: &Client, options: TrackingOptions) {
+        self.disable_tracking(client);
+        for prefix in &options.prefixes {
+            self.tracking.add_prefix(prefix.clone(), client.id());
+        }
+        // BCAST without prefix broadcasts all the keys
+        if options.bcast && options.prefixes.is_empty() {
+            self.tracking.add_prefix("", client.id());
+        }
+        client.set_tracking(Some(options));
+    }
+
+    /// Turns the tracking of the client off, the keys it read are forgotten
+    /// when next modified.
+    pub fn disable_tracking(&self, client: &Client) {
+        if let Some(options) = client.tracking() {
+            for prefix in &options.prefixes {
+                self.tracking.remove_prefix(prefix, client.id());
+            }
+            self.tracking.remove_prefix("", client.id());
+        }
+        client.set_tracking(None);
+    }
+
+    /// Remembers the keys read by the client, it is told when they are modified.
+    pub fn track_keys(&self, client_id: u64, keys: impl IntoIterator<Item = String>) {
+        for key in keys {
+            self.tracking.track_key(key, client_id);
+        }
+    }
+
+    // as Redis, a RESP2 client only gets the messages of the client it redirects
+    // to, if that one subscribed to the invalidation channel
+    fn invalidate_key(&self, key: &str) {
+        for id in self.tracking.take_clients(key) {
+            let Some(client) = self.client(id) else {
+                continue;
+            };
+            let Some(options) = client.tracking() else {
+                continue;
+            };
+            if options.noloop && CurrentClient::get() == Some(id) {
+                continue;
+            }
+            match options.redirect {
+                Some(redirect) => match self.client(redirect) {
+                    Some(target) if target.protover() >= 3 => target.push(invalidate_message(key)),
+                    Some(target) if target.is_subscribed(INVALIDATE_CHANNEL) => {
+                        target.push(invalidate_channel_message(key))
+                    }
+                    Some(_) => {}
+                    None if client.protover() >= 3 => {
+                        client.push(redirect_broken_message(redirect))
+                    }
+                    None => {}
+                },
+                None if client.protover() >= 3 => client.push(invalidate_message(key)),
+                None => {}
+            }
+        }
+    }
+
+    pub fn watch_key(&self, key: impl Into<String>, client_id: u64, dirty: Arc<AtomicBool>) {
+        self.watched_keys
+            .entry(key.into())
+            .or_default()
+            .insert(client_id, dirty);
+    }
+
+    pub fn unwatch_key(&self, key: &str, client_id: u64) {
+        self.watched_keys.remove_if_mut(key, |_, clients| {
+            clients.remove(&client_id);
+            clients.is_empty()
+        });
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.expire_if_needed(key);
+        self.hset.contains_key(key) || self.hmap.contains_key(key)
+    }
+
+    /// Removes the key whatever its type, returns true if it existed.
+    pub fn remove_key(&self, key: &str) -> bool {
+        self.expires.remove(key);
+        let set = self.hset.remove(key).is_some();
+        let hash = self.hmap.remove(key).is_some();
+        set || hash
+    }
+
+    pub fn expire_at(&self, key: &str) -> Option<u64> {
+        self.expires.get(key).map(|at| *at)
+    }
+
+    pub fn set_expire_at(&self, key: impl Into<String>, at: u64) {
+        self.expires.insert(key.into(), at);
+    }
+
+    /// Deletes the key if its time to live elapsed, keys are expired lazily when accessed.
+    pub fn expire_if_needed(&self, key: &str) -> bool {
+        if self.expire_at(key).is_none_or(|at| at > now_ms()) {
+            return false;
+        }
+        self.remove_key(key);
+        self.signal_modified_key(key);
+        self.stats.incr_expired_keys();
+        true
+    }
+
+    pub fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Subtracts the changes persisted by a save, keeping those made meanwhile.
+    pub fn clear_dirty(&self, saved: u64) {
+        let _ = self
+            .dirty
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dirty| {
+                Some(dirty.saturating_sub(saved))
+            });
+    }
+
+    /// Must be called by every path that modifies a key (writes, expiry, eviction),
+    /// so that transactions watching the key are aborted on EXEC, the clients
+    /// tracking the key are invalidated and the change counts towards the
+    /// `save` points.
+    pub fn signal_modified_key(&self, key: &str) {
+        self.dirty.fetch_add(1, Ordering::Relaxed);
+        if let Some(clients) = self.watched_keys.get(key) {
+            for dirty in clients.values() {
+                dirty.store(true, Ordering::Relaxed);
+            }
+        }
+        self.invalidate_key(key);
+    }
+}
+
+// 40 hex characters as the run id of Redis, from the process and the time
+fn new_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let seed = format!("{}:{}", std::process::id(), nanos);
+    sha1_smol::Sha1::from(seed).digest().to_string()
 }
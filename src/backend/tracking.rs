@@ -0,0 +1,149 @@
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashSet};
+
+use dashmap::DashMap;
+
+use crate::{BulkString, RespArray, RespFrame, RespPush};
+
+/// The channel of the invalidation messages for RESP2 clients redirecting them.
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+thread_local! {
+    // the client executing a command on this thread, for NOLOOP
+    static CURRENT_CLIENT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// The options of CLIENT TRACKING ON.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrackingOptions {
+    // the client receiving the invalidation messages instead of this one
+    pub redirect: Option<u64>,
+    // broadcast the changes of the keys of the prefixes rather than the keys read
+    pub bcast: bool,
+    pub prefixes: Vec<String>,
+    // track the keys read only after CLIENT CACHING yes
+    pub optin: bool,
+    // track the keys read unless after CLIENT CACHING no
+    pub optout: bool,
+    // no invalidation message for the keys modified by the client itself
+    pub noloop: bool,
+}
+
+impl TrackingOptions {
+    /// Whether the keys read by the next command are tracked, given the
+    /// CLIENT CACHING before it.
+    pub fn tracks_reads(&self, caching: Option<bool>) -> bool {
+        if self.bcast {
+            false
+        } else if self.optin {
+            caching == Some(true)
+        } else if self.optout {
+            caching != Some(false)
+        } else {
+            true
+        }
+    }
+}
+
+/// The clients to invalidate when a key is modified.
+#[derive(Debug, Default)]
+pub struct TrackingTable {
+    // key -> the clients which read it since its last invalidation
+    keys: DashMap<String, HashSet<u64>>,
+    // prefix -> the clients broadcasting it, "" for all the keys
+    prefixes: DashMap<String, HashSet<u64>>,
+}
+
+impl TrackingTable {
+    pub fn track_key(&self, key: impl Into<String>, client_id: u64) {
+        self.keys.entry(key.into()).or_default().insert(client_id);
+    }
+
+    pub fn add_prefix(&self, prefix: impl Into<String>, client_id: u64) {
+        self.prefixes
+            .entry(prefix.into())
+            .or_default()
+            .insert(client_id);
+    }
+
+    pub fn remove_prefix(&self, prefix: &str, client_id: u64) {
+        self.prefixes.remove_if_mut(prefix, |_, clients| {
+            clients.remove(&client_id);
+            clients.is_empty()
+        });
+    }
+
+    /// The number of keys tracked and of prefixes broadcast.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.keys.len(), self.prefixes.len())
+    }
+
+    /// The clients to invalidate for the key, which is no longer tracked
+    /// until read again.
+    pub fn take_clients(&self, key: &str) -> BTreeSet<u64> {
+        let mut clients = self
+            .keys
+            .remove(key)
+            .map(|(_, clients)| clients.into_iter().collect::<BTreeSet<_>>())
+            .unwrap_or_default();
+        if !self.prefixes.is_empty() {
+            for entry in self.prefixes.iter() {
+                if key.starts_with(entry.key().as_str()) {
+                    clients.extend(entry.value());
+                }
+            }
+        }
+        clients
+    }
+}
+
+/// Marks the client as executing commands on this thread until the guard is dropped.
+pub struct CurrentClient(Option<u64>);
+
+impl CurrentClient {
+    pub fn set(client_id: u64) -> Self {
+        CurrentClient(CURRENT_CLIENT.replace(Some(client_id)))
+    }
+
+    pub fn get() -> Option<u64> {
+        CURRENT_CLIENT.get()
+    }
+}
+
+impl Drop for CurrentClient {
+    fn drop(&mut self) {
+        CURRENT_CLIENT.set(self.0);
+    }
+}
+
+// ["invalidate", [key]], the message of RESP3 clients
+pub(crate) fn invalidate_message(key: &str) -> RespFrame {
+    RespPush::new(vec![
+        Some(BulkString::from("invalidate")).into(),
+        keys_frame(key),
+    ])
+    .into()
+}
+
+// ["message", "__redis__:invalidate", [key]], published to the channel
+pub(crate) fn invalidate_channel_message(key: &str) -> RespFrame {
+    RespPush::new(vec![
+        Some(BulkString::from("message")).into(),
+        Some(BulkString::from(INVALIDATE_CHANNEL)).into(),
+        keys_frame(key),
+    ])
+    .into()
+}
+
+// ["tracking-redir-broken", id], when the client of REDIRECT is gone
+pub(crate) fn redirect_broken_message(redirect: u64) -> RespFrame {
+    RespPush::new(vec![
+        Some(BulkString::from("tracking-redir-broken")).into(),
+        (redirect as i64).into(),
+    ])
+    .into()
+}
+
+fn keys_frame(key: &str) -> RespFrame {
+    Some(RespArray::new(vec![Some(BulkString::from(key)).into()])).into()
+}
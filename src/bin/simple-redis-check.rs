@@ -0,0 +1,170 @@
+use std::{
+    fs::{self, OpenOptions},
+    path::Path,
+    process::ExitCode,
+};
+
+use anyhow::{bail, Result};
+
+use simple_redis::{
+    check_aof, now_ms, parse_rdb, read_dataset, to_json, write_aof, write_aof_dir, write_rdb, Input,
+};
+
+const USAGE: &str = "Usage: simple-redis-check <command> [options]
+
+Inspects the persistence files of simple-redis without starting a server.
+<path> is an RDB file, an AOF file, or a multi-part AOF given by its directory
+or its manifest.
+
+Commands:
+  check [--fix] <path>       validate an RDB file, or the commands of an AOF;
+                             --fix truncates the AOF at the last valid command
+  keys <path>                print the keys with their type, size and TTL as JSON
+  convert <path> <output>    convert an RDB file to an AOF, or an AOF to an RDB file;
+                             the AOF is written as a multi-part AOF when <output>
+                             is a directory";
+
+const AOF_FILENAME: &str = "appendonly.aof";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let ret = match args.as_slice() {
+        ["check", path] => check(Path::new(path), false),
+        ["check", "--fix", path] | ["check", path, "--fix"] => check(Path::new(path), true),
+        ["keys", path] => keys(Path::new(path)),
+        ["convert", path, output] => convert(Path::new(path), Path::new(output)),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match ret {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// returns false when a file is not valid
+fn check(path: &Path, fix: bool) -> Result<bool> {
+    let files = match Input::resolve(path)? {
+        Input::Rdb(path) => {
+            if fix {
+                bail!("--fix only repairs an AOF");
+            }
+            return check_rdb(&path);
+        }
+        Input::Aof(files) => files,
+    };
+
+    let mut valid = true;
+    for (i, file) in files.iter().enumerate() {
+        let data = fs::read(file)?;
+        if data.starts_with(b"REDIS") {
+            valid &= check_rdb(file)?;
+            continue;
+        }
+
+        let check = check_aof(&data);
+        println!(
+            "AOF analyzed: filename={}, size={}, ok_up_to={}, commands={}, diff={}",
+            file.display(),
+            data.len(),
+            check.valid,
+            check.commands,
+            data.len() - check.valid
+        );
+        let Some(error) = check.error else {
+            println!("AOF {} is valid", file.display());
+            continue;
+        };
+        println!("{}", error);
+        // the files before the last one are never written again, a hole cannot be fixed
+        if !fix || i + 1 < files.len() {
+            println!(
+                "AOF {} is not valid. {}",
+                file.display(),
+                if fix {
+                    "Only the last file of the AOF can be fixed."
+                } else {
+                    "Use the --fix option to try fixing it."
+                }
+            );
+            valid = false;
+            continue;
+        }
+        OpenOptions::new()
+            .write(true)
+            .open(file)?
+            .set_len(check.valid as u64)?;
+        println!("Successfully truncated AOF {}", file.display());
+    }
+    Ok(valid)
+}
+
+fn check_rdb(path: &Path) -> Result<bool> {
+    match parse_rdb(&fs::read(path)?) {
+        Ok(file) => {
+            println!(
+                "RDB {} looks OK: version={}, keys={}, functions={}",
+                path.display(),
+                file.version,
+                file.entries.len(),
+                file.functions.len()
+            );
+            Ok(true)
+        }
+        Err(e) => {
+            println!("RDB {} is not valid: {}", path.display(), e);
+            Ok(false)
+        }
+    }
+}
+
+fn keys(path: &Path) -> Result<bool> {
+    let input = Input::resolve(path)?;
+    let file = read_dataset(&input)?;
+    println!("{}", to_json(&input, &file, now_ms()));
+    Ok(true)
+}
+
+fn convert(path: &Path, output: &Path) -> Result<bool> {
+    let input = Input::resolve(path)?;
+    let file = read_dataset(&input)?;
+    match input {
+        Input::Rdb(_) => {
+            let data = write_aof(&file)?;
+            if output.is_dir() {
+                write_aof_dir(output, AOF_FILENAME, &data)?;
+                println!(
+                    "Converted {} to the AOF {}",
+                    path.display(),
+                    output.join(format!("{}.manifest", AOF_FILENAME)).display()
+                );
+            } else {
+                fs::write(output, data)?;
+                println!(
+                    "Converted {} to the AOF {}",
+                    path.display(),
+                    output.display()
+                );
+            }
+        }
+        Input::Aof(_) => {
+            if output.is_dir() {
+                bail!("{} is a directory", output.display());
+            }
+            fs::write(output, write_rdb(&file))?;
+            println!(
+                "Converted {} to the RDB file {}",
+                path.display(),
+                output.display()
+            );
+        }
+    }
+    Ok(true)
+}
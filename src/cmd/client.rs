@@ -0,0 +1,554 @@
+use crate::{
+    Backend, BulkString, Client, CommandError, CommandExecutor, ExecError, RespArray, RespFrame,
+    TrackingOptions,
+};
+
+use super::{extract_args, script::to_string, validate_command, RESP_OK};
+
+// CLIENT acts on the connection calling it, it is intercepted by the
+// `Session` before reaching the executor.
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientCmd {
+    Id,
+    Info,
+    // the clients of the ids, all the clients if empty, none if no client
+    // has the type
+    List { ids: Vec<u64>, none: bool },
+    Kill(KillFilter),
+    SetName(String),
+    GetName,
+    NoEvict(bool),
+    // the options of TRACKING ON, None for TRACKING OFF
+    Tracking(Option<TrackingOptions>),
+    Caching(bool),
+}
+
+/// The clients to kill: `CLIENT KILL addr`, or the clients matching all the
+/// filters of `CLIENT KILL [ID id] [ADDR addr] [USER user] [SKIPME yes|no]`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct KillFilter {
+    pub id: Option<u64>,
+    pub addr: Option<String>,
+    pub user: Option<String>,
+    pub skipme: bool,
+    // the old form replies OK or an error rather than the number of clients
+    pub legacy: bool,
+}
+
+impl CommandExecutor for ClientCmd {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err(
+            "CLIENT is only allowed on a client connection",
+        ))
+    }
+}
+
+impl ClientCmd {
+    /// Executes the subcommand on behalf of the client.
+    pub fn execute_for(self, backend: &Backend, client: &Client) -> Result<RespFrame, ExecError> {
+        match self {
+            ClientCmd::Id => Ok((client.id() as i64).into()),
+            ClientCmd::Info => Ok(Some(BulkString::from(client_info(client))).into()),
+            ClientCmd::List { ids, none } => {
+                let list = backend
+                    .clients()
+                    .iter()
+                    .filter(|c| !none && (ids.is_empty() || ids.contains(&c.id())))
+                    .map(|c| client_info(c))
+                    .collect::<String>();
+                Ok(Some(BulkString::from(list)).into())
+            }
+            ClientCmd::Kill(filter) => {
+                if let Some(ref user) = filter.user {
+                    // there are no ACL users but the default user
+                    if user != "default" {
+                        return Err(ExecError::err(format!("No such user '{}'", user)));
+                    }
+                }
+                let mut killed = 0;
+                for c in backend.clients() {
+                    let skip = filter.id.is_some_and(|id| id != c.id())
+                        || filter.addr.as_ref().is_some_and(|addr| addr != c.addr())
+                        || (filter.skipme && c.id() == client.id());
+                    if !skip {
+                        c.kill();
+                        killed += 1;
+                    }
+                }
+                match filter.legacy {
+                    true if killed == 0 => Err(ExecError::err("No such client")),
+                    true => Ok(RESP_OK.clone()),
+                    false => Ok(killed.into()),
+                }
+            }
+            ClientCmd::SetName(name) => {
+                check_name(&name)?;
+                client.set_name((!name.is_empty()).then_some(name));
+                Ok(RESP_OK.clone())
+            }
+            ClientCmd::GetName => Ok(client.name().map(BulkString::from).into()),
+            ClientCmd::NoEvict(no_evict) => {
+                client.set_no_evict(no_evict);
+                Ok(RESP_OK.clone())
+            }
+            ClientCmd::Tracking(Some(mut options)) => {
+                if let Some(redirect) = options.redirect {
+                    if backend.client(redirect).is_none() {
+                        return Err(ExecError::err(
+                            "The client ID you want redirect to does not exist",
+                        ));
+                    }
+                }
+                if let Some(current) = client.tracking() {
+                    if current.bcast != options.bcast {
+                        return Err(ExecError::err(
+                            "You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.",
+                        ));
+                    }
+                    if (options.optin && current.optout) || (options.optout && current.optin) {
+                        return Err(ExecError::err(
+                            "You can't switch OPTIN/OPTOUT mode before disabling tracking for this client, and then re-enabling it with a different mode.",
+                        ));
+                    }
+                    // as Redis, the prefixes are added to those already broadcast
+                    let mut prefixes = current.prefixes;
+                    for prefix in options.prefixes {
+                        if !prefixes.contains(&prefix) {
+                            prefixes.push(prefix);
+                        }
+                    }
+                    options.prefixes = prefixes;
+                }
+                check_prefixes(&options.prefixes)?;
+                backend.enable_tracking(client, options);
+                Ok(RESP_OK.clone())
+            }
+            ClientCmd::Tracking(None) => {
+                backend.disable_tracking(client);
+                Ok(RESP_OK.clone())
+            }
+            ClientCmd::Caching(caching) => {
+                let options = client.tracking().unwrap_or_default();
+                if !options.optin && !options.optout {
+                    return Err(ExecError::err(
+                        "CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled",
+                    ));
+                }
+                if caching && !options.optin {
+                    return Err(ExecError::err(
+                        "CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode.",
+                    ));
+                }
+                if !caching && !options.optout {
+                    return Err(ExecError::err(
+                        "CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.",
+                    ));
+                }
+                client.set_caching(Some(caching));
+                Ok(RESP_OK.clone())
+            }
+        }
+    }
+}
+
+/// Client names are shown in CLIENT LIST, they cannot hold spaces or special characters.
+pub(crate) fn check_name(name: &str) -> Result<(), ExecError> {
+    if !name.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+        return Err(ExecError::err(
+            "Client names cannot contain spaces, newlines or special characters.",
+        ));
+    }
+    Ok(())
+}
+
+// a key would be invalidated twice by overlapping prefixes
+fn check_prefixes(prefixes: &[String]) -> Result<(), ExecError> {
+    for (i, prefix) in prefixes.iter().enumerate() {
+        for other in &prefixes[i + 1..] {
+            if prefix.starts_with(other.as_str()) || other.starts_with(prefix.as_str()) {
+                return Err(ExecError::err(format!(
+                    "Prefix '{}' overlaps with an existing prefix '{}'. Prefixes for a single client must not overlap.",
+                    other, prefix
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// a line of CLIENT LIST, with the fields of Redis that apply
+fn client_info(client: &Client) -> String {
+    let mut flags = String::new();
+    if client.multi() >= 0 {
+        flags.push('x');
+    }
+    if client.no_evict() {
+        flags.push('e');
+    }
+    if let Some(tracking) = client.tracking() {
+        flags.push('t');
+        if tracking.bcast {
+            flags.push('B');
+        }
+    }
+    if flags.is_empty() {
+        flags.push('N');
+    }
+    let (qbuf, qbuf_free, omem) = client.buffers();
+    format!(
+        "id={} addr={} laddr={} name={} age={} idle={} flags={} db=0 sub={} multi={} qbuf={} qbuf-free={} omem={} cmd={} user=default resp={}\n",
+        client.id(),
+        client.addr(),
+        client.laddr(),
+        client.name().unwrap_or_default(),
+        client.age(),
+        client.idle(),
+        flags,
+        client.subscriptions(),
+        client.multi(),
+        qbuf,
+        qbuf_free,
+        omem,
+        client.last_command().as_deref().unwrap_or("NULL"),
+        client.protover()
+    )
+}
+
+impl TryFrom<RespArray> for ClientCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = match value.get(1) {
+            Some(RespFrame::BulkString(Some(sub))) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "client command must have a subcommand".to_string(),
+                ))
+            }
+        };
+
+        match sub.as_slice() {
+            b"id" => {
+                validate_command(&value, &["client", "id"])?;
+                Ok(ClientCmd::Id)
+            }
+            b"info" => {
+                validate_command(&value, &["client", "info"])?;
+                Ok(ClientCmd::Info)
+            }
+            b"list" => {
+                validate_command(&value, &["client", "list"])?;
+                parse_list(extract_args(value, 2)?)
+            }
+            b"kill" => {
+                validate_command(&value, &["client", "kill"])?;
+                parse_kill(extract_args(value, 2)?)
+            }
+            b"setname" => {
+                validate_command(&value, &["client", "setname"])?;
+                let name = to_string(extract_args(value, 2)?.pop())?;
+                Ok(ClientCmd::SetName(name))
+            }
+            b"getname" => {
+                validate_command(&value, &["client", "getname"])?;
+                Ok(ClientCmd::GetName)
+            }
+            b"no-evict" => {
+                validate_command(&value, &["client", "no-evict"])?;
+                let mode = to_string(extract_args(value, 2)?.pop())?;
+                match mode.to_ascii_lowercase().as_str() {
+                    "on" => Ok(ClientCmd::NoEvict(true)),
+                    "off" => Ok(ClientCmd::NoEvict(false)),
+                    _ => Err(syntax_error()),
+                }
+            }
+            b"tracking" => {
+                validate_command(&value, &["client", "tracking"])?;
+                parse_tracking(extract_args(value, 2)?)
+            }
+            b"caching" => {
+                validate_command(&value, &["client", "caching"])?;
+                let mode = to_string(extract_args(value, 2)?.pop())?;
+                match mode.to_ascii_lowercase().as_str() {
+                    "yes" => Ok(ClientCmd::Caching(true)),
+                    "no" => Ok(ClientCmd::Caching(false)),
+                    _ => Err(syntax_error()),
+                }
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'",
+                String::from_utf8_lossy(&sub)
+            ))),
+        }
+    }
+}
+
+// CLIENT TRACKING on|off [REDIRECT id] [PREFIX prefix ...] [BCAST] [OPTIN] [OPTOUT] [NOLOOP]
+fn parse_tracking(args: Vec<RespFrame>) -> Result<ClientCmd, CommandError> {
+    let mut args = args.into_iter();
+    let on = match to_string(args.next())?.to_ascii_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => return Err(syntax_error()),
+    };
+
+    let mut options = TrackingOptions::default();
+    while let Some(option) = args.next() {
+        match to_string(Some(option))?.to_ascii_lowercase().as_str() {
+            "redirect" if args.len() >= 1 => {
+                let redirect = to_string(args.next())?.parse::<u64>().map_err(|_| {
+                    CommandError::InvalidArgument(
+                        "value is not an integer or out of range".to_string(),
+                    )
+                })?;
+                options.redirect = Some(redirect);
+            }
+            "prefix" if args.len() >= 1 => options.prefixes.push(to_string(args.next())?),
+            "bcast" => options.bcast = true,
+            "optin" => options.optin = true,
+            "optout" => options.optout = true,
+            "noloop" => options.noloop = true,
+            _ => return Err(syntax_error()),
+        }
+    }
+    if !on {
+        return Ok(ClientCmd::Tracking(None));
+    }
+
+    let invalid = |message: &str| Err(CommandError::InvalidArgument(message.to_string()));
+    if !options.bcast && !options.prefixes.is_empty() {
+        return invalid("PREFIX option requires BCAST mode to be enabled");
+    }
+    if options.bcast && (options.optin || options.optout) {
+        return invalid("OPTIN and OPTOUT are not compatible with BCAST");
+    }
+    if options.optin && options.optout {
+        return invalid("You can't use OPTIN and OPTOUT at the same time");
+    }
+    Ok(ClientCmd::Tracking(Some(options)))
+}
+
+// CLIENT LIST [TYPE normal|master|replica|pubsub] [ID id [id ...]]
+fn parse_list(args: Vec<RespFrame>) -> Result<ClientCmd, CommandError> {
+    let mut args = args.into_iter();
+    let mut ids = Vec::new();
+    let mut none = false;
+    while let Some(arg) = args.next() {
+        match to_string(Some(arg))?.to_ascii_lowercase().as_str() {
+            "type" if args.len() >= 1 => {
+                let kind = to_string(args.next())?;
+                match kind.to_ascii_lowercase().as_str() {
+                    // all the clients are normal clients
+                    "normal" => {}
+                    "master" | "replica" | "slave" | "pubsub" => none = true,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(format!(
+                            "Unknown client type '{}'",
+                            kind
+                        )))
+                    }
+                }
+            }
+            "id" if args.len() >= 1 => {
+                for id in args.by_ref() {
+                    ids.push(parse_id(id, "Invalid client ID")?);
+                }
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok(ClientCmd::List { ids, none })
+}
+
+fn parse_kill(args: Vec<RespFrame>) -> Result<ClientCmd, CommandError> {
+    let mut filter = KillFilter {
+        id: None,
+        addr: None,
+        user: None,
+        skipme: true,
+        legacy: false,
+    };
+    // CLIENT KILL addr, the caller is not skipped
+    if args.len() == 1 {
+        filter.addr = Some(to_string(args.into_iter().next())?);
+        filter.skipme = false;
+        filter.legacy = true;
+        return Ok(ClientCmd::Kill(filter));
+    }
+    if !args.len().is_multiple_of(2) {
+        return Err(syntax_error());
+    }
+
+    let mut args = args.into_iter();
+    while let Some(option) = args.next() {
+        let option = to_string(Some(option))?;
+        match option.to_ascii_lowercase().as_str() {
+            "id" => filter.id = Some(parse_id(args.next(), "client-id should be greater than 0")?),
+            "addr" => filter.addr = Some(to_string(args.next())?),
+            "user" => filter.user = Some(to_string(args.next())?),
+            "skipme" => {
+                filter.skipme = match to_string(args.next())?.to_ascii_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(syntax_error()),
+                }
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok(ClientCmd::Kill(filter))
+}
+
+fn parse_id(arg: impl Into<Option<RespFrame>>, error: &str) -> Result<u64, CommandError> {
+    match to_string(arg.into())?.parse::<u64>() {
+        Ok(id) if id > 0 => Ok(id),
+        _ => Err(CommandError::InvalidArgument(error.to_string())),
+    }
+}
+
+fn syntax_error() -> CommandError {
+    CommandError::InvalidArgument("syntax error".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_util;
+    use crate::Command;
+    use anyhow::Result;
+
+    fn cmd(args: &[&str]) -> Result<ClientCmd> {
+        match test_util::cmd(args)? {
+            Command::ClientCmd(cmd) => Ok(cmd),
+            cmd => anyhow::bail!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn test_client_try_from() -> Result<()> {
+        assert_eq!(cmd(&["client", "ID"])?, ClientCmd::Id);
+        assert_eq!(
+            cmd(&["client", "list", "type", "normal", "id", "1", "2"])?,
+            ClientCmd::List {
+                ids: vec![1, 2],
+                none: false
+            }
+        );
+        assert_eq!(
+            cmd(&["client", "kill", "127.0.0.1:5000"])?,
+            ClientCmd::Kill(KillFilter {
+                id: None,
+                addr: Some("127.0.0.1:5000".to_string()),
+                user: None,
+                skipme: false,
+                legacy: true
+            })
+        );
+        assert_eq!(
+            cmd(&["client", "kill", "id", "3", "skipme", "no"])?,
+            ClientCmd::Kill(KillFilter {
+                id: Some(3),
+                addr: None,
+                user: None,
+                skipme: false,
+                legacy: false
+            })
+        );
+        assert_eq!(
+            cmd(&["client", "no-evict", "ON"])?,
+            ClientCmd::NoEvict(true)
+        );
+
+        for args in [
+            &["client", "kill", "id", "0"][..],
+            &["client", "kill", "id", "1", "skipme"],
+            &["client", "kill", "foo", "bar"],
+            &["client", "list", "type", "foo"],
+            &["client", "list", "id", "x"],
+            &["client", "no-evict", "maybe"],
+            &["client", "tracking", "maybe"],
+            &["client", "tracking", "on", "prefix", "a"],
+            &["client", "tracking", "on", "bcast", "optin"],
+            &["client", "tracking", "on", "optin", "optout"],
+            &["client", "tracking", "on", "redirect", "x"],
+            &["client", "caching", "maybe"],
+            &["client", "setname"],
+            &["client", "foo"],
+        ] {
+            assert!(cmd(args).is_err(), "{:?}", args);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_kill() -> Result<()> {
+        let backend = Backend::new();
+        let clients = (1..=3)
+            .map(|id| {
+                Arc::new(Client::new(
+                    id,
+                    format!("127.0.0.1:{}", id),
+                    "127.0.0.1:6379",
+                ))
+            })
+            .collect::<Vec<_>>();
+        for client in &clients {
+            backend.register_client(client.clone());
+        }
+        let me = &clients[0];
+
+        let ret = cmd(&["client", "kill", "127.0.0.1:9"])?.execute_for(&backend, me);
+        assert_eq!(ret, Err(ExecError::err("No such client")));
+        let ret = cmd(&["client", "kill", "user", "foo"])?.execute_for(&backend, me);
+        assert_eq!(ret, Err(ExecError::err("No such user 'foo'")));
+
+        let ret = cmd(&["client", "kill", "addr", "127.0.0.1:2"])?.execute_for(&backend, me)?;
+        assert_eq!(ret, 1.into());
+        assert!(clients[1].is_killed());
+        // the caller is skipped unless SKIPME no
+        let ret = cmd(&["client", "kill", "user", "default"])?.execute_for(&backend, me)?;
+        assert_eq!(ret, 2.into());
+        assert!(!me.is_killed());
+        let ret = cmd(&["client", "kill", "127.0.0.1:1"])?.execute_for(&backend, me)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(me.is_killed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_list() -> Result<()> {
+        let backend = Backend::new();
+        let client = Arc::new(Client::new(7, "127.0.0.1:5000", "127.0.0.1:6379"));
+        backend.register_client(client.clone());
+        backend.register_client(Arc::new(Client::new(8, "127.0.0.1:5001", "127.0.0.1:6379")));
+
+        let ret = cmd(&["client", "setname", "app"])?.execute_for(&backend, &client)?;
+        assert_eq!(ret, RESP_OK.clone());
+        let ret = cmd(&["client", "setname", "a b"])?.execute_for(&backend, &client);
+        assert!(ret.is_err());
+        let ret = cmd(&["client", "getname"])?.execute_for(&backend, &client)?;
+        assert_eq!(ret, Some(BulkString::from("app")).into());
+        cmd(&["client", "no-evict", "on"])?.execute_for(&backend, &client)?;
+        client.set_last_command("client|info");
+
+        let ret = cmd(&["client", "info"])?.execute_for(&backend, &client)?;
+        let expected = "id=7 addr=127.0.0.1:5000 laddr=127.0.0.1:6379 name=app age=0 idle=0 flags=e db=0 sub=0 multi=-1 qbuf=0 qbuf-free=0 omem=0 cmd=client|info user=default resp=2\n";
+        assert_eq!(ret, Some(BulkString::from(expected)).into());
+
+        let list = |args: &[&str]| -> Result<Vec<String>> {
+            match cmd(args)?.execute_for(&backend, &client)? {
+                RespFrame::BulkString(Some(list)) => Ok(String::from_utf8(list.to_vec())?
+                    .lines()
+                    .map(|line| line.split(' ').next().unwrap_or_default().to_string())
+                    .collect()),
+                frame => anyhow::bail!("unexpected reply: {:?}", frame),
+            }
+        };
+        assert_eq!(list(&["client", "list"])?, ["id=7", "id=8"]);
+        assert_eq!(list(&["client", "list", "id", "8", "9"])?, ["id=8"]);
+        assert!(list(&["client", "list", "type", "pubsub"])?.is_empty());
+        Ok(())
+    }
+}
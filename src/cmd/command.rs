@@ -0,0 +1,533 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    glob_match, Backend, BulkString, CommandError, CommandExecutor, ExecError, RespArray,
+    RespFrame, RespNull, RespSet, SimpleString,
+};
+
+use super::{
+    command_table, extract_args, lookup_command, script::to_string, validate_command, CommandSpec,
+    FindKeys, KeySpec,
+};
+
+// COMMAND, the introspection of the command table
+#[derive(Debug)]
+pub enum CommandCmd {
+    // the commands of the names, all the commands without names
+    Info(Vec<String>),
+    Count,
+    Docs(Vec<String>),
+    List(Option<ListFilter>),
+    GetKeys(RespArray),
+    GetKeysAndFlags(RespArray),
+}
+
+// COMMAND LIST FILTERBY <MODULE module-name | ACLCAT category | PATTERN pattern>
+#[derive(Debug)]
+pub enum ListFilter {
+    Module(String),
+    AclCat(String),
+    Pattern(String),
+}
+
+impl CommandExecutor for CommandCmd {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        let frame = match self {
+            CommandCmd::Info(names) if names.is_empty() => {
+                let infos = command_table()
+                    .iter()
+                    .map(|spec| command_info(spec, None))
+                    .collect::<Vec<RespFrame>>();
+                Some(RespArray::new(infos)).into()
+            }
+            CommandCmd::Info(names) => {
+                let infos = names
+                    .iter()
+                    .map(|name| match find(name) {
+                        Some((spec, parent)) => command_info(spec, parent),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<RespFrame>>();
+                Some(RespArray::new(infos)).into()
+            }
+            CommandCmd::Count => (command_table().len() as i64).into(),
+            CommandCmd::Docs(names) => {
+                let mut docs = BTreeMap::new();
+                if names.is_empty() {
+                    for spec in command_table() {
+                        docs.insert(bulk(spec.name), command_docs(spec, None));
+                    }
+                }
+                // the unknown commands are skipped
+                for name in names {
+                    if let Some((spec, parent)) = find(&name) {
+                        docs.insert(bulk(&full_name(spec, parent)), command_docs(spec, parent));
+                    }
+                }
+                RespFrame::Map(docs)
+            }
+            CommandCmd::List(filter) => {
+                let mut names = Vec::new();
+                for spec in command_table() {
+                    let subcommands = spec.subcommands.iter().map(|sub| (sub, Some(spec)));
+                    for (spec, parent) in std::iter::once((spec, None)).chain(subcommands) {
+                        let name = full_name(spec, parent);
+                        let group = parent.map_or(spec.group, |parent| parent.group);
+                        let matched = match filter {
+                            None => true,
+                            // there are no modules
+                            Some(ListFilter::Module(_)) => false,
+                            Some(ListFilter::AclCat(ref category)) => acl_categories(spec, group)
+                                .iter()
+                                .any(|c| c[1..].eq_ignore_ascii_case(category)),
+                            Some(ListFilter::Pattern(ref pattern)) => {
+                                glob_match(pattern.as_bytes(), name.as_bytes(), true)
+                            }
+                        };
+                        if matched {
+                            names.push(bulk(&name));
+                        }
+                    }
+                }
+                Some(RespArray::new(names)).into()
+            }
+            CommandCmd::GetKeys(args) => {
+                let keys = get_keys(&args)?
+                    .into_iter()
+                    .map(|(i, _)| args[i].clone())
+                    .collect::<Vec<RespFrame>>();
+                Some(RespArray::new(keys)).into()
+            }
+            CommandCmd::GetKeysAndFlags(args) => {
+                let keys = get_keys(&args)?
+                    .into_iter()
+                    .map(|(i, flags)| {
+                        Some(RespArray::new(vec![args[i].clone(), set(flags)])).into()
+                    })
+                    .collect::<Vec<RespFrame>>();
+                Some(RespArray::new(keys)).into()
+            }
+        };
+        Ok(frame)
+    }
+}
+
+fn bulk(s: &str) -> RespFrame {
+    Some(BulkString::from(s)).into()
+}
+
+fn set(items: &[&str]) -> RespFrame {
+    RespSet::new(
+        items
+            .iter()
+            .map(|item| SimpleString::new(*item).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn map(entries: Vec<(&str, RespFrame)>) -> RespFrame {
+    RespFrame::Map(
+        entries
+            .into_iter()
+            .map(|(key, value)| (bulk(key), value))
+            .collect(),
+    )
+}
+
+// the command of a name, or the subcommand of a "command|subcommand" name,
+// with its command
+fn find(name: &str) -> Option<(&'static CommandSpec, Option<&'static CommandSpec>)> {
+    match name.split_once('|') {
+        None => lookup_command(name.as_bytes()).map(|spec| (spec, None)),
+        Some((name, sub)) => {
+            let parent = lookup_command(name.as_bytes())?;
+            let spec = parent
+                .subcommands
+                .iter()
+                .find(|spec| spec.name.eq_ignore_ascii_case(sub))?;
+            Some((spec, Some(parent)))
+        }
+    }
+}
+
+fn full_name(spec: &CommandSpec, parent: Option<&CommandSpec>) -> String {
+    match parent {
+        Some(parent) => format!("{}|{}", parent.name, spec.name),
+        None => spec.name.to_string(),
+    }
+}
+
+// the ACL categories of a command follow from its flags and its group
+fn acl_categories(spec: &CommandSpec, group: &str) -> Vec<&'static str> {
+    let mut categories = Vec::new();
+    if spec.has_flag("write") {
+        categories.push("@write");
+    }
+    if spec.has_flag("readonly") {
+        categories.push("@read");
+    }
+    if spec.has_flag("admin") {
+        categories.extend(["@admin", "@dangerous"]);
+    }
+    categories.push(if spec.has_flag("fast") {
+        "@fast"
+    } else {
+        "@slow"
+    });
+    match group {
+        "hash" => categories.push("@hash"),
+        "set" => categories.push("@set"),
+        "scripting" => categories.push("@scripting"),
+        "transactions" => categories.push("@transaction"),
+        "connection" => categories.push("@connection"),
+        "generic" => categories.push("@keyspace"),
+        _ => {}
+    }
+    categories
+}
+
+// name, arity, flags, first key, last key, step, ACL categories, tips, key
+// specifications and subcommands, as the reply of Redis 7
+fn command_info(spec: &CommandSpec, parent: Option<&CommandSpec>) -> RespFrame {
+    let mut flags = spec.flags.to_vec();
+    if spec.movable_keys() {
+        flags.push("movablekeys");
+    }
+    let group = parent.map_or(spec.group, |parent| parent.group);
+    let (first_key, last_key, step) = spec.key_range();
+    let key_specs = spec
+        .key_specs
+        .iter()
+        .map(key_spec)
+        .collect::<Vec<RespFrame>>();
+    let subcommands = spec
+        .subcommands
+        .iter()
+        .map(|sub| command_info(sub, Some(spec)))
+        .collect::<Vec<RespFrame>>();
+
+    Some(RespArray::new(vec![
+        bulk(&full_name(spec, parent)),
+        spec.arity.into(),
+        set(&flags),
+        first_key.into(),
+        last_key.into(),
+        step.into(),
+        set(&acl_categories(spec, group)),
+        set(&[]),
+        Some(RespArray::new(key_specs)).into(),
+        Some(RespArray::new(subcommands)).into(),
+    ]))
+    .into()
+}
+
+fn key_spec(spec: &KeySpec) -> RespFrame {
+    let begin_search = map(vec![
+        ("type", bulk("index")),
+        (
+            "spec",
+            map(vec![("index", (spec.begin_search as i64).into())]),
+        ),
+    ]);
+    let find_keys = match spec.find_keys {
+        FindKeys::Range { lastkey, keystep } => map(vec![
+            ("type", bulk("range")),
+            (
+                "spec",
+                map(vec![
+                    ("lastkey", lastkey.into()),
+                    ("keystep", (keystep as i64).into()),
+                    ("limit", 0.into()),
+                ]),
+            ),
+        ]),
+        FindKeys::KeyNum {
+            keynumidx,
+            firstkey,
+            keystep,
+        } => map(vec![
+            ("type", bulk("keynum")),
+            (
+                "spec",
+                map(vec![
+                    ("keynumidx", (keynumidx as i64).into()),
+                    ("firstkey", (firstkey as i64).into()),
+                    ("keystep", (keystep as i64).into()),
+                ]),
+            ),
+        ]),
+    };
+    map(vec![
+        ("flags", set(spec.flags)),
+        ("begin_search", begin_search),
+        ("find_keys", find_keys),
+    ])
+}
+
+fn command_docs(spec: &CommandSpec, parent: Option<&CommandSpec>) -> RespFrame {
+    let group = parent.map_or(spec.group, |parent| parent.group);
+    let mut docs = vec![
+        ("summary", bulk(spec.summary)),
+        ("since", bulk(spec.since)),
+        ("group", bulk(group)),
+        ("complexity", bulk(spec.complexity)),
+    ];
+    if !spec.subcommands.is_empty() {
+        let subcommands = spec
+            .subcommands
+            .iter()
+            .map(|sub| {
+                (
+                    bulk(&full_name(sub, Some(spec))),
+                    command_docs(sub, Some(spec)),
+                )
+            })
+            .collect();
+        docs.push(("subcommands", RespFrame::Map(subcommands)));
+    }
+    map(docs)
+}
+
+// the positions of the keys of a command and their flags, for COMMAND GETKEYS
+fn get_keys(args: &RespArray) -> Result<Vec<(usize, &'static [&'static str])>, ExecError> {
+    let spec = match args.first() {
+        Some(RespFrame::BulkString(Some(name))) => lookup_command(name),
+        _ => None,
+    }
+    .ok_or_else(|| ExecError::err("Invalid command specified"))?;
+    if spec.subcommands.is_empty() && spec.key_specs.is_empty() {
+        return Err(ExecError::err("The command has no key arguments"));
+    }
+    let spec = match spec.check(args) {
+        Ok(spec) => spec,
+        Err(CommandError::InvalidCommand(_)) => {
+            return Err(ExecError::err("Invalid command specified"))
+        }
+        Err(_) => {
+            return Err(ExecError::err(
+                "Invalid number of arguments specified for command",
+            ))
+        }
+    };
+    if spec.key_specs.is_empty() {
+        return Err(ExecError::err("The command has no key arguments"));
+    }
+
+    match spec.keys_of(args) {
+        Ok(keys) if !keys.is_empty() || spec.has_flag("no_mandatory_keys") => Ok(keys),
+        _ => Err(ExecError::err("Invalid arguments specified for command")),
+    }
+}
+
+impl TryFrom<RespArray> for CommandCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() == 1 {
+            validate_command(&value, &["command"])?;
+            return Ok(CommandCmd::Info(vec![]));
+        }
+        let sub = match value.get(1) {
+            Some(RespFrame::BulkString(Some(sub))) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "command command must have a subcommand".to_string(),
+                ))
+            }
+        };
+
+        match sub.as_slice() {
+            b"count" => {
+                validate_command(&value, &["command", "count"])?;
+                Ok(CommandCmd::Count)
+            }
+            b"info" | b"docs" => {
+                let info = sub == b"info";
+                validate_command(&value, &["command", if info { "info" } else { "docs" }])?;
+                let names = extract_args(value, 2)?
+                    .into_iter()
+                    .map(|arg| to_string(Some(arg)))
+                    .collect::<Result<Vec<String>, CommandError>>()?;
+                Ok(if info {
+                    CommandCmd::Info(names)
+                } else {
+                    CommandCmd::Docs(names)
+                })
+            }
+            b"list" => {
+                validate_command(&value, &["command", "list"])?;
+                let args = extract_args(value, 2)?
+                    .into_iter()
+                    .map(|arg| to_string(Some(arg)))
+                    .collect::<Result<Vec<String>, CommandError>>()?;
+                let filter = match args.as_slice() {
+                    [] => None,
+                    [filterby, kind, arg] if filterby.eq_ignore_ascii_case("filterby") => {
+                        match kind.to_ascii_lowercase().as_str() {
+                            "module" => Some(ListFilter::Module(arg.clone())),
+                            "aclcat" => Some(ListFilter::AclCat(arg.clone())),
+                            "pattern" => Some(ListFilter::Pattern(arg.clone())),
+                            _ => {
+                                return Err(CommandError::InvalidArgument(
+                                    "syntax error".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                };
+                Ok(CommandCmd::List(filter))
+            }
+            b"getkeys" => {
+                validate_command(&value, &["command", "getkeys"])?;
+                Ok(CommandCmd::GetKeys(RespArray::new(extract_args(value, 2)?)))
+            }
+            b"getkeysandflags" => {
+                validate_command(&value, &["command", "getkeysandflags"])?;
+                Ok(CommandCmd::GetKeysAndFlags(RespArray::new(extract_args(
+                    value, 2,
+                )?)))
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'",
+                String::from_utf8_lossy(&sub)
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RespArray, RespFrame};
+
+    use super::*;
+    use crate::test_util::cmd;
+    use anyhow::Result;
+
+    fn command(args: &[&str]) -> Result<RespFrame> {
+        Ok(cmd(args)?.execute(&Backend::new())?)
+    }
+
+    fn elements(frame: RespFrame) -> Vec<RespFrame> {
+        match frame {
+            RespFrame::Array(Some(elements)) => elements.0,
+            frame => panic!("not an array: {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_command_info() -> Result<()> {
+        assert_eq!(
+            command(&["COMMAND", "COUNT"])?,
+            (command_table().len() as i64).into()
+        );
+        assert_eq!(
+            elements(command(&["command"])?).len(),
+            command_table().len()
+        );
+
+        let infos = elements(command(&[
+            "command",
+            "info",
+            "HMGET",
+            "nosuch",
+            "script|load",
+        ])?);
+        assert_eq!(infos.len(), 3);
+        let hmget = elements(infos[0].clone());
+        assert_eq!(hmget.len(), 10);
+        assert_eq!(hmget[0], bulk("hmget"));
+        assert_eq!(hmget[1], (-3).into());
+        assert_eq!(hmget[2], set(&["readonly", "fast"]));
+        assert_eq!(&hmget[3..6], &[1.into(), 1.into(), 1.into()]);
+        assert_eq!(hmget[6], set(&["@read", "@fast", "@hash"]));
+        assert_eq!(elements(hmget[8].clone()).len(), 1);
+        assert_eq!(infos[1], RespFrame::Null(RespNull));
+        assert_eq!(elements(infos[2].clone())[0], bulk("script|load"));
+
+        let eval = elements(elements(command(&["command", "info", "eval"])?).remove(0));
+        assert_eq!(
+            eval[2],
+            set(&[
+                "noscript",
+                "skip_monitor",
+                "may_replicate",
+                "no_mandatory_keys",
+                "stale",
+                "movablekeys"
+            ])
+        );
+        assert_eq!(&eval[3..6], &[0.into(), 0.into(), 0.into()]);
+
+        let docs = command(&["command", "docs", "echo", "nosuch"])?;
+        let RespFrame::Map(docs) = docs else {
+            panic!("not a map")
+        };
+        assert_eq!(docs.len(), 1);
+        assert_eq!(
+            docs[&bulk("echo")],
+            map(vec![
+                ("summary", bulk("Returns the given string.")),
+                ("since", bulk("1.0.0")),
+                ("group", bulk("connection")),
+                ("complexity", bulk("O(1)")),
+            ])
+        );
+
+        let names = elements(command(&[
+            "command", "list", "filterby", "pattern", "script*",
+        ])?);
+        assert_eq!(
+            names,
+            [
+                "script",
+                "script|load",
+                "script|exists",
+                "script|flush",
+                "script|kill"
+            ]
+            .map(bulk)
+            .to_vec()
+        );
+        let names = elements(command(&["command", "list", "filterby", "aclcat", "set"])?);
+        assert_eq!(names, vec![bulk("sadd"), bulk("sismember")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys() -> Result<()> {
+        let keys = command(&["command", "getkeys", "HMGET", "map", "a", "b"])?;
+        assert_eq!(elements(keys), vec![bulk("map")]);
+        let keys = command(&["command", "getkeys", "watch", "a", "b", "c"])?;
+        assert_eq!(elements(keys), vec![bulk("a"), bulk("b"), bulk("c")]);
+        let keys = command(&["command", "getkeys", "eval", "return 1", "2", "a", "b", "c"])?;
+        assert_eq!(elements(keys), vec![bulk("a"), bulk("b")]);
+        let keys = command(&["command", "getkeys", "fcall", "f", "0"])?;
+        assert_eq!(elements(keys), vec![]);
+
+        let keys = command(&["command", "getkeysandflags", "restore", "k", "0", "v"])?;
+        assert_eq!(
+            elements(keys),
+            vec![Some(RespArray::new(vec![bulk("k"), set(&["OW", "UPDATE"])])).into()]
+        );
+
+        let cases: &[(&[&str], &str)] = &[
+            (&["nosuch", "a"], "Invalid command specified"),
+            (&["echo", "a"], "The command has no key arguments"),
+            (
+                &["hmget", "map"],
+                "Invalid number of arguments specified for command",
+            ),
+            (
+                &["eval", "return 1", "3", "a"],
+                "Invalid arguments specified for command",
+            ),
+        ];
+        for (args, err) in cases {
+            let args = [&["command", "getkeys"], *args].concat();
+            let ret = command(&args).unwrap_err().downcast::<ExecError>()?;
+            assert_eq!(ret, ExecError::err(*err));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    config_get, config_set, rewrite_config, Backend, BulkString, CommandError, CommandExecutor,
+    ExecError, RespArray, RespFrame,
+};
+
+use super::{extract_args, script::to_string, validate_command, RESP_OK};
+
+#[derive(Debug)]
+pub enum ConfigCmd {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    Rewrite,
+    ResetStat,
+}
+
+impl CommandExecutor for ConfigCmd {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        match self {
+            // a map for RESP3 clients, the codec flattens it into an array for RESP2 clients
+            ConfigCmd::Get(patterns) => {
+                let values = config_get(backend, &patterns)
+                    .into_iter()
+                    .map(|(name, value)| {
+                        (
+                            Some(BulkString::from(name)).into(),
+                            Some(BulkString::from(value)).into(),
+                        )
+                    })
+                    .collect::<BTreeMap<RespFrame, RespFrame>>();
+                Ok(RespFrame::Map(values))
+            }
+            ConfigCmd::Set(values) => {
+                config_set(backend, &values).map_err(ExecError::Err)?;
+                Ok(RESP_OK.clone())
+            }
+            ConfigCmd::Rewrite => {
+                rewrite_config(backend).map_err(ExecError::Err)?;
+                Ok(RESP_OK.clone())
+            }
+            ConfigCmd::ResetStat => {
+                backend.stats.reset();
+                Ok(RESP_OK.clone())
+            }
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ConfigCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = match value.get(1) {
+            Some(RespFrame::BulkString(Some(sub))) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "config command must have a subcommand".to_string(),
+                ))
+            }
+        };
+
+        match sub.as_slice() {
+            b"get" => {
+                validate_command(&value, &["config", "get"])?;
+                let patterns = extract_args(value, 2)?
+                    .into_iter()
+                    .map(|arg| to_string(Some(arg)))
+                    .collect::<Result<Vec<String>, CommandError>>()?;
+                Ok(ConfigCmd::Get(patterns))
+            }
+            b"set" => {
+                validate_command(&value, &["config", "set"])?;
+                // the parameters and their values come in pairs
+                if !value.len().is_multiple_of(2) {
+                    return Err(CommandError::WrongArity("config|set".to_string()));
+                }
+                let mut args = extract_args(value, 2)?.into_iter();
+                let mut values = Vec::new();
+                while let Some(name) = args.next() {
+                    values.push((to_string(Some(name))?, to_string(args.next())?));
+                }
+                Ok(ConfigCmd::Set(values))
+            }
+            b"rewrite" => {
+                validate_command(&value, &["config", "rewrite"])?;
+                Ok(ConfigCmd::Rewrite)
+            }
+            b"resetstat" => {
+                validate_command(&value, &["config", "resetstat"])?;
+                Ok(ConfigCmd::ResetStat)
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'",
+                String::from_utf8_lossy(&sub)
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cmd;
+    use crate::{AppendFsync, SimpleError};
+    use anyhow::Result;
+
+    #[test]
+    fn test_config_get_set() -> Result<()> {
+        let backend = Backend::new();
+        let ret =
+            cmd(&["config", "set", "appendfsync", "no", "timeout", "30"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert_eq!(backend.aof().fsync(), AppendFsync::No);
+
+        let ret = cmd(&["CONFIG", "GET", "appendfs*", "timeout"])?.execute(&backend)?;
+        let expected = [("appendfsync", "no"), ("timeout", "30")]
+            .into_iter()
+            .map(|(name, value)| {
+                (
+                    Some(BulkString::from(name)).into(),
+                    Some(BulkString::from(value)).into(),
+                )
+            })
+            .collect::<BTreeMap<RespFrame, RespFrame>>();
+        assert_eq!(ret, RespFrame::Map(expected));
+
+        let ret = cmd(&["config", "set", "port", "6380"])?.execute(&backend);
+        assert_eq!(
+            RespFrame::from(ret.unwrap_err()),
+            SimpleError::new(
+                "ERR CONFIG SET failed (possibly related to argument 'port') - can't set immutable config"
+            )
+            .into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_args() -> Result<()> {
+        let err = cmd(&["config", "set", "timeout"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'config|set' command"
+        );
+        let err = cmd(&["config", "set", "timeout", "1", "maxmemory"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'config|set' command"
+        );
+        assert!(cmd(&["config", "get"]).is_err());
+        assert!(cmd(&["config", "rewrite", "now"]).is_err());
+
+        let ret = cmd(&["config", "rewrite"])?.execute(&Backend::new());
+        assert_eq!(
+            ret,
+            Err(ExecError::err(
+                "The server is running without a config file"
+            ))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{
+    rdb::{read_u8, read_value, restore_value, value_of, verify_footer, write_footer, write_value},
+    util::now_ms,
+    Backend, BulkString, CommandError, CommandExecutor, ExecError, RdbError, RdbValue, RespArray,
+    RespFrame,
+};
+
+use super::{extract_args, script::to_string, validate_command, RESP_OK};
+
+#[derive(Debug)]
+pub struct Dump {
+    pub key: String,
+}
+
+#[derive(Debug)]
+pub struct Restore {
+    pub key: String,
+    // milliseconds to live, or the unix time in milliseconds with ABSTTL, 0 for no expire
+    pub ttl: u64,
+    pub payload: Vec<u8>,
+    pub replace: bool,
+    pub absttl: bool,
+}
+
+impl CommandExecutor for Dump {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        backend.expire_if_needed(&self.key);
+        match value_of(backend, &self.key) {
+            Some(value) => Ok(Some(BulkString::new(dump_payload(&value))).into()),
+            None => Ok(RespFrame::BulkString(None)),
+        }
+    }
+}
+
+impl CommandExecutor for Restore {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        let value = match parse_payload(&self.payload) {
+            Ok(value) => value,
+            Err(RdbError::BadFooter) => {
+                return Err(ExecError::err("DUMP payload version or checksum are wrong"))
+            }
+            Err(_) => return Err(ExecError::err("Bad data format")),
+        };
+        if !self.replace && backend.contains_key(&self.key) {
+            return Err(ExecError::BusyKey);
+        }
+
+        let now = now_ms();
+        let expire_at = match (self.ttl, self.absttl) {
+            (0, _) => None,
+            (at, true) => Some(at),
+            (ttl, false) => Some(now.saturating_add(ttl)),
+        };
+        let deleted = self.replace && backend.remove_key(&self.key);
+        // an absolute time in the past: the key is only deleted
+        if expire_at.is_some_and(|at| at <= now) {
+            if deleted {
+                backend.signal_modified_key(&self.key);
+            }
+            return Ok(RESP_OK.clone());
+        }
+
+        if restore_value(backend, self.key.clone(), value).is_err() {
+            return Err(ExecError::err("Bad data format"));
+        }
+        if let Some(at) = expire_at {
+            backend.set_expire_at(self.key.clone(), at);
+        }
+        backend.signal_modified_key(&self.key);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl Restore {
+    /// The command written to the AOF: a relative TTL becomes an absolute one,
+    /// so that the key does not live longer when the AOF is loaded.
+    pub fn to_absolute_argv(&self) -> RespArray {
+        let ttl = match (self.ttl, self.absttl) {
+            (ttl, false) if ttl > 0 => now_ms().saturating_add(ttl),
+            (ttl, _) => ttl,
+        };
+        let mut args = vec![
+            BulkString::from("restore"),
+            BulkString::from(self.key.as_str()),
+            BulkString::from(ttl.to_string()),
+            BulkString::new(self.payload.clone()),
+        ];
+        if ttl > 0 {
+            args.push(BulkString::from("absttl"));
+        }
+        if self.replace {
+            args.push(BulkString::from("replace"));
+        }
+        RespArray::new(
+            args.into_iter()
+                .map(|arg| Some(arg).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+}
+
+/// The serialized value followed by the RDB version and a CRC64, as DUMP returns it.
+pub fn dump_payload(value: &RdbValue) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_value(&mut buf, value);
+    write_footer(&mut buf);
+    buf
+}
+
+fn parse_payload(payload: &[u8]) -> Result<RdbValue, RdbError> {
+    let mut buf = verify_footer(payload)?;
+    let value_type = read_u8(&mut buf)?;
+    let value = read_value(value_type, &mut buf)?;
+    if !buf.is_empty() {
+        return Err(RdbError::Invalid(
+            "trailing data after the value".to_string(),
+        ));
+    }
+    Ok(value)
+}
+
+impl TryFrom<RespArray> for Dump {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dump"])?;
+        let key = to_string(extract_args(value, 1)?.pop())?;
+        Ok(Dump { key })
+    }
+}
+
+// RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+impl TryFrom<RespArray> for Restore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["restore"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = to_string(args.next())?;
+        let ttl = to_string(args.next())?
+            .parse::<i64>()
+            .map_err(|_| {
+                CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+            })
+            .and_then(|ttl| {
+                u64::try_from(ttl).map_err(|_| {
+                    CommandError::InvalidArgument("Invalid TTL value, must be >= 0".to_string())
+                })
+            })?;
+        let payload = match args.next() {
+            Some(RespFrame::BulkString(Some(payload))) => payload.into_vec(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "argument must be a bulk string".to_string(),
+                ))
+            }
+        };
+
+        let mut restore = Restore {
+            key,
+            ttl,
+            payload,
+            replace: false,
+            absttl: false,
+        };
+        // without LRU or LFU eviction, IDLETIME and FREQ are only validated
+        let (mut idletime, mut freq) = (false, false);
+        while let Some(arg) = args.next() {
+            match to_string(Some(arg))?.to_ascii_lowercase().as_str() {
+                "replace" => restore.replace = true,
+                "absttl" => restore.absttl = true,
+                "idletime" if !freq => {
+                    idletime = true;
+                    let idle = to_string(args.next())?.parse::<i64>().map_err(|_| {
+                        CommandError::InvalidArgument(
+                            "value is not an integer or out of range".to_string(),
+                        )
+                    })?;
+                    if idle < 0 {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid IDLETIME value, must be >= 0".to_string(),
+                        ));
+                    }
+                }
+                "freq" if !idletime => {
+                    freq = true;
+                    let frequency = to_string(args.next())?.parse::<i64>().map_err(|_| {
+                        CommandError::InvalidArgument(
+                            "value is not an integer or out of range".to_string(),
+                        )
+                    })?;
+                    if !(0..=255).contains(&frequency) {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid FREQ value, must be >= 0 and <= 255".to_string(),
+                        ));
+                    }
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::{rdb::write_length, RespFrame};
+
+    use super::*;
+    use crate::test_util::bytes_cmd;
+    use anyhow::Result;
+
+    fn dump(backend: &Backend, key: &str) -> Result<Vec<u8>> {
+        match bytes_cmd(&[b"dump", key.as_bytes()])?.execute(backend)? {
+            RespFrame::BulkString(Some(payload)) => Ok(payload.into_vec()),
+            frame => panic!("unexpected reply {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_dump_and_restore() -> Result<()> {
+        let backend = Backend::new();
+        bytes_cmd(&[b"sadd", b"myset", b"a", b"b"])?.execute(&backend)?;
+        bytes_cmd(&[b"hset", b"myhash", b"field", b"value"])?.execute(&backend)?;
+        let set = dump(&backend, "myset")?;
+        let hash = dump(&backend, "myhash")?;
+        assert_eq!(
+            bytes_cmd(&[b"dump", b"nokey"])?.execute(&backend)?,
+            RespFrame::BulkString(None)
+        );
+
+        let ret = bytes_cmd(&[b"restore", b"myset", b"0", &set])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::BusyKey));
+        // a hash replaces the set
+        let ret = bytes_cmd(&[b"restore", b"myset", b"0", &hash, b"replace"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(backend.hset.get("myset").is_none());
+        assert_eq!(dump(&backend, "myset")?, hash);
+
+        let ret =
+            bytes_cmd(&[b"restore", b"copy", b"0", &set, b"idletime", b"10"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert_eq!(backend.hset.get("copy").map(|s| s.len()), Some(2));
+        assert_eq!(backend.expire_at("copy"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_ttl() -> Result<()> {
+        let backend = Backend::new();
+        bytes_cmd(&[b"sadd", b"myset", b"a"])?.execute(&backend)?;
+        let payload = dump(&backend, "myset")?;
+
+        bytes_cmd(&[b"restore", b"ttl", b"20", &payload])?.execute(&backend)?;
+        assert!(backend.expire_at("ttl").is_some());
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(
+            bytes_cmd(&[b"dump", b"ttl"])?.execute(&backend)?,
+            RespFrame::BulkString(None)
+        );
+
+        // an absolute time in the past deletes the key
+        let ret = bytes_cmd(&[b"restore", b"myset", b"1", &payload, b"absttl", b"replace"])?
+            .execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(!backend.contains_key("myset"));
+
+        let at = (now_ms() + 60_000).to_string();
+        bytes_cmd(&[b"restore", b"abs", at.as_bytes(), &payload, b"absttl"])?.execute(&backend)?;
+        assert_eq!(backend.expire_at("abs"), Some(at.parse::<u64>()?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_bad_payload() -> Result<()> {
+        let backend = Backend::new();
+        bytes_cmd(&[b"sadd", b"myset", b"a"])?.execute(&backend)?;
+        let mut payload = dump(&backend, "myset")?;
+
+        let last = payload.len() - 1;
+        payload[last] ^= 0xff;
+        let ret = bytes_cmd(&[b"restore", b"key", b"0", &payload])?.execute(&backend);
+        assert_eq!(
+            ret,
+            Err(ExecError::err("DUMP payload version or checksum are wrong"))
+        );
+
+        // an unknown value type
+        let mut payload = vec![0x7f];
+        write_footer(&mut payload);
+        let ret = bytes_cmd(&[b"restore", b"key", b"0", &payload])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::err("Bad data format")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_huge_length() -> Result<()> {
+        let backend = Backend::new();
+        let restore = |payload: &[u8]| -> Result<Result<RespFrame, ExecError>> {
+            let mut payload = payload.to_vec();
+            write_footer(&mut payload);
+            Ok(bytes_cmd(&[b"restore", b"key", b"0", &payload])?.execute(&backend))
+        };
+
+        // an intset (type 11) of 2^32 - 1 members of 8 bytes, with a single one given
+        let mut intset = vec![11, 16];
+        intset.extend_from_slice(&8u32.to_le_bytes());
+        intset.extend_from_slice(&u32::MAX.to_le_bytes());
+        intset.extend_from_slice(&1i64.to_le_bytes());
+        let ret = restore(&intset)?;
+        assert_eq!(ret, Err(ExecError::err("Bad data format")));
+
+        // a set (type 2) member of 2^62 bytes once decompressed from 2 bytes
+        let mut lzf = vec![2, 1, 0xc3, 2];
+        write_length(&mut lzf, 1 << 62);
+        lzf.extend_from_slice(&[0x00, b'a']);
+        let ret = restore(&lzf)?;
+        assert_eq!(ret, Err(ExecError::err("Bad data format")));
+
+        assert!(value_of(&backend, "key").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_options() {
+        let payload: &[u8] = b"payload";
+        assert!(bytes_cmd(&[b"restore", b"key", b"-1", payload]).is_err());
+        assert!(bytes_cmd(&[b"restore", b"key", b"0", payload, b"idletime", b"-1"]).is_err());
+        assert!(bytes_cmd(&[b"restore", b"key", b"0", payload, b"freq", b"256"]).is_err());
+        assert!(bytes_cmd(&[
+            b"restore",
+            b"key",
+            b"0",
+            payload,
+            b"freq",
+            b"1",
+            b"idletime",
+            b"1"
+        ])
+        .is_err());
+        assert!(bytes_cmd(&[b"restore", b"key", b"0", payload, b"nx"]).is_err());
+        assert!(
+            bytes_cmd(&[b"restore", b"key", b"0", payload, b"freq", b"255", b"absttl"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_absolute_argv() -> Result<()> {
+        let restore = Restore {
+            key: "key".to_string(),
+            ttl: 1000,
+            payload: b"payload".to_vec(),
+            replace: true,
+            absttl: false,
+        };
+        let argv = restore.to_absolute_argv();
+        assert_eq!(argv.len(), 6);
+        let at: u64 = to_string(argv.get(2).cloned())?.parse()?;
+        assert!(at > now_ms());
+        assert_eq!(to_string(argv.get(4).cloned())?, "absttl");
+        Ok(())
+    }
+}
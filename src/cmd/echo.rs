@@ -1,4 +1,4 @@
-use crate::{Backend, CommandError, CommandExecutor, RespArray, RespFrame};
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
 
 use super::{extract_args, validate_command};
 
@@ -8,15 +8,15 @@ pub struct Echo {
 }
 
 impl CommandExecutor for Echo {
-    fn execute(self, _: &Backend) -> RespFrame {
-        self.echo
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Ok(self.echo)
     }
 }
 
 impl TryFrom<RespArray> for Echo {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["echo"], 1, super::ArgsCheckRule::Equal)?;
+        validate_command(&value, &["echo"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
@@ -42,7 +42,7 @@ mod tests {
         .into();
 
         let echo = Command::try_from(frame)?;
-        let ret = echo.execute(&Backend::new());
+        let ret = echo.execute(&Backend::new())?;
         assert_eq!(ret, Some(BulkString::new("hello".to_string())).into());
         Ok(())
     }
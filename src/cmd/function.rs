@@ -0,0 +1,367 @@
+use crate::{
+    call_function, glob_match, Backend, BulkString, CommandError, CommandExecutor, ExecError,
+    Library, RespArray, RespFrame, RestorePolicy,
+};
+
+use super::{
+    extract_args,
+    script::{split_keys_and_args, to_string},
+    validate_command, RESP_OK,
+};
+
+#[derive(Debug)]
+pub struct FCall {
+    pub name: String,
+    pub keys: Vec<BulkString>,
+    pub args: Vec<BulkString>,
+    // FCALL_RO
+    pub read_only: bool,
+}
+
+#[derive(Debug)]
+pub enum Function {
+    Load {
+        replace: bool,
+        code: String,
+    },
+    List {
+        pattern: Option<String>,
+        with_code: bool,
+    },
+    Delete(String),
+    Dump,
+    Restore {
+        payload: Vec<u8>,
+        policy: RestorePolicy,
+    },
+    Flush,
+    Kill,
+}
+
+impl CommandExecutor for FCall {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        call_function(backend, &self.name, self.keys, self.args, self.read_only)
+    }
+}
+
+impl CommandExecutor for Function {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        match self {
+            Function::Load { replace, code } => {
+                match backend.functions.load(&backend.scripts, &code, replace) {
+                    Ok(name) => Ok(Some(BulkString::from(name)).into()),
+                    Err(e) => Err(ExecError::Reply(e)),
+                }
+            }
+            Function::List { pattern, with_code } => {
+                let libraries = backend
+                    .functions
+                    .list()
+                    .into_iter()
+                    .filter(|lib| match &pattern {
+                        Some(pattern) => glob_match(pattern.as_bytes(), lib.name.as_bytes(), false),
+                        None => true,
+                    })
+                    .map(|lib| library_to_frame(lib, with_code))
+                    .collect::<Vec<RespFrame>>();
+                Ok(Some(RespArray::new(libraries)).into())
+            }
+            Function::Delete(name) => {
+                if backend.functions.delete(&name) {
+                    Ok(RESP_OK.clone())
+                } else {
+                    Err(ExecError::err("Library not found"))
+                }
+            }
+            Function::Dump => Ok(Some(BulkString::new(backend.functions.dump())).into()),
+            Function::Restore { payload, policy } => {
+                match backend
+                    .functions
+                    .restore(&backend.scripts, &payload, policy)
+                {
+                    Ok(()) => Ok(RESP_OK.clone()),
+                    Err(e) => Err(ExecError::Reply(e)),
+                }
+            }
+            Function::Flush => {
+                backend.functions.flush();
+                Ok(RESP_OK.clone())
+            }
+            Function::Kill => backend.scripts.kill(),
+        }
+    }
+}
+
+// the reply of FUNCTION LIST for one library, as returned by Redis 7 to RESP2 clients
+fn library_to_frame(library: Library, with_code: bool) -> RespFrame {
+    let functions = library
+        .functions
+        .into_values()
+        .map(|f| {
+            let flags = f
+                .flags
+                .into_iter()
+                .map(|flag| Some(BulkString::from(flag)).into())
+                .collect::<Vec<RespFrame>>();
+            Some(RespArray::new(vec![
+                b"name".into(),
+                Some(BulkString::from(f.name)).into(),
+                b"description".into(),
+                f.description.map(BulkString::from).into(),
+                b"flags".into(),
+                Some(RespArray::new(flags)).into(),
+            ]))
+            .into()
+        })
+        .collect::<Vec<RespFrame>>();
+
+    let mut frame = vec![
+        b"library_name".into(),
+        Some(BulkString::from(library.name)).into(),
+        b"engine".into(),
+        Some(BulkString::from(library.engine)).into(),
+        b"functions".into(),
+        Some(RespArray::new(functions)).into(),
+    ];
+    if with_code {
+        frame.push(b"library_code".into());
+        frame.push(Some(BulkString::from(library.code)).into());
+    }
+    Some(RespArray::new(frame)).into()
+}
+
+impl TryFrom<RespArray> for FCall {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let read_only = matches!(
+            value.first(),
+            Some(RespFrame::BulkString(Some(cmd))) if cmd.eq_ignore_ascii_case(b"fcall_ro")
+        );
+        let name = if read_only { "fcall_ro" } else { "fcall" };
+        validate_command(&value, &[name])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let name = to_string(args.next())?;
+        let (keys, args) = split_keys_and_args(args)?;
+        Ok(FCall {
+            name,
+            keys,
+            args,
+            read_only,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Function {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = match value.get(1) {
+            Some(RespFrame::BulkString(Some(sub))) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "function command must have a subcommand".to_string(),
+                ))
+            }
+        };
+
+        match sub.as_slice() {
+            b"load" => {
+                validate_command(&value, &["function", "load"])?;
+                let mut args = extract_args(value, 2)?;
+                let code = to_string(args.pop())?;
+                let replace = match args.len() {
+                    0 => false,
+                    1 if to_string(args.pop())?.eq_ignore_ascii_case("replace") => true,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Unknown option given".to_string(),
+                        ))
+                    }
+                };
+                Ok(Function::Load { replace, code })
+            }
+            b"list" => {
+                let mut pattern = None;
+                let mut with_code = false;
+                let mut args = extract_args(value, 2)?.into_iter();
+                while let Some(arg) = args.next() {
+                    match to_string(Some(arg))?.to_ascii_lowercase().as_str() {
+                        "withcode" if !with_code => with_code = true,
+                        "libraryname" if pattern.is_none() => {
+                            pattern = Some(to_string(args.next()).map_err(|_| {
+                                CommandError::InvalidArgument(
+                                    "library name argument was not given".to_string(),
+                                )
+                            })?)
+                        }
+                        option => {
+                            return Err(CommandError::InvalidArgument(format!(
+                                "Unknown argument {}",
+                                option
+                            )))
+                        }
+                    }
+                }
+                Ok(Function::List { pattern, with_code })
+            }
+            b"delete" => {
+                validate_command(&value, &["function", "delete"])?;
+                let mut args = extract_args(value, 2)?.into_iter();
+                Ok(Function::Delete(to_string(args.next())?))
+            }
+            b"dump" => {
+                validate_command(&value, &["function", "dump"])?;
+                Ok(Function::Dump)
+            }
+            b"restore" => {
+                validate_command(&value, &["function", "restore"])?;
+                let mut args = extract_args(value, 2)?.into_iter();
+                let payload = match args.next() {
+                    Some(RespFrame::BulkString(Some(payload))) => payload.into_vec(),
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "argument must be a bulk string".to_string(),
+                        ))
+                    }
+                };
+                let policy = match args.next() {
+                    None => RestorePolicy::Append,
+                    Some(arg) => match to_string(Some(arg))?.to_ascii_lowercase().as_str() {
+                        "append" => RestorePolicy::Append,
+                        "replace" => RestorePolicy::Replace,
+                        "flush" => RestorePolicy::Flush,
+                        _ => {
+                            return Err(CommandError::InvalidArgument(
+                                "Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE.".to_string(),
+                            ))
+                        }
+                    },
+                };
+                if args.next().is_some() {
+                    return Err(CommandError::InvalidArgument(
+                        "function restore command must have at most 2 arguments".to_string(),
+                    ));
+                }
+                Ok(Function::Restore { payload, policy })
+            }
+            b"flush" => {
+                validate_command(&value, &["function", "flush"])?;
+                // the ASYNC and SYNC modes are accepted, flushing is always synchronous
+                if value.len() > 2 {
+                    let mode = to_string(value.get(2).cloned())?.to_ascii_lowercase();
+                    if value.len() > 3 || (mode != "async" && mode != "sync") {
+                        return Err(CommandError::InvalidArgument(
+                            "FUNCTION FLUSH only supports SYNC|ASYNC option".to_string(),
+                        ));
+                    }
+                }
+                Ok(Function::Flush)
+            }
+            b"kill" => {
+                validate_command(&value, &["function", "kill"])?;
+                Ok(Function::Kill)
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'",
+                String::from_utf8_lossy(&sub)
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RespArray, RespFrame};
+
+    use super::*;
+    use crate::test_util::bytes_cmd;
+    use anyhow::Result;
+
+    const LIBRARY: &str = "#!lua name=mylib
+redis.register_function{function_name='myecho', callback=function(keys, args) return args[1] end, flags={'no-writes'}, description='echo'}";
+
+    #[test]
+    fn test_function_load_and_fcall() -> Result<()> {
+        let backend = Backend::new();
+        let ret = bytes_cmd(&[b"function", b"load", LIBRARY.as_bytes()])?.execute(&backend)?;
+        assert_eq!(ret, b"mylib".into());
+
+        let ret = bytes_cmd(&[b"fcall", b"myecho", b"0", b"hello"])?.execute(&backend)?;
+        assert_eq!(ret, b"hello".into());
+        let ret = bytes_cmd(&[b"fcall_ro", b"myecho", b"0", b"world"])?.execute(&backend)?;
+        assert_eq!(ret, b"world".into());
+
+        let ret = bytes_cmd(&[b"function", b"load", LIBRARY.as_bytes()])?.execute(&backend);
+        assert_eq!(
+            ret,
+            Err(ExecError::Reply(
+                "ERR Library 'mylib' already exists".to_string()
+            ))
+        );
+        let ret = bytes_cmd(&[b"function", b"load", b"REPLACE", LIBRARY.as_bytes()])?
+            .execute(&backend)?;
+        assert_eq!(ret, b"mylib".into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_list() -> Result<()> {
+        let backend = Backend::new();
+        bytes_cmd(&[b"function", b"load", LIBRARY.as_bytes()])?.execute(&backend)?;
+
+        let ret = bytes_cmd(&[b"function", b"list", b"libraryname", b"my*"])?.execute(&backend)?;
+        let function: RespFrame = Some(RespArray::new(vec![
+            b"name".into(),
+            b"myecho".into(),
+            b"description".into(),
+            b"echo".into(),
+            b"flags".into(),
+            Some(RespArray::new(vec![b"no-writes".into()])).into(),
+        ]))
+        .into();
+        let library: RespFrame = Some(RespArray::new(vec![
+            b"library_name".into(),
+            b"mylib".into(),
+            b"engine".into(),
+            b"LUA".into(),
+            b"functions".into(),
+            Some(RespArray::new(vec![function])).into(),
+        ]))
+        .into();
+        assert_eq!(ret, Some(RespArray::new(vec![library])).into());
+
+        let ret =
+            bytes_cmd(&[b"function", b"list", b"libraryname", b"other*"])?.execute(&backend)?;
+        assert_eq!(ret, Some(RespArray::new(vec![])).into());
+
+        assert!(bytes_cmd(&[b"function", b"list", b"foo"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_dump_restore_delete() -> Result<()> {
+        let backend = Backend::new();
+        bytes_cmd(&[b"function", b"load", LIBRARY.as_bytes()])?.execute(&backend)?;
+        let payload = match bytes_cmd(&[b"function", b"dump"])?.execute(&backend)? {
+            RespFrame::BulkString(Some(payload)) => payload.into_vec(),
+            frame => panic!("unexpected reply {:?}", frame),
+        };
+
+        let ret = bytes_cmd(&[b"function", b"delete", b"mylib"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        let ret = bytes_cmd(&[b"function", b"delete", b"mylib"])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::err("Library not found")));
+        let ret = bytes_cmd(&[b"fcall", b"myecho", b"0", b"hello"])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::err("Function not found")));
+
+        let ret = bytes_cmd(&[b"function", b"restore", &payload])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        let ret = bytes_cmd(&[b"fcall", b"myecho", b"0", b"hello"])?.execute(&backend)?;
+        assert_eq!(ret, b"hello".into());
+
+        let ret = bytes_cmd(&[b"function", b"flush"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(backend.functions.list().is_empty());
+        Ok(())
+    }
+}
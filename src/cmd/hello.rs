@@ -0,0 +1,92 @@
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
+
+use super::{extract_args, script::to_string, validate_command};
+
+// HELLO switches the protocol of the connection, it is intercepted by the
+// `Session` before reaching the executor.
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Hello {
+    pub protover: Option<i64>,
+    pub auth: Option<(String, String)>,
+    pub setname: Option<String>,
+}
+
+impl CommandExecutor for Hello {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err(
+            "HELLO is only allowed on a client connection",
+        ))
+    }
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hello"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut hello = Hello::default();
+        let Some(protover) = args.next() else {
+            return Ok(hello);
+        };
+        hello.protover = Some(to_string(Some(protover))?.parse().map_err(|_| {
+            CommandError::InvalidArgument(
+                "Protocol version is not an integer or out of range".to_string(),
+            )
+        })?);
+
+        while let Some(arg) = args.next() {
+            let option = to_string(Some(arg))?;
+            match option.to_ascii_lowercase().as_str() {
+                "auth" if args.len() >= 2 => {
+                    let username = to_string(args.next())?;
+                    let password = to_string(args.next())?;
+                    hello.auth = Some((username, password));
+                }
+                "setname" if args.len() >= 1 => hello.setname = Some(to_string(args.next())?),
+                _ => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{}'",
+                        option
+                    )))
+                }
+            }
+        }
+        Ok(hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::cmd, Command};
+
+    use super::*;
+
+    #[test]
+    fn test_hello_try_from() -> anyhow::Result<()> {
+        let Command::Hello(hello) = cmd(&["hello"])? else {
+            panic!("expect a HELLO command");
+        };
+        assert_eq!(hello, Hello::default());
+
+        let Command::Hello(hello) = cmd(&["hello", "3", "setname", "app", "AUTH", "u", "p"])?
+        else {
+            panic!("expect a HELLO command");
+        };
+        assert_eq!(
+            hello,
+            Hello {
+                protover: Some(3),
+                auth: Some(("u".to_string(), "p".to_string())),
+                setname: Some("app".to_string()),
+            }
+        );
+
+        assert!(cmd(&["hello", "three"]).is_err());
+        assert!(cmd(&["hello", "3", "auth", "u"]).is_err());
+        assert!(cmd(&["hello", "3", "setname"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use crate::{Backend, BulkString, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
+
+use super::{extract_args, script::to_string, validate_command};
+
+#[derive(Debug)]
+pub struct HGetAll {
+    pub key: String,
+}
+
+// a map for RESP3 clients, the codec flattens it into an array for RESP2 clients
+impl CommandExecutor for HGetAll {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        backend.expire_if_needed(&self.key);
+        if backend.hset.contains_key(&self.key) {
+            return Err(ExecError::WrongType);
+        }
+
+        let mut map = BTreeMap::new();
+        if let Some(item) = backend.hmap.get(&self.key) {
+            for entry in item.value().iter() {
+                map.insert(
+                    Some(BulkString::from(entry.key().as_str())).into(),
+                    entry.value().clone(),
+                );
+            }
+        }
+        Ok(RespFrame::Map(map))
+    }
+}
+
+impl TryFrom<RespArray> for HGetAll {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hgetall"])?;
+        let key = to_string(extract_args(value, 1)?.pop())?;
+        Ok(HGetAll { key })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RespEncode;
+
+    use super::*;
+    use crate::test_util::cmd;
+    use anyhow::Result;
+
+    #[test]
+    fn test_hgetall() -> Result<()> {
+        let backend = Backend::new();
+        cmd(&["hset", "myhash", "field2", "value2"])?.execute(&backend)?;
+        cmd(&["hset", "myhash", "field1", "value1"])?.execute(&backend)?;
+
+        let ret = cmd(&["hgetall", "myhash"])?.execute(&backend)?;
+        assert_eq!(
+            ret.clone().encode(),
+            b"%2\r\n$6\r\nfield1\r\n$6\r\nvalue1\r\n$6\r\nfield2\r\n$6\r\nvalue2\r\n"
+        );
+        assert_eq!(
+            ret.into_resp2().encode(),
+            b"*4\r\n$6\r\nfield1\r\n$6\r\nvalue1\r\n$6\r\nfield2\r\n$6\r\nvalue2\r\n"
+        );
+
+        let ret = cmd(&["hgetall", "nokey"])?.execute(&backend)?;
+        assert_eq!(ret, RespFrame::Map(BTreeMap::new()));
+
+        cmd(&["sadd", "myset", "a"])?.execute(&backend)?;
+        let ret = cmd(&["hgetall", "myset"])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::WrongType));
+        assert_eq!(ret.unwrap_err().code(), "WRONGTYPE");
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Backend, CommandError, CommandExecutor, RespArray, RespFrame, SimpleError};
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame, RespNull};
 
 use super::{extract_args, validate_command};
 
@@ -9,46 +9,41 @@ pub struct HmGet {
 }
 
 impl CommandExecutor for HmGet {
-    fn execute(self, backend: &Backend) -> RespFrame {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
         let mut result = RespArray::new(vec![]);
+        backend.expire_if_needed(&self.key);
+        if backend.hset.contains_key(&self.key) {
+            return Err(ExecError::WrongType);
+        }
 
-        match backend.hmap.get(&self.key) {
-            Some(item) => {
-                let map = item.value();
-                for member in self.members {
-                    match map.get(&member) {
-                        Some(value) => result.push(value.value().clone()),
-                        None => result.push(RespFrame::BulkString(None)), //兼容RESP 2
-                    }
-                }
+        // the fields of a missing key are missing
+        let item = backend.hmap.get(&self.key);
+        for member in self.members {
+            match item.as_ref().and_then(|map| map.get(&member)) {
+                Some(value) => result.push(value.value().clone()),
+                None => result.push(RespNull.into()),
             }
-            None => {
-                return RespFrame::Error(SimpleError::new(format!(
-                    "key {} is not exist",
-                    &self.key
-                )))
-            }
-        };
+        }
 
-        RespFrame::Array(Some(result))
+        Ok(RespFrame::Array(Some(result)))
     }
 }
 
 impl TryFrom<RespArray> for HmGet {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hmget"], 2, super::ArgsCheckRule::EqualOrGreater)?;
+        validate_command(&value, &["hmget"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         let key = match args.next() {
-            Some(RespFrame::BulkString(Some(key))) => Ok(String::from_utf8(key.0)?),
+            Some(RespFrame::BulkString(Some(key))) => Ok(String::from_utf8(key.into_vec())?),
             _ => Err(CommandError::InvalidArgument("Invalid hmget".to_string())),
         }?;
 
         let members = args
             .filter_map(|arg| {
                 if let RespFrame::BulkString(Some(key)) = arg {
-                    String::from_utf8(key.0).ok()
+                    String::from_utf8(key.into_vec()).ok()
                 } else {
                     None
                 }
@@ -82,7 +77,7 @@ mod tests {
         .into();
 
         let sadd = Command::try_from(frame)?;
-        let ret = sadd.execute(backend);
+        let ret = sadd.execute(backend)?;
 
         assert_eq!(ret, 1.into());
 
@@ -95,7 +90,7 @@ mod tests {
         .into();
 
         let sadd = Command::try_from(frame)?;
-        let ret = sadd.execute(backend);
+        let ret = sadd.execute(backend)?;
 
         assert_eq!(ret, 1.into());
 
@@ -109,14 +104,14 @@ mod tests {
         .into();
 
         let sadd = Command::try_from(frame)?;
-        let ret = sadd.execute(backend);
+        let ret = sadd.execute(backend)?;
 
         assert_eq!(
             ret,
             Some(RespArray::new(vec![
                 Some(BulkString::new("value1".to_string())).into(),
                 Some(BulkString::new("value2".to_string())).into(),
-                RespFrame::BulkString(None),
+                RespNull.into(),
             ]))
             .into()
         );
@@ -1,4 +1,4 @@
-use crate::{Backend, CommandError, CommandExecutor, RespArray, RespFrame};
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
 
 use super::{extract_args, validate_command};
 
@@ -10,19 +10,25 @@ pub struct HSet {
 }
 
 impl CommandExecutor for HSet {
-    fn execute(self, backend: &Backend) -> RespFrame {
-        let hmap = backend.hmap.entry(self.key).or_default();
-        match hmap.insert(self.field, self.value) {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        backend.expire_if_needed(&self.key);
+        if backend.hset.contains_key(&self.key) {
+            return Err(ExecError::WrongType);
+        }
+        let hmap = backend.hmap.entry(self.key.clone()).or_default();
+        let ret = match hmap.insert(self.field, self.value) {
             Some(_) => 0.into(), //update
             None => 1.into(),    //insert
-        }
+        };
+        backend.signal_modified_key(&self.key);
+        Ok(ret)
     }
 }
 
 impl TryFrom<RespArray> for HSet {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hset"], 3, super::ArgsCheckRule::Equal)?;
+        validate_command(&value, &["hset"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next(), args.next()) {
@@ -31,8 +37,8 @@ impl TryFrom<RespArray> for HSet {
                 Some(RespFrame::BulkString(Some(field))),
                 Some(value),
             ) => Ok(HSet {
-                key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                key: String::from_utf8(key.into_vec())?,
+                field: String::from_utf8(field.into_vec())?,
                 value,
             }),
             _ => Err(CommandError::InvalidArgument(
@@ -60,7 +66,7 @@ mod tests {
         .into();
 
         let sadd = Command::try_from(frame)?;
-        let ret = sadd.execute(&Backend::new());
+        let ret = sadd.execute(&Backend::new())?;
 
         assert_eq!(ret, 1.into());
         Ok(())
@@ -1,33 +1,376 @@
-use crate::{Backend, CommandError, CommandExecutor, RespArray, RespFrame};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::validate_command;
+use crate::{
+    memory::rss_bytes, session::REDIS_VERSION, used_memory, Backend, BulkString, CommandError,
+    CommandExecutor, ExecError, RespArray, RespFrame,
+};
 
+use super::{extract_args, script::to_string, validate_command};
+
+// the sections in the order of the reply, with their title
+const SECTIONS: &[(&str, &str)] = &[
+    ("server", "Server"),
+    ("clients", "Clients"),
+    ("memory", "Memory"),
+    ("persistence", "Persistence"),
+    ("stats", "Stats"),
+    ("replication", "Replication"),
+    ("cpu", "CPU"),
+    ("commandstats", "Commandstats"),
+    ("errorstats", "Errorstats"),
+    ("keyspace", "Keyspace"),
+];
+
+// the sections of INFO without arguments, as Redis all of them but commandstats
+const DEFAULT_SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cpu",
+    "errorstats",
+    "keyspace",
+];
+
+// the clock ticks per second of /proc/self/stat, USER_HZ is 100 on Linux
+const CLOCK_TICKS: f64 = 100.0;
+
+/// The section names, `all`, `default` or `everything` in lowercase, none for
+/// the default sections.
 #[derive(Debug)]
-pub struct Info();
+pub struct Info {
+    pub sections: Vec<String>,
+}
 
 impl CommandExecutor for Info {
-    fn execute(self, _: &Backend) -> RespFrame {
-        "Ok".into()
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        let mut info = String::new();
+        for (name, title) in self.selected() {
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+            info.push_str(&format!("# {}\r\n", title));
+            for (field, value) in section(backend, name) {
+                info.push_str(&format!("{}:{}\r\n", field, value));
+            }
+        }
+        Ok(Some(BulkString::from(info)).into())
     }
 }
 
 impl Info {
-    pub fn new() -> Self {
-        Self()
+    pub fn new(sections: Vec<String>) -> Self {
+        Self { sections }
+    }
+
+    // the sections to reply with, unknown section names are ignored
+    fn selected(&self) -> Vec<(&'static str, &'static str)> {
+        let is_selected = |name: &str| {
+            if self.sections.is_empty() {
+                return DEFAULT_SECTIONS.contains(&name);
+            }
+            self.sections.iter().any(|arg| match arg.as_str() {
+                // there are no modules, all is everything
+                "all" | "everything" => true,
+                "default" => DEFAULT_SECTIONS.contains(&name),
+                arg => arg == name,
+            })
+        };
+        SECTIONS
+            .iter()
+            .filter(|(name, _)| is_selected(name))
+            .copied()
+            .collect()
     }
 }
 
 impl Default for Info {
     fn default() -> Self {
-        Self::new()
+        Self::new(vec![])
     }
 }
 
 impl TryFrom<RespArray> for Info {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["info"], 0, super::ArgsCheckRule::Equal)?;
+        validate_command(&value, &["info"])?;
+
+        let sections = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| Ok(to_string(Some(arg))?.to_ascii_lowercase()))
+            .collect::<Result<Vec<String>, CommandError>>()?;
+        Ok(Info::new(sections))
+    }
+}
+
+fn section(backend: &Backend, name: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut field = |name: &str, value: String| fields.push((name.to_string(), value));
+    let stats = backend.stats();
+    match name {
+        "server" => {
+            let uptime = backend.uptime().as_secs();
+            let executable = std::env::current_exe()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            let config_file = backend
+                .config()
+                .file()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            field("redis_version", REDIS_VERSION.to_string());
+            field("redis_mode", "standalone".to_string());
+            field(
+                "os",
+                format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            );
+            field("arch_bits", usize::BITS.to_string());
+            field("process_id", std::process::id().to_string());
+            field("run_id", backend.run_id().to_string());
+            field("tcp_port", backend.config().port().to_string());
+            field("server_time_usec", now_us().to_string());
+            field("uptime_in_seconds", uptime.to_string());
+            field("uptime_in_days", (uptime / 86400).to_string());
+            field("executable", executable);
+            field("config_file", config_file);
+        }
+        "clients" => {
+            field("connected_clients", backend.client_count().to_string());
+            field("blocked_clients", "0".to_string());
+            let tracking = backend.clients().iter().filter(|c| c.is_tracking()).count();
+            field("tracking_clients", tracking.to_string());
+        }
+        "memory" => {
+            // the bytes allocated, and the memory of the process as seen by the system
+            let used = used_memory() as u64;
+            let rss = rss_bytes();
+            let maxmemory = backend.config().maxmemory();
+            field("used_memory", used.to_string());
+            field("used_memory_human", bytes_to_human(used));
+            field("used_memory_rss", rss.to_string());
+            field("used_memory_rss_human", bytes_to_human(rss));
+            field("maxmemory", maxmemory.to_string());
+            field("maxmemory_human", bytes_to_human(maxmemory));
+            field("maxmemory_policy", "noeviction".to_string());
+        }
+        "persistence" => {
+            let rdb = backend.rdb();
+            let status = |ok: bool| if ok { "ok" } else { "err" }.to_string();
+            field("loading", "0".to_string());
+            field("rdb_changes_since_last_save", backend.dirty().to_string());
+            field(
+                "rdb_bgsave_in_progress",
+                (rdb.bgsave_in_progress() as u8).to_string(),
+            );
+            field("rdb_last_save_time", rdb.last_save().to_string());
+            field("rdb_last_bgsave_status", status(rdb.last_bgsave_ok()));
+            field(
+                "aof_enabled",
+                (backend.aof().is_enabled() as u8).to_string(),
+            );
+            field(
+                "aof_rewrite_in_progress",
+                (backend.aof().rewrite_in_progress() as u8).to_string(),
+            );
+        }
+        "stats" => {
+            field(
+                "total_connections_received",
+                stats.connections_received().to_string(),
+            );
+            field(
+                "total_commands_processed",
+                stats.commands_processed().to_string(),
+            );
+            field("rejected_connections", "0".to_string());
+            field("expired_keys", stats.expired_keys().to_string());
+            field("evicted_keys", "0".to_string());
+            field("total_error_replies", stats.error_replies().to_string());
+            let (keys, prefixes) = backend.tracking().counts();
+            field("tracking_total_keys", keys.to_string());
+            field("tracking_total_prefixes", prefixes.to_string());
+        }
+        "replication" => {
+            field("role", "master".to_string());
+            field("connected_slaves", "0".to_string());
+        }
+        "cpu" => {
+            let (sys, user) = cpu_seconds();
+            field("used_cpu_sys", format!("{:.6}", sys));
+            field("used_cpu_user", format!("{:.6}", user));
+        }
+        "commandstats" => {
+            for (name, cmd) in stats.commands() {
+                let usec_per_call = match cmd.calls {
+                    0 => 0.0,
+                    calls => cmd.usec as f64 / calls as f64,
+                };
+                field(
+                    &format!("cmdstat_{}", name),
+                    format!(
+                        "calls={},usec={},usec_per_call={:.2},rejected_calls={},failed_calls={}",
+                        cmd.calls, cmd.usec, usec_per_call, cmd.rejected_calls, cmd.failed_calls
+                    ),
+                );
+            }
+        }
+        "errorstats" => {
+            for (code, count) in stats.errors() {
+                field(&format!("errorstat_{}", code), format!("count={}", count));
+            }
+        }
+        "keyspace" => {
+            let keys = backend.hmap.len() + backend.hset.len();
+            if keys > 0 {
+                field(
+                    "db0",
+                    format!("keys={},expires={},avg_ttl=0", keys, backend.expires.len()),
+                );
+            }
+        }
+        _ => {}
+    }
+    fields
+}
+
+fn now_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_micros() as u64)
+}
+
+// the system and user CPU time in seconds from /proc/self/stat, 0 where there
+// is no procfs
+fn cpu_seconds() -> (f64, f64) {
+    let ticks = fs::read_to_string("/proc/self/stat").ok().and_then(|stat| {
+        // the fields after the command name, which may contain spaces
+        let fields = stat[stat.rfind(')')? + 1..]
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let utime = fields.get(11)?.parse::<u64>().ok()?;
+        let stime = fields.get(12)?.parse::<u64>().ok()?;
+        Some((stime, utime))
+    });
+    match ticks {
+        Some((stime, utime)) => (stime as f64 / CLOCK_TICKS, utime as f64 / CLOCK_TICKS),
+        None => (0.0, 0.0),
+    }
+}
+
+// as Redis, 1023B, 1.00K, 1.50M, 2.00G
+fn bytes_to_human(bytes: u64) -> String {
+    const UNITS: &[(u64, &str)] = &[
+        (1 << 40, "T"),
+        (1 << 30, "G"),
+        (1 << 20, "M"),
+        (1 << 10, "K"),
+    ];
+    for (size, unit) in UNITS {
+        if bytes >= *size {
+            return format!("{:.2}{}", bytes as f64 / *size as f64, unit);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cmd;
+    use anyhow::Result;
+
+    fn info(backend: &Backend, args: &[&str]) -> Result<String> {
+        match cmd(&[&["info"], args].concat())?.execute(backend)? {
+            RespFrame::BulkString(Some(info)) => Ok(String::from_utf8(info.to_vec())?),
+            frame => anyhow::bail!("unexpected reply: {:?}", frame),
+        }
+    }
+
+    fn titles(info: &str) -> Vec<&str> {
+        info.lines()
+            .filter_map(|line| line.strip_prefix("# "))
+            .collect()
+    }
+
+    #[test]
+    fn test_info_sections() -> Result<()> {
+        let backend = Backend::new();
+        let ret = info(&backend, &[])?;
+        assert_eq!(
+            titles(&ret),
+            [
+                "Server",
+                "Clients",
+                "Memory",
+                "Persistence",
+                "Stats",
+                "Replication",
+                "CPU",
+                "Errorstats",
+                "Keyspace"
+            ]
+        );
+        assert!(ret.starts_with("# Server\r\nredis_version:7.0.0\r\n"));
+        assert!(ret.contains("\r\n\r\n# Clients\r\n"));
+        assert!(ret.ends_with("# Keyspace\r\n"));
+
+        let ret = info(&backend, &["CPU", "server", "foo"])?;
+        assert_eq!(titles(&ret), ["Server", "CPU"]);
+        let ret = info(&backend, &["commandstats", "default"])?;
+        assert_eq!(titles(&ret).len(), 10);
+        assert_eq!(titles(&info(&backend, &["everything"])?).len(), 10);
+        assert_eq!(titles(&info(&backend, &["all"])?).len(), 10);
+        assert_eq!(info(&backend, &["foo"])?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_memory() -> Result<()> {
+        let backend = Backend::new();
+        let ret = info(&backend, &["memory"])?;
+        let field = |name: &str| -> u64 {
+            ret.lines()
+                .find_map(|line| line.strip_prefix(&format!("{}:", name)))
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default()
+        };
+        assert!(field("used_memory") > 0);
+        assert!(ret.contains("\r\nused_memory_rss:"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_stats() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset.insert("myset".to_string(), Default::default());
+        backend.stats.record_call("sadd", 10, false);
+        backend.stats.record_call("sadd", 20, true);
+        backend.stats.incr_rejected_call("config|get");
+        backend.stats.incr_error_reply("WRONGTYPE");
+
+        let ret = info(&backend, &["commandstats", "errorstats", "keyspace"])?;
+        assert_eq!(
+            ret,
+            "# Commandstats\r\n\
+             cmdstat_config|get:calls=0,usec=0,usec_per_call=0.00,rejected_calls=1,failed_calls=0\r\n\
+             cmdstat_sadd:calls=2,usec=30,usec_per_call=15.00,rejected_calls=0,failed_calls=1\r\n\
+             \r\n# Errorstats\r\n\
+             errorstat_WRONGTYPE:count=1\r\n\
+             \r\n# Keyspace\r\n\
+             db0:keys=1,expires=0,avg_ttl=0\r\n"
+        );
+        Ok(())
+    }
 
-        Ok(Info::new())
+    #[test]
+    fn test_bytes_to_human() {
+        assert_eq!(bytes_to_human(0), "0B");
+        assert_eq!(bytes_to_human(1023), "1023B");
+        assert_eq!(bytes_to_human(1024), "1.00K");
+        assert_eq!(bytes_to_human(3 << 19), "1.50M");
+        assert_eq!(bytes_to_human(1 << 30), "1.00G");
     }
 }
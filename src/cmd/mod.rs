@@ -1,9 +1,21 @@
+mod client;
+mod command;
+mod config;
+mod dump;
 mod echo;
+mod function;
+mod hello;
+mod hgetall;
 mod hmget;
 mod hset;
 pub mod info;
+mod pubsub;
 mod sadd;
+mod save;
+mod script;
 mod sismember;
+mod table;
+mod transaction;
 mod unrecognized;
 
 use enum_dispatch::enum_dispatch;
@@ -11,32 +23,130 @@ use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
 use thiserror::Error;
 
-use crate::{Backend, RespArray, RespError, RespFrame, SimpleString};
+use crate::{Backend, RespArray, RespError, RespFrame, SimpleError, SimpleString};
 
 use self::{
-    echo::Echo, hmget::HmGet, hset::HSet, info::Info, sadd::SAdd, sismember::SisMember,
-    unrecognized::Unrecognized,
+    echo::Echo, hgetall::HGetAll, hmget::HmGet, hset::HSet, info::Info, sadd::SAdd,
+    sismember::SisMember, unrecognized::Unrecognized,
 };
 
+pub(crate) use client::check_name;
+pub use client::{ClientCmd, KillFilter};
+pub use command::{CommandCmd, ListFilter};
+pub use config::ConfigCmd;
+pub use dump::{dump_payload, Dump, Restore};
+pub use function::{FCall, Function};
+pub use hello::Hello;
+pub use pubsub::{Subscribe, Unsubscribe};
+pub use save::{BgRewriteAof, BgSave, LastSave, Save};
+pub use script::{Eval, EvalSha, Script};
+pub use table::{
+    command_name, command_spec, command_table, is_write_command, lookup_command, read_keys,
+    CommandSpec, FindKeys, KeySpec,
+};
+pub use transaction::{Discard, Exec, Multi, Unwatch, Watch};
+
 lazy_static! {
-    static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
+    pub(crate) static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
 }
 
+// the messages are those of the error replies of Redis, without their code
 #[derive(Error, Debug)]
 pub enum CommandError {
-    #[error("Invalid command: {0}")]
+    #[error("{0}")]
     InvalidCommand(String),
-    #[error("Invalid argument: {0}")]
+    #[error("{0}")]
     InvalidArgument(String),
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
     #[error("{0}")]
     RespError(#[from] RespError),
     #[error("Utf8 error: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+// the reply to a command rejected before its execution
+impl From<CommandError> for SimpleError {
+    fn from(e: CommandError) -> Self {
+        // a simple error cannot hold a newline
+        SimpleError::new(format!("ERR {}", e).replace(['\r', '\n'], " "))
+    }
+}
+
+/// The error of a command failing at its execution, replied with the code
+/// and the message of Redis.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ExecError {
+    #[error("ERR {0}")]
+    Err(String),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    #[error("NOSCRIPT No matching script. Please use EVAL.")]
+    NoScript,
+    #[error(
+        "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE."
+    )]
+    Busy,
+    #[error("NOTBUSY No scripts in execution right now.")]
+    NotBusy,
+    #[error(
+        "UNKILLABLE Sorry the script already executed write commands against the dataset. \
+         You can either wait the script termination or kill the server in a hard way \
+         using the SHUTDOWN NOSAVE command."
+    )]
+    Unkillable,
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    Oom,
+    // an error reply with its code, as raised by a script
+    #[error("{0}")]
+    Reply(String),
+}
+
+impl ExecError {
+    pub(crate) fn err(message: impl Into<String>) -> Self {
+        ExecError::Err(message.into())
+    }
+
+    /// The code of the error, the first word of its reply.
+    pub fn code(&self) -> &str {
+        match self {
+            ExecError::Err(_) => "ERR",
+            ExecError::WrongType => "WRONGTYPE",
+            ExecError::BusyKey => "BUSYKEY",
+            ExecError::NoScript => "NOSCRIPT",
+            ExecError::Busy => "BUSY",
+            ExecError::NotBusy => "NOTBUSY",
+            ExecError::Unkillable => "UNKILLABLE",
+            ExecError::Oom => "OOM",
+            ExecError::Reply(reply) => reply.split(' ').next().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<SimpleError> for ExecError {
+    fn from(e: SimpleError) -> Self {
+        ExecError::Reply(e.0)
+    }
+}
+
+impl From<ExecError> for SimpleError {
+    fn from(e: ExecError) -> Self {
+        // the messages of the scripts may hold newlines
+        SimpleError::new(e.to_string().replace(['\r', '\n'], " "))
+    }
+}
+
+impl From<ExecError> for RespFrame {
+    fn from(e: ExecError) -> Self {
+        SimpleError::from(e).into()
+    }
+}
+
 #[enum_dispatch]
 pub trait CommandExecutor {
-    fn execute(self, backend: &Backend) -> RespFrame;
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError>;
 }
 
 #[enum_dispatch(CommandExecutor)]
@@ -47,7 +157,30 @@ pub enum Command {
     SAdd(SAdd),
     SisMember(SisMember),
     HmGet(HmGet),
+    HGetAll(HGetAll),
     HSet(HSet),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Hello(Hello),
+    Eval(Eval),
+    EvalSha(EvalSha),
+    Script(Script),
+    FCall(FCall),
+    Function(Function),
+    Save(Save),
+    BgSave(BgSave),
+    LastSave(LastSave),
+    BgRewriteAof(BgRewriteAof),
+    Dump(Dump),
+    Restore(Restore),
+    CommandCmd(CommandCmd),
+    ConfigCmd(ConfigCmd),
+    ClientCmd(ClientCmd),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
     // unrecognized command
     Unrecognized(Unrecognized),
 }
@@ -72,14 +205,10 @@ impl TryFrom<RespArray> for Command {
     fn try_from(v: RespArray) -> Result<Self, Self::Error> {
         match v.first() {
             Some(RespFrame::BulkString(ref cmd)) => match cmd {
-                Some(cmd) => match cmd.as_ref() {
-                    b"echo" => Ok(Echo::try_from(v)?.into()),
-                    b"hset" => Ok(HSet::try_from(v)?.into()),
-                    b"hmget" => Ok(HmGet::try_from(v)?.into()),
-                    b"sadd" => Ok(SAdd::try_from(v)?.into()),
-                    b"sismember" => Ok(SisMember::try_from(v)?.into()),
-                    b"info" => Ok(Info::try_from(v)?.into()),
-                    _ => Ok(Unrecognized::new(cmd.clone()).into()),
+                // the names are looked up regardless of their case
+                Some(cmd) => match lookup_command(cmd) {
+                    Some(spec) => spec.parse(v),
+                    None => Ok(Unrecognized::new(v).into()),
                 },
                 _ => Err(CommandError::InvalidCommand("Command is null".to_string())),
             },
@@ -90,37 +219,18 @@ impl TryFrom<RespArray> for Command {
     }
 }
 
-enum ArgsCheckRule {
-    Equal,
-    EqualOrGreater,
-}
-
-fn validate_command(
-    value: &RespArray,
-    names: &[&'static str],
-    n_args: usize,
-    rule: ArgsCheckRule,
-) -> Result<(), CommandError> {
-    match rule {
-        ArgsCheckRule::Equal => {
-            if value.len() != n_args + names.len() {
-                return Err(CommandError::InvalidArgument(format!(
-                    "{} command must have exactly {} argument",
-                    names.join(" "),
-                    n_args
-                )));
-            }
-        }
-        ArgsCheckRule::EqualOrGreater => {
-            if value.len() < n_args + names.len() {
-                return Err(CommandError::InvalidArgument(format!(
-                    "{} command must have minimum required {} argument",
-                    names.join(" "),
-                    n_args
-                )));
-            }
+// checks the names of the command and its subcommand, and the number of the
+// arguments against the arity of the command table
+fn validate_command(value: &RespArray, names: &[&'static str]) -> Result<(), CommandError> {
+    match lookup_command(names[0].as_bytes()) {
+        Some(spec) => spec.check(value)?,
+        None => {
+            return Err(CommandError::InvalidCommand(format!(
+                "{} is not in the command table",
+                names[0]
+            )))
         }
-    }
+    };
 
     for (i, name) in names.iter().enumerate() {
         match value[i] {
@@ -150,3 +260,16 @@ fn validate_command(
 fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
     Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_reply_newlines() {
+        let e = SimpleError::from(ExecError::Reply("x\r\n+OK".to_string()));
+        assert_eq!(e, SimpleError::new("x  +OK"));
+        let e = SimpleError::from(ExecError::err("a\nb"));
+        assert_eq!(e, SimpleError::new("ERR a b"));
+    }
+}
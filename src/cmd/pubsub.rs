@@ -0,0 +1,61 @@
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
+
+use super::{extract_args, script::to_string, validate_command};
+
+// SUBSCRIBE and UNSUBSCRIBE change the channels of the connection, they are
+// intercepted by the `Session` before reaching the executor. The channels
+// receive the invalidation messages of CLIENT TRACKING.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Subscribe {
+    pub channels: Vec<String>,
+}
+
+// all the channels if none
+#[derive(Debug, PartialEq, Eq)]
+pub struct Unsubscribe {
+    pub channels: Vec<String>,
+}
+
+impl CommandExecutor for Subscribe {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err(
+            "SUBSCRIBE is only allowed on a client connection",
+        ))
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err(
+            "UNSUBSCRIBE is only allowed on a client connection",
+        ))
+    }
+}
+
+impl TryFrom<RespArray> for Subscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["subscribe"])?;
+        Ok(Subscribe {
+            channels: channels(value)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Unsubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unsubscribe"])?;
+        Ok(Unsubscribe {
+            channels: channels(value)?,
+        })
+    }
+}
+
+fn channels(value: RespArray) -> Result<Vec<String>, CommandError> {
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|arg| to_string(Some(arg)))
+        .collect()
+}
@@ -1,6 +1,6 @@
 // use tracing::info;
 
-use crate::{Backend, CommandError, CommandExecutor, RespArray, RespFrame};
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
 
 use super::{extract_args, validate_command};
 
@@ -11,9 +11,13 @@ pub struct SAdd {
 }
 
 impl CommandExecutor for SAdd {
-    fn execute(self, backend: &Backend) -> RespFrame {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
         // println!("{:?}", self);
-        let set = backend.hset.entry(self.key).or_default();
+        backend.expire_if_needed(&self.key);
+        if backend.hmap.contains_key(&self.key) {
+            return Err(ExecError::WrongType);
+        }
+        let set = backend.hset.entry(self.key.clone()).or_default();
 
         let mut count: i64 = 0;
         for member in self.members.0 {
@@ -22,18 +26,21 @@ impl CommandExecutor for SAdd {
             }
         }
         // info!("{:?}", count);
-        count.into()
+        if count > 0 {
+            backend.signal_modified_key(&self.key);
+        }
+        Ok(count.into())
     }
 }
 
 impl TryFrom<RespArray> for SAdd {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["sadd"], 2, super::ArgsCheckRule::EqualOrGreater)?;
+        validate_command(&value, &["sadd"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         let key = match args.next() {
-            Some(RespFrame::BulkString(Some(key))) => Ok(String::from_utf8(key.0)?),
+            Some(RespFrame::BulkString(Some(key))) => Ok(String::from_utf8(key.into_vec())?),
             _ => Err(CommandError::InvalidArgument("Invalid sadd".to_string())),
         }?;
 
@@ -67,7 +74,7 @@ mod tests {
         .into();
 
         let sadd = Command::try_from(frame)?;
-        let ret = sadd.execute(&Backend::new());
+        let ret = sadd.execute(&Backend::new())?;
         assert_eq!(ret, 3.into());
         Ok(())
     }
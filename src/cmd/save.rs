@@ -0,0 +1,146 @@
+use crate::{
+    bgsave, rewrite, save, Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame,
+    SimpleString,
+};
+
+use super::{extract_args, validate_command, RESP_OK};
+
+#[derive(Debug)]
+pub struct Save;
+
+#[derive(Debug)]
+pub struct BgSave {
+    // BGSAVE SCHEDULE: wait for the running save instead of failing
+    pub schedule: bool,
+}
+
+#[derive(Debug)]
+pub struct LastSave;
+
+#[derive(Debug)]
+pub struct BgRewriteAof;
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        match save(backend) {
+            Ok(()) => Ok(RESP_OK.clone()),
+            Err(e) => Err(ExecError::err(e.to_string())),
+        }
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        if self.schedule && backend.rdb.bgsave_in_progress() {
+            backend.rdb.schedule_bgsave();
+            return Ok(SimpleString::new("Background saving scheduled").into());
+        }
+        match bgsave(backend) {
+            Ok(()) => Ok(SimpleString::new("Background saving started").into()),
+            Err(e) => Err(ExecError::err(e.to_string())),
+        }
+    }
+}
+
+impl CommandExecutor for LastSave {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        Ok((backend.rdb.last_save() as i64).into())
+    }
+}
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        match rewrite(backend) {
+            Ok(()) => Ok(SimpleString::new("Background append only file rewriting started").into()),
+            Err(e) => Err(ExecError::err(e.to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Save {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["save"])?;
+        Ok(Save)
+    }
+}
+
+impl TryFrom<RespArray> for BgSave {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgsave"])?;
+        if value.len() > 2 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        match extract_args(value, 1)?.pop() {
+            None => Ok(BgSave { schedule: false }),
+            Some(RespFrame::BulkString(Some(arg))) if arg.eq_ignore_ascii_case(b"schedule") => {
+                Ok(BgSave { schedule: true })
+            }
+            Some(_) => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LastSave {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lastsave"])?;
+        Ok(LastSave)
+    }
+}
+
+impl TryFrom<RespArray> for BgRewriteAof {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgrewriteaof"])?;
+        Ok(BgRewriteAof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+    use crate::test_util::{cmd, temp_path};
+    use anyhow::Result;
+
+    #[test]
+    fn test_save_and_lastsave() -> Result<()> {
+        let backend = Backend::new();
+        let path = temp_path("test-cmd-save.rdb");
+        backend.rdb.set_path(&path);
+
+        let ret = cmd(&["save"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(path.exists());
+
+        let ret = cmd(&["lastsave"])?.execute(&backend)?;
+        assert_eq!(ret, (backend.rdb.last_save() as i64).into());
+
+        let ret = cmd(&["bgsave"])?.execute(&backend)?;
+        assert_eq!(ret, SimpleString::new("Background saving started").into());
+        let ret = cmd(&["bgsave", "schedule"])?.execute(&backend)?;
+        assert!(
+            ret == SimpleString::new("Background saving scheduled").into()
+                || ret == SimpleString::new("Background saving started").into()
+        );
+        while backend.rdb.bgsave_in_progress() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        std::fs::remove_file(&path)?;
+        assert!(cmd(&["bgsave", "now"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bgrewriteaof_disabled() -> Result<()> {
+        let backend = Backend::new();
+        let ret = cmd(&["bgrewriteaof"])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::err("Append only file is disabled")));
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{
+    eval_script, Backend, BulkString, CommandError, CommandExecutor, ExecError, RespArray,
+    RespFrame,
+};
+
+use super::{extract_args, validate_command, RESP_OK};
+
+#[derive(Debug)]
+pub struct Eval {
+    pub script: String,
+    pub keys: Vec<BulkString>,
+    pub args: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct EvalSha {
+    pub sha1: String,
+    pub keys: Vec<BulkString>,
+    pub args: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub enum Script {
+    Load(String),
+    Exists(Vec<String>),
+    Flush,
+    Kill,
+}
+
+impl CommandExecutor for Eval {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        let sha = backend.scripts.load(self.script.as_str());
+        eval_script(backend, &sha, &self.script, self.keys, self.args)
+    }
+}
+
+impl CommandExecutor for EvalSha {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        match backend.scripts.get(&self.sha1) {
+            Some(script) => eval_script(backend, &self.sha1, &script, self.keys, self.args),
+            None => Err(ExecError::NoScript),
+        }
+    }
+}
+
+impl CommandExecutor for Script {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
+        match self {
+            Script::Load(script) => Ok(Some(BulkString::from(backend.scripts.load(script))).into()),
+            Script::Exists(shas) => Ok(Some(RespArray::new(
+                shas.iter()
+                    .map(|sha| (backend.scripts.exists(sha) as i64).into())
+                    .collect::<Vec<RespFrame>>(),
+            ))
+            .into()),
+            Script::Flush => {
+                backend.scripts.flush();
+                Ok(RESP_OK.clone())
+            }
+            Script::Kill => backend.scripts.kill(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Eval {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["eval"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let script = to_string(args.next())?;
+        let (keys, args) = split_keys_and_args(args)?;
+        Ok(Eval { script, keys, args })
+    }
+}
+
+impl TryFrom<RespArray> for EvalSha {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["evalsha"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sha1 = to_string(args.next())?;
+        let (keys, args) = split_keys_and_args(args)?;
+        Ok(EvalSha { sha1, keys, args })
+    }
+}
+
+impl TryFrom<RespArray> for Script {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = match value.get(1) {
+            Some(RespFrame::BulkString(Some(sub))) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "script command must have a subcommand".to_string(),
+                ))
+            }
+        };
+
+        match sub.as_slice() {
+            b"load" => {
+                validate_command(&value, &["script", "load"])?;
+                let mut args = extract_args(value, 2)?.into_iter();
+                Ok(Script::Load(to_string(args.next())?))
+            }
+            b"exists" => {
+                validate_command(&value, &["script", "exists"])?;
+                let shas = extract_args(value, 2)?
+                    .into_iter()
+                    .map(|arg| to_string(Some(arg)))
+                    .collect::<Result<Vec<String>, CommandError>>()?;
+                Ok(Script::Exists(shas))
+            }
+            b"flush" => {
+                validate_command(&value, &["script", "flush"])?;
+                // the ASYNC and SYNC modes are accepted, flushing is always synchronous
+                if value.len() > 2 {
+                    let mode = to_string(value.get(2).cloned())?.to_ascii_lowercase();
+                    if value.len() > 3 || (mode != "async" && mode != "sync") {
+                        return Err(CommandError::InvalidArgument(
+                            "SCRIPT FLUSH only support SYNC|ASYNC option".to_string(),
+                        ));
+                    }
+                }
+                Ok(Script::Flush)
+            }
+            b"kill" => {
+                validate_command(&value, &["script", "kill"])?;
+                Ok(Script::Kill)
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'",
+                String::from_utf8_lossy(&sub)
+            ))),
+        }
+    }
+}
+
+pub(super) fn to_string(arg: Option<RespFrame>) -> Result<String, CommandError> {
+    match arg {
+        Some(RespFrame::BulkString(Some(s))) => Ok(String::from_utf8(s.into_vec())?),
+        _ => Err(CommandError::InvalidArgument(
+            "argument must be a bulk string".to_string(),
+        )),
+    }
+}
+
+// numkeys key [key ...] arg [arg ...]
+pub(super) fn split_keys_and_args(
+    mut args: impl Iterator<Item = RespFrame>,
+) -> Result<(Vec<BulkString>, Vec<BulkString>), CommandError> {
+    let numkeys: i64 = to_string(args.next())?.parse().map_err(|_| {
+        CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+    })?;
+    if numkeys < 0 {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be negative".to_string(),
+        ));
+    }
+
+    let mut rest = args
+        .map(|arg| match arg {
+            RespFrame::BulkString(Some(s)) => Ok(s),
+            _ => Err(CommandError::InvalidArgument(
+                "argument must be a bulk string".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<BulkString>, CommandError>>()?;
+    if numkeys as usize > rest.len() {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be greater than number of args".to_string(),
+        ));
+    }
+
+    let args = rest.split_off(numkeys as usize);
+    Ok((rest, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RespArray;
+
+    use super::*;
+    use crate::test_util::cmd;
+    use anyhow::Result;
+
+    #[test]
+    fn test_eval_and_evalsha() -> Result<()> {
+        let backend = Backend::new();
+        let script = "return ARGV[1]";
+        let ret = cmd(&["eval", script, "0", "hello"])?.execute(&backend)?;
+        assert_eq!(ret, b"hello".into());
+
+        let sha = crate::sha1_hex(script.as_bytes());
+        let ret = cmd(&["evalsha", &sha.to_uppercase(), "0", "world"])?.execute(&backend)?;
+        assert_eq!(ret, b"world".into());
+
+        let ret =
+            cmd(&["evalsha", "ffffffffffffffffffffffffffffffffffffffff", "0"])?.execute(&backend);
+        assert_eq!(ret, Err(ExecError::NoScript));
+
+        assert!(cmd(&["eval", script, "2", "key"]).is_err());
+        assert!(cmd(&["eval", script, "-1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_load_exists_flush() -> Result<()> {
+        let backend = Backend::new();
+        let ret = cmd(&["script", "load", "return 1"])?.execute(&backend)?;
+        let sha = crate::sha1_hex(b"return 1");
+        assert_eq!(ret, Some(BulkString::from(sha.clone())).into());
+
+        let ret = cmd(&["script", "exists", &sha, "nosuchsha"])?.execute(&backend)?;
+        assert_eq!(ret, Some(RespArray::new(vec![1.into(), 0.into()])).into());
+
+        let ret = cmd(&["script", "flush", "async"])?.execute(&backend)?;
+        assert_eq!(ret, RESP_OK.clone());
+        let ret = cmd(&["script", "exists", &sha])?.execute(&backend)?;
+        assert_eq!(ret, Some(RespArray::new(vec![0.into()])).into());
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 // use tracing::info;
 
-use crate::{Backend, CommandError, CommandExecutor, RespArray, RespFrame};
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
 
 use super::{extract_args, validate_command};
 
@@ -11,8 +11,12 @@ pub struct SisMember {
 }
 
 impl CommandExecutor for SisMember {
-    fn execute(self, backend: &Backend) -> RespFrame {
+    fn execute(self, backend: &Backend) -> Result<RespFrame, ExecError> {
         // println!("{:?}", self);
+        backend.expire_if_needed(&self.key);
+        if backend.hmap.contains_key(&self.key) {
+            return Err(ExecError::WrongType);
+        }
         let exist: i64 = match backend.hset.get(&self.key) {
             Some(set) => match set.get(&self.member) {
                 Some(_) => 1,
@@ -21,24 +25,19 @@ impl CommandExecutor for SisMember {
             None => 0,
         };
         // info!("{:?}", exist);
-        exist.into()
+        Ok(exist.into())
     }
 }
 
 impl TryFrom<RespArray> for SisMember {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(
-            &value,
-            &["sismember"],
-            2,
-            super::ArgsCheckRule::EqualOrGreater,
-        )?;
+        validate_command(&value, &["sismember"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(Some(key))), Some(member)) => Ok(SisMember {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.into_vec())?,
                 member,
             }),
             _ => Err(CommandError::InvalidArgument("Invalid echo".to_string())),
@@ -67,7 +66,7 @@ mod tests {
         .into();
 
         let sadd = Command::try_from(frame)?;
-        let ret = sadd.execute(&backend);
+        let ret = sadd.execute(&backend)?;
         assert_eq!(ret, 3.into());
 
         // sismember myset A
@@ -79,7 +78,7 @@ mod tests {
         .into();
 
         let sis_member = Command::try_from(frame)?;
-        let ret = sis_member.execute(&backend);
+        let ret = sis_member.execute(&backend)?;
         assert_eq!(ret, 1.into());
 
         // sismember myset D
@@ -91,7 +90,7 @@ mod tests {
         .into();
 
         let sis_member = Command::try_from(frame)?;
-        let ret = sis_member.execute(&backend);
+        let ret = sis_member.execute(&backend)?;
         assert_eq!(ret, 0.into());
         Ok(())
     }
@@ -0,0 +1,854 @@
+use crate::{RespArray, RespFrame};
+
+use super::{
+    script::to_string, BgRewriteAof, BgSave, ClientCmd, Command, CommandCmd, CommandError,
+    ConfigCmd, Discard, Dump, Echo, Eval, EvalSha, Exec, FCall, Function, HGetAll, HSet, Hello,
+    HmGet, Info, LastSave, Multi, Restore, SAdd, Save, Script, SisMember, Subscribe, Unsubscribe,
+    Unwatch, Watch,
+};
+
+type Parser = fn(RespArray) -> Result<Command, CommandError>;
+
+/// A command of the command table: its arity, flags, key specifications and
+/// documentation as reported by COMMAND, and the parser of its arguments.
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    // the number of arguments including the name, -N for N or more
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    pub key_specs: &'static [KeySpec],
+    pub subcommands: &'static [CommandSpec],
+    // the group of the documentation, the subcommands are in the group of their command
+    pub group: &'static str,
+    pub since: &'static str,
+    pub summary: &'static str,
+    pub complexity: &'static str,
+    // the subcommands are parsed by their command
+    parse: Option<Parser>,
+}
+
+/// Where the keys of a command are in its arguments, as a key specification
+/// of Redis 7 searching from an index.
+#[derive(Debug)]
+pub struct KeySpec {
+    pub flags: &'static [&'static str],
+    pub begin_search: usize,
+    pub find_keys: FindKeys,
+}
+
+#[derive(Debug)]
+pub enum FindKeys {
+    // the keys up to the last key, relative to the beginning or negative from
+    // the end of the arguments
+    Range {
+        lastkey: i64,
+        keystep: usize,
+    },
+    // the number of keys is an argument, the keys follow it
+    KeyNum {
+        keynumidx: usize,
+        firstkey: usize,
+        keystep: usize,
+    },
+}
+
+impl KeySpec {
+    const fn range(
+        flags: &'static [&'static str],
+        begin_search: usize,
+        lastkey: i64,
+        keystep: usize,
+    ) -> Self {
+        KeySpec {
+            flags,
+            begin_search,
+            find_keys: FindKeys::Range { lastkey, keystep },
+        }
+    }
+
+    const fn keynum(flags: &'static [&'static str], begin_search: usize) -> Self {
+        KeySpec {
+            flags,
+            begin_search,
+            find_keys: FindKeys::KeyNum {
+                keynumidx: 0,
+                firstkey: 1,
+                keystep: 1,
+            },
+        }
+    }
+
+    // the positions of the keys in the arguments
+    fn positions(&self, args: &RespArray) -> Result<Vec<usize>, CommandError> {
+        let begin = self.begin_search;
+        if begin >= args.len() {
+            return Ok(vec![]);
+        }
+        let (first, last, step) = match self.find_keys {
+            FindKeys::Range { lastkey, keystep } => {
+                let last = if lastkey < 0 {
+                    args.len().checked_sub(lastkey.unsigned_abs() as usize)
+                } else {
+                    Some(begin + lastkey as usize)
+                };
+                (begin, last, keystep)
+            }
+            FindKeys::KeyNum {
+                keynumidx,
+                firstkey,
+                keystep,
+            } => {
+                let numkeys = to_string(args.get(begin + keynumidx).cloned())?
+                    .parse::<usize>()
+                    .map_err(|_| invalid_arguments())?;
+                let first = begin + firstkey;
+                if numkeys == 0 {
+                    return Ok(vec![]);
+                }
+                (first, Some(first + (numkeys - 1) * keystep), keystep)
+            }
+        };
+        match last {
+            Some(last) if last >= first && last < args.len() => {
+                Ok((first..=last).step_by(step).collect())
+            }
+            _ => Err(invalid_arguments()),
+        }
+    }
+}
+
+impl CommandSpec {
+    const fn new(name: &'static str, arity: i64, group: &'static str, parse: Parser) -> Self {
+        CommandSpec {
+            group,
+            parse: Some(parse),
+            ..CommandSpec::sub(name, arity)
+        }
+    }
+
+    const fn sub(name: &'static str, arity: i64) -> Self {
+        CommandSpec {
+            name,
+            arity,
+            flags: &[],
+            key_specs: &[],
+            subcommands: &[],
+            group: "",
+            since: "",
+            summary: "",
+            complexity: "",
+            parse: None,
+        }
+    }
+
+    const fn flags(mut self, flags: &'static [&'static str]) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    const fn keys(mut self, key_specs: &'static [KeySpec]) -> Self {
+        self.key_specs = key_specs;
+        self
+    }
+
+    const fn subcommands(mut self, subcommands: &'static [CommandSpec]) -> Self {
+        self.subcommands = subcommands;
+        self
+    }
+
+    const fn docs(
+        mut self,
+        since: &'static str,
+        summary: &'static str,
+        complexity: &'static str,
+    ) -> Self {
+        self.since = since;
+        self.summary = summary;
+        self.complexity = complexity;
+        self
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Whether the number of keys is an argument of the command.
+    pub fn movable_keys(&self) -> bool {
+        self.key_specs
+            .iter()
+            .any(|spec| matches!(spec.find_keys, FindKeys::KeyNum { .. }))
+    }
+
+    /// The first key, the last key (negative from the end) and the step of the
+    /// keys as reported before key specifications, 0 for the commands without
+    /// keys or with movable keys.
+    pub fn key_range(&self) -> (i64, i64, i64) {
+        match self.key_specs {
+            [KeySpec {
+                begin_search,
+                find_keys: FindKeys::Range { lastkey, keystep },
+                ..
+            }] => {
+                let first = *begin_search as i64;
+                let last = if *lastkey < 0 {
+                    *lastkey
+                } else {
+                    first + lastkey
+                };
+                (first, last, *keystep as i64)
+            }
+            _ => (0, 0, 0),
+        }
+    }
+
+    /// The positions of the keys in the arguments of the command, with the
+    /// flags of their key specification.
+    pub fn keys_of(
+        &self,
+        args: &RespArray,
+    ) -> Result<Vec<(usize, &'static [&'static str])>, CommandError> {
+        let mut keys = Vec::new();
+        for spec in self.key_specs {
+            for i in spec.positions(args)? {
+                keys.push((i, spec.flags));
+            }
+        }
+        Ok(keys)
+    }
+
+    fn arity_matches(&self, argc: usize) -> bool {
+        let arity = self.arity.unsigned_abs() as usize;
+        if self.arity < 0 {
+            argc >= arity
+        } else {
+            argc == arity
+        }
+    }
+
+    /// Checks the number of arguments against the arity of the command, or of
+    /// its subcommand, and returns the spec of the subcommand if any.
+    pub(crate) fn check(&'static self, args: &RespArray) -> Result<&'static Self, CommandError> {
+        if !self.arity_matches(args.len()) {
+            return Err(wrong_arity(self.name));
+        }
+        if self.subcommands.is_empty() {
+            return Ok(self);
+        }
+
+        let sub = match args.get(1) {
+            Some(RespFrame::BulkString(Some(sub))) => sub,
+            // COMMAND without a subcommand
+            None => return Ok(self),
+            _ => return Err(wrong_arity(self.name)),
+        };
+        let spec = self
+            .subcommands
+            .iter()
+            .find(|spec| spec.name.as_bytes().eq_ignore_ascii_case(sub))
+            .ok_or_else(|| {
+                CommandError::InvalidCommand(format!(
+                    "unknown subcommand '{}'. Try {} HELP.",
+                    String::from_utf8_lossy(sub),
+                    self.name.to_ascii_uppercase()
+                ))
+            })?;
+        if !spec.arity_matches(args.len()) {
+            return Err(wrong_arity(&format!("{}|{}", self.name, spec.name)));
+        }
+        Ok(spec)
+    }
+
+    /// Parses the arguments of the command after checking their number.
+    pub(crate) fn parse(&'static self, args: RespArray) -> Result<Command, CommandError> {
+        self.check(&args)?;
+        match self.parse {
+            Some(parse) => parse(args),
+            None => Err(CommandError::InvalidCommand(format!(
+                "{} is a subcommand",
+                self.name
+            ))),
+        }
+    }
+}
+
+fn wrong_arity(name: &str) -> CommandError {
+    CommandError::WrongArity(name.to_string())
+}
+
+fn invalid_arguments() -> CommandError {
+    CommandError::InvalidArgument("Invalid arguments specified for command".to_string())
+}
+
+fn parser<T>(args: RespArray) -> Result<Command, CommandError>
+where
+    T: TryFrom<RespArray, Error = CommandError> + Into<Command>,
+{
+    Ok(T::try_from(args)?.into())
+}
+
+// the flags, key specifications and documentation are those of Redis 7
+static COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("echo", 2, "connection", parser::<Echo>)
+        .flags(&["loading", "stale", "fast"])
+        .docs("1.0.0", "Returns the given string.", "O(1)"),
+    CommandSpec::new("hset", 4, "hash", parser::<HSet>)
+        .flags(&["write", "denyoom", "fast"])
+        .keys(&[KeySpec::range(&["RW", "UPDATE"], 1, 0, 1)])
+        .docs(
+            "2.0.0",
+            "Creates or modifies the value of a field in a hash.",
+            "O(1) for each field/value pair added, so O(N) to add N field/value pairs when the command is called with multiple field/value pairs.",
+        ),
+    CommandSpec::new("hmget", -3, "hash", parser::<HmGet>)
+        .flags(&["readonly", "fast"])
+        .keys(&[KeySpec::range(&["RO", "ACCESS"], 1, 0, 1)])
+        .docs(
+            "2.0.0",
+            "Returns the values of all fields in a hash.",
+            "O(N) where N is the number of fields being requested.",
+        ),
+    CommandSpec::new("hgetall", 2, "hash", parser::<HGetAll>)
+        .flags(&["readonly"])
+        .keys(&[KeySpec::range(&["RO", "ACCESS"], 1, 0, 1)])
+        .docs(
+            "2.0.0",
+            "Returns all fields and values in a hash.",
+            "O(N) where N is the size of the hash.",
+        ),
+    CommandSpec::new("sadd", -3, "set", parser::<SAdd>)
+        .flags(&["write", "denyoom", "fast"])
+        .keys(&[KeySpec::range(&["RW", "INSERT"], 1, 0, 1)])
+        .docs(
+            "1.0.0",
+            "Adds one or more members to a set. Creates the key if it doesn't exist.",
+            "O(1) for each element added, so O(N) to add N elements when the command is called with multiple arguments.",
+        ),
+    CommandSpec::new("sismember", 3, "set", parser::<SisMember>)
+        .flags(&["readonly", "fast"])
+        .keys(&[KeySpec::range(&["RO"], 1, 0, 1)])
+        .docs(
+            "1.0.0",
+            "Determines whether a member belongs to a set.",
+            "O(1)",
+        ),
+    CommandSpec::new("info", -1, "server", parser::<Info>)
+        .flags(&["loading", "stale"])
+        .docs(
+            "1.0.0",
+            "Returns information and statistics about the server.",
+            "O(1)",
+        ),
+    CommandSpec::new("multi", 1, "transactions", parser::<Multi>)
+        .flags(&["noscript", "loading", "stale", "fast", "allow_busy"])
+        .docs("1.2.0", "Starts a transaction.", "O(1)"),
+    CommandSpec::new("exec", 1, "transactions", parser::<Exec>)
+        .flags(&["noscript", "loading", "stale", "skip_slowlog"])
+        .docs(
+            "1.2.0",
+            "Executes all commands in a transaction.",
+            "Depends on commands in the transaction",
+        ),
+    CommandSpec::new("discard", 1, "transactions", parser::<Discard>)
+        .flags(&["noscript", "loading", "stale", "fast", "allow_busy"])
+        .docs(
+            "2.0.0",
+            "Discards a transaction.",
+            "O(N), when N is the number of queued commands",
+        ),
+    CommandSpec::new("watch", -2, "transactions", parser::<Watch>)
+        .flags(&["noscript", "loading", "stale", "fast", "allow_busy"])
+        .keys(&[KeySpec::range(&["RO"], 1, -1, 1)])
+        .docs(
+            "2.2.0",
+            "Monitors changes to keys to determine the execution of a transaction.",
+            "O(1) for every key.",
+        ),
+    CommandSpec::new("unwatch", 1, "transactions", parser::<Unwatch>)
+        .flags(&["noscript", "loading", "stale", "fast", "allow_busy"])
+        .docs(
+            "2.2.0",
+            "Forgets about watched keys of a transaction.",
+            "O(1)",
+        ),
+    CommandSpec::new("hello", -1, "connection", parser::<Hello>)
+        .flags(&[
+            "noscript",
+            "loading",
+            "stale",
+            "fast",
+            "no_auth",
+            "allow_busy",
+        ])
+        .docs("6.0.0", "Handshakes with the Redis server.", "O(1)"),
+    CommandSpec::new("subscribe", -2, "pubsub", parser::<Subscribe>)
+        .flags(&["pubsub", "noscript", "loading", "stale"])
+        .docs(
+            "2.0.0",
+            "Listens for messages published to channels.",
+            "O(N) where N is the number of channels to subscribe to.",
+        ),
+    CommandSpec::new("unsubscribe", -1, "pubsub", parser::<Unsubscribe>)
+        .flags(&["pubsub", "noscript", "loading", "stale"])
+        .docs(
+            "2.0.0",
+            "Stops listening to messages posted to channels.",
+            "O(N) where N is the number of channels to unsubscribe.",
+        ),
+    CommandSpec::new("eval", -3, "scripting", parser::<Eval>)
+        .flags(&[
+            "noscript",
+            "skip_monitor",
+            "may_replicate",
+            "no_mandatory_keys",
+            "stale",
+        ])
+        .keys(&[KeySpec::keynum(&["RW", "ACCESS", "UPDATE"], 2)])
+        .docs(
+            "2.6.0",
+            "Executes a server-side Lua script.",
+            "Depends on the script that is executed.",
+        ),
+    CommandSpec::new("evalsha", -3, "scripting", parser::<EvalSha>)
+        .flags(&[
+            "noscript",
+            "skip_monitor",
+            "may_replicate",
+            "no_mandatory_keys",
+            "stale",
+        ])
+        .keys(&[KeySpec::keynum(&["RW", "ACCESS", "UPDATE"], 2)])
+        .docs(
+            "2.6.0",
+            "Executes a server-side Lua script by SHA1 digest.",
+            "Depends on the script that is executed.",
+        ),
+    CommandSpec::new("script", -2, "scripting", parser::<Script>)
+        .subcommands(&[
+            CommandSpec::sub("load", 3)
+                .flags(&["noscript", "stale"])
+                .docs(
+                    "2.6.0",
+                    "Loads a server-side Lua script to the script cache.",
+                    "O(N) with N being the length in bytes of the script body.",
+                ),
+            CommandSpec::sub("exists", -3)
+                .flags(&["noscript"])
+                .docs(
+                    "2.6.0",
+                    "Determines whether server-side Lua scripts exist in the script cache.",
+                    "O(N) with N being the number of scripts to check (so checking a single script is an O(1) operation).",
+                ),
+            CommandSpec::sub("flush", -2)
+                .flags(&["noscript"])
+                .docs(
+                    "2.6.0",
+                    "Removes all server-side Lua scripts from the script cache.",
+                    "O(N) with N being the number of scripts in cache",
+                ),
+            CommandSpec::sub("kill", 2)
+                .flags(&["noscript", "allow_busy"])
+                .docs(
+                    "2.6.0",
+                    "Terminates a server-side Lua script during execution.",
+                    "O(1)",
+                ),
+        ])
+        .docs(
+            "2.6.0",
+            "A container for Lua scripts management commands.",
+            "Depends on subcommand.",
+        ),
+    CommandSpec::new("fcall", -3, "scripting", parser::<FCall>)
+        .flags(&[
+            "noscript",
+            "skip_monitor",
+            "may_replicate",
+            "no_mandatory_keys",
+            "stale",
+        ])
+        .keys(&[KeySpec::keynum(&["RW", "ACCESS", "UPDATE"], 2)])
+        .docs(
+            "7.0.0",
+            "Invokes a function.",
+            "Depends on the function that is executed.",
+        ),
+    CommandSpec::new("fcall_ro", -3, "scripting", parser::<FCall>)
+        .flags(&[
+            "readonly",
+            "noscript",
+            "skip_monitor",
+            "no_mandatory_keys",
+            "stale",
+        ])
+        .keys(&[KeySpec::keynum(&["RO", "ACCESS"], 2)])
+        .docs(
+            "7.0.0",
+            "Invokes a read-only function.",
+            "Depends on the function that is executed.",
+        ),
+    CommandSpec::new("function", -2, "scripting", parser::<Function>)
+        .subcommands(&[
+            CommandSpec::sub("load", -3)
+                .flags(&["write", "denyoom", "noscript"])
+                .docs(
+                    "7.0.0",
+                    "Creates a library.",
+                    "O(1) (considering compilation time is redundant)",
+                ),
+            CommandSpec::sub("list", -2)
+                .flags(&["noscript"])
+                .docs(
+                    "7.0.0",
+                    "Returns information about all libraries.",
+                    "O(N) where N is the number of functions",
+                ),
+            CommandSpec::sub("delete", 3)
+                .flags(&["write", "noscript"])
+                .docs("7.0.0", "Deletes a library and its functions.", "O(1)"),
+            CommandSpec::sub("dump", 2)
+                .flags(&["noscript"])
+                .docs(
+                    "7.0.0",
+                    "Dumps all libraries into a serialized binary payload.",
+                    "O(N) where N is the number of functions",
+                ),
+            CommandSpec::sub("restore", -3)
+                .flags(&["write", "denyoom", "noscript"])
+                .docs(
+                    "7.0.0",
+                    "Restores all libraries from a payload.",
+                    "O(N) where N is the number of functions on the payload",
+                ),
+            CommandSpec::sub("flush", -2)
+                .flags(&["write", "noscript"])
+                .docs(
+                    "7.0.0",
+                    "Deletes all libraries and functions.",
+                    "O(N) where N is the number of functions deleted",
+                ),
+            CommandSpec::sub("kill", 2)
+                .flags(&["noscript", "allow_busy"])
+                .docs("7.0.0", "Terminates a function during execution.", "O(1)"),
+        ])
+        .docs(
+            "7.0.0",
+            "A container for function commands.",
+            "Depends on subcommand.",
+        ),
+    CommandSpec::new("save", 1, "server", parser::<Save>)
+        .flags(&["admin", "noscript", "no_async_loading", "no_multi"])
+        .docs(
+            "1.0.0",
+            "Synchronously saves the database(s) to disk.",
+            "O(N) where N is the total number of keys in all databases",
+        ),
+    CommandSpec::new("bgsave", -1, "server", parser::<BgSave>)
+        .flags(&["admin", "noscript", "no_async_loading"])
+        .docs(
+            "1.0.0",
+            "Asynchronously saves the database(s) to disk.",
+            "O(1)",
+        ),
+    CommandSpec::new("lastsave", 1, "server", parser::<LastSave>)
+        .flags(&["loading", "stale", "fast"])
+        .docs(
+            "1.0.0",
+            "Returns the Unix timestamp of the last successful save to disk.",
+            "O(1)",
+        ),
+    CommandSpec::new("bgrewriteaof", 1, "server", parser::<BgRewriteAof>)
+        .flags(&["admin", "noscript", "no_async_loading"])
+        .docs(
+            "1.0.0",
+            "Asynchronously rewrites the append-only file to disk.",
+            "O(1)",
+        ),
+    CommandSpec::new("dump", 2, "generic", parser::<Dump>)
+        .flags(&["readonly"])
+        .keys(&[KeySpec::range(&["RO", "ACCESS"], 1, 0, 1)])
+        .docs(
+            "2.6.0",
+            "Returns a serialized representation of the value stored at a key.",
+            "O(1) to access the key and additional O(N*M) to serialize it, where N is the number of Redis objects composing the value and M their average size.",
+        ),
+    CommandSpec::new("restore", -4, "generic", parser::<Restore>)
+        .flags(&["write", "denyoom"])
+        .keys(&[KeySpec::range(&["OW", "UPDATE"], 1, 0, 1)])
+        .docs(
+            "2.6.0",
+            "Creates a key from the serialized representation of a value.",
+            "O(1) to create the new key and additional O(N*M) to reconstruct the serialized value, where N is the number of Redis objects composing the value and M their average size.",
+        ),
+    CommandSpec::new("command", -1, "server", parser::<CommandCmd>)
+        .flags(&["loading", "stale"])
+        .subcommands(&[
+            CommandSpec::sub("count", 2)
+                .flags(&["loading", "stale"])
+                .docs("2.8.13", "Returns a count of commands.", "O(1)"),
+            CommandSpec::sub("docs", -2)
+                .flags(&["loading", "stale"])
+                .docs(
+                    "7.0.0",
+                    "Returns documentary information about one, multiple or all commands.",
+                    "O(N) where N is the number of commands to look up",
+                ),
+            CommandSpec::sub("getkeys", -3)
+                .flags(&["loading", "stale"])
+                .docs(
+                    "2.8.13",
+                    "Extracts the key names from an arbitrary command.",
+                    "O(N) where N is the number of arguments to the command",
+                ),
+            CommandSpec::sub("getkeysandflags", -3)
+                .flags(&["loading", "stale"])
+                .docs(
+                    "7.0.0",
+                    "Extracts the key names and access flags for an arbitrary command.",
+                    "O(N) where N is the number of arguments to the command",
+                ),
+            CommandSpec::sub("info", -2)
+                .flags(&["loading", "stale"])
+                .docs(
+                    "2.8.13",
+                    "Returns information about one, multiple or all commands.",
+                    "O(N) where N is the number of commands to look up",
+                ),
+            CommandSpec::sub("list", -2)
+                .flags(&["loading", "stale"])
+                .docs(
+                    "7.0.0",
+                    "Returns a list of command names.",
+                    "O(N) where N is the total number of Redis commands",
+                ),
+        ])
+        .docs(
+            "2.8.13",
+            "Returns detailed information about all commands.",
+            "O(N) where N is the total number of Redis commands",
+        ),
+    CommandSpec::new("config", -2, "server", parser::<ConfigCmd>)
+        .subcommands(&[
+            CommandSpec::sub("get", -3)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs(
+                    "2.0.0",
+                    "Returns the effective values of configuration parameters.",
+                    "O(N) when N is the number of configuration parameters provided",
+                ),
+            CommandSpec::sub("set", -4)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs(
+                    "2.0.0",
+                    "Sets configuration parameters in-flight.",
+                    "O(N) when N is the number of configuration parameters provided",
+                ),
+            CommandSpec::sub("rewrite", 2)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs(
+                    "2.8.0",
+                    "Persists the effective configuration to file.",
+                    "O(1)",
+                ),
+            CommandSpec::sub("resetstat", 2)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs("2.0.0", "Resets the server's statistics.", "O(1)"),
+        ])
+        .docs(
+            "2.0.0",
+            "A container for server configuration commands.",
+            "Depends on subcommand.",
+        ),
+    CommandSpec::new("client", -2, "connection", parser::<ClientCmd>)
+        .subcommands(&[
+            CommandSpec::sub("id", 2)
+                .flags(&["noscript", "loading", "stale"])
+                .docs("5.0.0", "Returns the unique client ID of the connection.", "O(1)"),
+            CommandSpec::sub("info", 2)
+                .flags(&["noscript", "loading", "stale"])
+                .docs("6.2.0", "Returns information about the connection.", "O(1)"),
+            CommandSpec::sub("list", -2)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs(
+                    "2.4.0",
+                    "Lists open connections.",
+                    "O(N) where N is the number of client connections",
+                ),
+            CommandSpec::sub("kill", -3)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs(
+                    "2.4.0",
+                    "Terminates open connections.",
+                    "O(N) where N is the number of client connections",
+                ),
+            CommandSpec::sub("setname", 3)
+                .flags(&["noscript", "loading", "stale"])
+                .docs("2.6.9", "Sets the connection name.", "O(1)"),
+            CommandSpec::sub("getname", 2)
+                .flags(&["noscript", "loading", "stale"])
+                .docs("2.6.9", "Returns the name of the connection.", "O(1)"),
+            CommandSpec::sub("tracking", -3)
+                .flags(&["noscript", "loading", "stale"])
+                .docs(
+                    "6.0.0",
+                    "Controls server-assisted client-side caching for the connection.",
+                    "O(1). Some options may introduce additional complexity.",
+                ),
+            CommandSpec::sub("caching", 3)
+                .flags(&["noscript", "loading", "stale"])
+                .docs(
+                    "6.0.0",
+                    "Instructs the server whether to track the keys in the next request.",
+                    "O(1)",
+                ),
+            CommandSpec::sub("no-evict", 3)
+                .flags(&["admin", "noscript", "loading", "stale"])
+                .docs(
+                    "7.0.0",
+                    "Sets the client eviction mode of the connection.",
+                    "O(1)",
+                ),
+        ])
+        .docs(
+            "2.4.0",
+            "A container for client connection commands.",
+            "Depends on subcommand.",
+        ),
+];
+
+/// All the commands of the server.
+pub fn command_table() -> &'static [CommandSpec] {
+    COMMANDS
+}
+
+/// The command of the name, regardless of its case.
+pub fn lookup_command(name: &[u8]) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name.as_bytes().eq_ignore_ascii_case(name))
+}
+
+/// The name of the command in the statistics, `command|subcommand` for a
+/// subcommand, None for an unknown command.
+pub fn command_name(args: &RespArray) -> Option<String> {
+    let spec = match args.first() {
+        Some(RespFrame::BulkString(Some(name))) => lookup_command(name)?,
+        _ => return None,
+    };
+    let sub = match args.get(1) {
+        Some(RespFrame::BulkString(Some(sub))) => spec
+            .subcommands
+            .iter()
+            .find(|spec| spec.name.as_bytes().eq_ignore_ascii_case(sub)),
+        _ => None,
+    };
+    match sub {
+        Some(sub) => Some(format!("{}|{}", spec.name, sub.name)),
+        None => Some(spec.name.to_string()),
+    }
+}
+
+/// The spec of the command of the arguments, or of its subcommand, None for
+/// an unknown command or a wrong number of arguments.
+pub fn command_spec(args: &RespArray) -> Option<&'static CommandSpec> {
+    match args.first() {
+        Some(RespFrame::BulkString(Some(name))) => lookup_command(name)?.check(args).ok(),
+        _ => None,
+    }
+}
+
+/// Whether the command of the arguments may modify the dataset.
+pub fn is_write_command(args: &RespArray) -> bool {
+    command_spec(args).is_some_and(|spec| spec.has_flag("write"))
+}
+
+/// The keys of a read-only command, none for other commands.
+pub fn read_keys(args: &RespArray) -> Vec<String> {
+    let Some(spec) = command_spec(args) else {
+        return vec![];
+    };
+    if !spec.has_flag("readonly") {
+        return vec![];
+    }
+    spec.keys_of(args)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(i, _)| to_string(args.get(i).cloned()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::array;
+    use anyhow::Result;
+
+    #[test]
+    fn test_command_table() {
+        for (i, spec) in COMMANDS.iter().enumerate() {
+            assert_eq!(spec.name, spec.name.to_ascii_lowercase());
+            assert!(spec.parse.is_some(), "{}", spec.name);
+            assert!(COMMANDS[..i].iter().all(|other| other.name != spec.name));
+            for sub in spec.subcommands {
+                assert!(sub.parse.is_none(), "{}|{}", spec.name, sub.name);
+            }
+        }
+        assert_eq!(lookup_command(b"HSet").map(|spec| spec.name), Some("hset"));
+        assert!(lookup_command(b"unknown").is_none());
+    }
+
+    #[test]
+    fn test_case_insensitive_dispatch() -> Result<()> {
+        let cmd = Command::try_from(array(&["HSET", "map", "hello", "world"]))?;
+        assert!(matches!(cmd, Command::HSet(_)));
+        let cmd = Command::try_from(array(&["eChO", "hello"]))?;
+        assert!(matches!(cmd, Command::Echo(_)));
+        let cmd = Command::try_from(array(&["SCRIPT", "FLUSH", "ASYNC"]))?;
+        assert!(matches!(cmd, Command::Script(Script::Flush)));
+        let cmd = Command::try_from(array(&["GET", "key"]))?;
+        assert!(matches!(cmd, Command::Unrecognized(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_write_command() {
+        assert!(is_write_command(&array(&["HSET", "map", "hello", "world"])));
+        assert!(is_write_command(&array(&["function", "FLUSH"])));
+        assert!(!is_write_command(&array(&["function", "list"])));
+        assert!(!is_write_command(&array(&["hmget", "map", "hello"])));
+        assert!(!is_write_command(&array(&["sadd", "set"])));
+        assert!(!is_write_command(&array(&["get", "key"])));
+    }
+
+    #[test]
+    fn test_arity() {
+        let err = Command::try_from(array(&["echo"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'echo' command"
+        );
+        let err = Command::try_from(array(&["ECHO", "a", "b"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'echo' command"
+        );
+        assert!(Command::try_from(array(&["hmget", "map"])).is_err());
+        assert!(Command::try_from(array(&["hmget", "map", "a", "b"])).is_ok());
+
+        let err = Command::try_from(array(&["script"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'script' command"
+        );
+        let err = Command::try_from(array(&["script", "load"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'script|load' command"
+        );
+        let err = Command::try_from(array(&["function", "foo"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown subcommand 'foo'. Try FUNCTION HELP."
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{Backend, CommandError, CommandExecutor, ExecError, RespArray, RespFrame};
+
+use super::{extract_args, validate_command, RESP_OK};
+
+// MULTI, EXEC, DISCARD and WATCH only make sense on a client connection, they
+// are intercepted by the `Session` before reaching the executor.
+
+#[derive(Debug)]
+pub struct Multi;
+
+#[derive(Debug)]
+pub struct Exec;
+
+#[derive(Debug)]
+pub struct Discard;
+
+#[derive(Debug)]
+pub struct Watch {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Unwatch;
+
+impl CommandExecutor for Multi {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err(
+            "MULTI is only allowed on a client connection",
+        ))
+    }
+}
+
+impl CommandExecutor for Exec {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err("EXEC without MULTI"))
+    }
+}
+
+impl CommandExecutor for Discard {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err("DISCARD without MULTI"))
+    }
+}
+
+impl CommandExecutor for Watch {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Err(ExecError::err(
+            "WATCH is only allowed on a client connection",
+        ))
+    }
+}
+
+// UNWATCH queued in a transaction runs after the watched keys were checked,
+// and EXEC forgets all watched keys anyway
+impl CommandExecutor for Unwatch {
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for Multi {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["multi"])?;
+        Ok(Multi)
+    }
+}
+
+impl TryFrom<RespArray> for Exec {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exec"])?;
+        Ok(Exec)
+    }
+}
+
+impl TryFrom<RespArray> for Discard {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["discard"])?;
+        Ok(Discard)
+    }
+}
+
+impl TryFrom<RespArray> for Watch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["watch"])?;
+
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(Some(key)) => Ok(String::from_utf8(key.into_vec())?),
+                _ => Err(CommandError::InvalidArgument("Invalid watch".to_string())),
+            })
+            .collect::<Result<Vec<String>, CommandError>>()?;
+
+        Ok(Watch { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Unwatch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unwatch"])?;
+        Ok(Unwatch)
+    }
+}
@@ -1,20 +1,90 @@
-use crate::{Backend, BulkString, CommandExecutor, RespFrame, SimpleError};
+use crate::{Backend, CommandExecutor, ExecError, RespArray, RespFrame};
 
+// the arguments of an unknown command, its name first
 #[derive(Debug)]
-pub struct Unrecognized(pub(crate) BulkString);
+pub struct Unrecognized(pub(crate) RespArray);
 
 impl CommandExecutor for Unrecognized {
-    fn execute(self, _: &Backend) -> RespFrame {
-        SimpleError::new(format!(
-            "ERR unknown command '{}', with args beginning with:",
-            self.0
-        ))
-        .into()
+    fn execute(self, _: &Backend) -> Result<RespFrame, ExecError> {
+        let mut args = self.0.iter().map(|arg| match arg {
+            RespFrame::BulkString(Some(arg)) => String::from_utf8_lossy(arg).to_string(),
+            _ => String::new(),
+        });
+        let name = args.next().unwrap_or_default();
+
+        // as Redis, the name and the arguments are cut after 128 bytes
+        let mut quoted = String::new();
+        for arg in args {
+            if quoted.len() >= 128 {
+                break;
+            }
+            let arg = truncate(&arg, 128 - quoted.len());
+            quoted.push_str(&format!("'{}' ", arg));
+        }
+        let message = format!(
+            "unknown command '{}', with args beginning with: {}",
+            truncate(&name, 128),
+            quoted
+        );
+        // a simple error cannot hold a newline
+        Err(ExecError::Err(message.replace(['\r', '\n'], " ")))
     }
 }
 
+// the longest prefix of at most len bytes on a char boundary
+fn truncate(s: &str, len: usize) -> &str {
+    let mut end = len.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 impl Unrecognized {
-    pub fn new(cmd: BulkString) -> Self {
-        Self(cmd)
+    pub fn new(argv: RespArray) -> Self {
+        Self(argv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::array, SimpleError};
+
+    #[test]
+    fn test_unrecognized() {
+        let backend = Backend::new();
+
+        let ret = Unrecognized::new(array(&["foo"])).execute(&backend);
+        assert_eq!(
+            ret,
+            Err(ExecError::err(
+                "unknown command 'foo', with args beginning with: "
+            ))
+        );
+        let ret = Unrecognized::new(array(&["foo", "a b", "c\r\nd"])).execute(&backend);
+        assert_eq!(
+            ret,
+            Err(ExecError::err(
+                "unknown command 'foo', with args beginning with: 'a b' 'c  d' "
+            ))
+        );
+
+        let long = "x".repeat(200);
+        let ret = Unrecognized::new(array(&[&long, &long, "b"])).execute(&backend);
+        let expected = format!(
+            "unknown command '{}', with args beginning with: '{}' ",
+            "x".repeat(128),
+            "x".repeat(128)
+        );
+        assert_eq!(ret, Err(ExecError::Err(expected)));
+        assert_eq!(
+            RespFrame::from(ret.unwrap_err()),
+            SimpleError::new(format!(
+                "ERR unknown command '{0}', with args beginning with: '{0}' ",
+                "x".repeat(128)
+            ))
+            .into()
+        );
     }
 }
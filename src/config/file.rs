@@ -0,0 +1,397 @@
+// The config file has the format of redis.conf: a directive per line, its name
+// followed by its arguments, quoted as the arguments of an inline command.
+// The command line options are directives as well: `--port 6380` is `port 6380`.
+
+use std::{fs, io, path::Path};
+
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{respv4::split_args, Backend};
+
+use super::{lookup_param, ConfigParam, PARAMS};
+
+const REWRITE_SIGNATURE: &str = "# Generated by CONFIG REWRITE";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Fatal error, can't open config file '{0}': {1}")]
+    Open(String, io::Error),
+    #[error("Reading the configuration file, at line {line}: '{text}': {reason}")]
+    Invalid {
+        line: usize,
+        text: String,
+        reason: String,
+    },
+    #[error("Invalid command line option '{0}'")]
+    Option(String),
+}
+
+/// Configures the server from the command line arguments, as redis-server:
+/// `[/path/to/redis.conf] [--<directive> <args>...]...`, the options taking
+/// precedence over the file.
+pub fn load_config(backend: &Backend, args: &[String]) -> Result<(), ConfigError> {
+    let (file, options) = match args.split_first() {
+        Some((file, options)) if !file.starts_with("--") => (Some(file), options),
+        _ => (None, args),
+    };
+
+    let mut loader = Loader::default();
+    if let Some(file) = file {
+        let open = |e| ConfigError::Open(file.clone(), e);
+        // kept absolute for CONFIG REWRITE, `dir` changes the working directory
+        let path = fs::canonicalize(file).map_err(open)?;
+        let text = fs::read_to_string(&path).map_err(open)?;
+        backend.config.set_file(Some(path));
+        loader.load(backend, &text, false)?;
+    }
+    loader.load(backend, &options_to_text(options)?, true)
+}
+
+// the options as lines of directives
+fn options_to_text(options: &[String]) -> Result<String, ConfigError> {
+    let mut text = String::new();
+    for option in options {
+        match option.strip_prefix("--") {
+            Some(name) => {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(name);
+            }
+            None if text.is_empty() => return Err(ConfigError::Option(option.clone())),
+            None => {
+                text.push(' ');
+                text.push_str(&quote(option));
+            }
+        }
+    }
+    Ok(text)
+}
+
+#[derive(Default)]
+struct Loader {
+    // the save points are added by successive `save` lines
+    save: Option<String>,
+}
+
+impl Loader {
+    // unknown directives are skipped in a file, so that the file of a Redis
+    // server can be used, and rejected in the options
+    fn load(&mut self, backend: &Backend, text: &str, strict: bool) -> Result<(), ConfigError> {
+        for (i, line) in text.lines().enumerate() {
+            let invalid = |reason: &str| ConfigError::Invalid {
+                line: i + 1,
+                text: line.to_string(),
+                reason: reason.to_string(),
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let args = split_args(trimmed.as_bytes())
+                .ok_or_else(|| invalid("Unbalanced quotes in configuration line"))?
+                .into_iter()
+                .map(|arg| String::from_utf8_lossy(&arg).to_string())
+                .collect::<Vec<_>>();
+            let Some((name, values)) = args.split_first() else {
+                continue;
+            };
+
+            let param = match lookup_param(name) {
+                Some(param) => param,
+                None if strict => {
+                    return Err(invalid("Bad directive or wrong number of arguments"))
+                }
+                None => {
+                    warn!(
+                        "Unsupported directive '{}' at line {}, skipped",
+                        name,
+                        i + 1
+                    );
+                    continue;
+                }
+            };
+            if values.is_empty() || (values.len() > 1 && !param.multi_arg) {
+                return Err(invalid("wrong number of arguments"));
+            }
+            let mut value = values.join(" ");
+            if param.name == "save" {
+                value = match self.save.take() {
+                    Some(save) if !save.is_empty() && !value.is_empty() => {
+                        format!("{} {}", save, value)
+                    }
+                    _ => value,
+                };
+                self.save = Some(value.clone());
+            }
+            param.set(backend, &value).map_err(|e| invalid(&e))?;
+        }
+        Ok(())
+    }
+}
+
+/// CONFIG REWRITE: writes the current configuration to the config file, the
+/// lines of the parameters are updated in place and the comments are kept.
+pub fn rewrite_config(backend: &Backend) -> Result<(), String> {
+    let file = backend
+        .config
+        .file()
+        .ok_or_else(|| "The server is running without a config file".to_string())?;
+    let failed = |e: io::Error| format!("Rewriting config file: {}", e);
+
+    let old = match fs::read_to_string(&file) {
+        Ok(old) => old,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(failed(e)),
+    };
+    write_file(&file, &rewrite_text(backend, &old)).map_err(failed)
+}
+
+fn rewrite_text(backend: &Backend, old: &str) -> String {
+    let mut done: Vec<&str> = Vec::new();
+    let mut lines = Vec::new();
+    for line in old.lines() {
+        let trimmed = line.trim();
+        let param = match trimmed.starts_with('#') {
+            true => None,
+            false => split_args(trimmed.as_bytes())
+                .and_then(|args| args.into_iter().next())
+                .and_then(|name| lookup_param(&String::from_utf8_lossy(&name))),
+        };
+        match param {
+            // the other lines of the parameter are removed
+            Some(param) if done.contains(&param.name) => {}
+            Some(param) => {
+                done.push(param.name);
+                lines.extend(param_lines(backend, param));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    // the parameters missing from the file are only written when not the default
+    let missing = PARAMS
+        .iter()
+        .filter(|param| !done.contains(&param.name) && param.get(backend) != param.default)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() && !lines.iter().any(|line| line == REWRITE_SIGNATURE) {
+        lines.push(REWRITE_SIGNATURE.to_string());
+    }
+    for param in missing {
+        lines.extend(param_lines(backend, param));
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+fn param_lines(backend: &Backend, param: &ConfigParam) -> Vec<String> {
+    let value = param.get(backend);
+    match param.name {
+        // a line for each save point
+        "save" if !value.is_empty() => {
+            let args = value.split(' ').collect::<Vec<_>>();
+            args.chunks(2)
+                .map(|point| format!("save {}", point.join(" ")))
+                .collect()
+        }
+        _ if param.multi_arg && !value.is_empty() => vec![format!("{} {}", param.name, value)],
+        _ => vec![format!("{} {}", param.name, quote(&value))],
+    }
+}
+
+// the value quoted when it is not a single plain argument
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b'\'' | b'\\'));
+    if plain {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for b in value.bytes() {
+        match b {
+            b'\\' | b'"' => {
+                quoted.push('\\');
+                quoted.push(b as char);
+            }
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            b' ' => quoted.push(' '),
+            b if b.is_ascii_graphic() => quoted.push(b as char),
+            b => quoted.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// written to a temporary file first, the config file is never left half written
+fn write_file(path: &Path, text: &str) -> io::Result<()> {
+    let tmp = path.with_file_name(format!(
+        "{}.tmp-{}",
+        path.file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default(),
+        std::process::id()
+    ));
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::*;
+    use crate::{test_util::temp_path, AppendFsync, SaveParam};
+
+    fn temp_file(name: &str, text: &str) -> PathBuf {
+        let path = temp_path(&format!("{}.conf", name));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_load_config() -> anyhow::Result<()> {
+        let path = temp_file(
+            "test-config-load",
+            "# a comment\n\
+             port 7000\n\
+             \n\
+             bind 127.0.0.1 -::1\n\
+             save 900 1\n\
+             save 300 10\n\
+             appendfsync always\n\
+             lua-time-limit 100\n\
+             tcp-backlog 511\n",
+        );
+        let backend = Backend::new();
+        let options = [
+            "--port",
+            "7001",
+            "--maxmemory",
+            "100mb",
+            "--loglevel",
+            "warning",
+        ];
+        load_config(
+            &backend,
+            &args(&[&[path.to_str().unwrap()][..], &options].concat()),
+        )?;
+
+        assert_eq!(backend.config.port(), 7001);
+        assert_eq!(backend.config.bind(), vec!["127.0.0.1", "-::1"]);
+        assert_eq!(
+            backend.rdb.save_params(),
+            vec![
+                SaveParam {
+                    seconds: 900,
+                    changes: 1
+                },
+                SaveParam {
+                    seconds: 300,
+                    changes: 10
+                }
+            ]
+        );
+        assert_eq!(backend.aof.fsync(), AppendFsync::Always);
+        assert_eq!(backend.scripts.time_limit(), Duration::from_millis(100));
+        assert_eq!(backend.config.maxmemory(), 100 * 1024 * 1024);
+        assert_eq!(backend.config.loglevel().to_string(), "warning");
+        assert_eq!(backend.config.file(), Some(fs::canonicalize(&path)?));
+
+        // `save ""` disables the snapshots
+        load_config(&backend, &args(&["--save", ""]))?;
+        assert!(backend.rdb.save_params().is_empty());
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_errors() {
+        let backend = Backend::new();
+        let cases: &[(&[&str], &str)] = &[
+            (
+                &["--port", "abc"],
+                "Reading the configuration file, at line 1: 'port abc': argument couldn't be parsed into an integer",
+            ),
+            (
+                &["--nosuch", "1"],
+                "Reading the configuration file, at line 1: 'nosuch 1': Bad directive or wrong number of arguments",
+            ),
+            (
+                &["--timeout", "1", "--port"],
+                "Reading the configuration file, at line 2: 'port': wrong number of arguments",
+            ),
+            (&["--port", "1", "2"], "Reading the configuration file, at line 1: 'port 1 2': wrong number of arguments"),
+            (&["/nosuch/redis.conf"], "Fatal error, can't open config file '/nosuch/redis.conf': No such file or directory (os error 2)"),
+        ];
+        for (options, err) in cases {
+            let ret = load_config(&backend, &args(options));
+            assert_eq!(ret.unwrap_err().to_string(), *err);
+        }
+
+        let path = temp_file("test-config-quotes", "dbfilename \"dump.rdb\n");
+        let ret = load_config(&backend, &args(&[path.to_str().unwrap()]));
+        assert!(matches!(ret, Err(ConfigError::Invalid { line: 1, .. })));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_config() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            rewrite_config(&backend),
+            Err("The server is running without a config file".to_string())
+        );
+
+        let path = temp_file(
+            "test-config-rewrite",
+            "# the port\n\
+             port 7000\n\
+             save 900 1\n\
+             unknown directive\n\
+             save 300 10\n",
+        );
+        load_config(&backend, &args(&[path.to_str().unwrap()]))?;
+        backend.config.set_loglevel("verbose".parse().unwrap());
+        backend.rdb.set_path("my dump.rdb");
+        rewrite_config(&backend).unwrap();
+
+        let text = fs::read_to_string(&path)?;
+        let dir = quote(&std::env::current_dir()?.display().to_string());
+        assert_eq!(
+            text,
+            format!(
+                "# the port\n\
+                 port 7000\n\
+                 save 900 1\n\
+                 save 300 10\n\
+                 unknown directive\n\
+                 {}\n\
+                 loglevel verbose\n\
+                 dir {}\n\
+                 dbfilename \"my dump.rdb\"\n",
+                REWRITE_SIGNATURE, dir
+            )
+        );
+
+        // a rewritten file is loaded as it was written
+        let loaded = Backend::new();
+        load_config(&loaded, &args(&[path.to_str().unwrap()]))?;
+        assert_eq!(loaded.rdb.path(), PathBuf::from("my dump.rdb"));
+        assert_eq!(rewrite_text(&loaded, &text), text);
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,697 @@
+mod file;
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU16, AtomicU64, AtomicU8, Ordering},
+        PoisonError, RwLock,
+    },
+    time::Duration,
+};
+
+use tracing::level_filters::LevelFilter;
+
+use crate::{start_aof, stop_aof, util::glob_match, AppendFsync, Backend, RespLimits, SaveParam};
+
+pub use file::{load_config, rewrite_config, ConfigError};
+
+// the defaults of redis.conf
+const DEFAULT_PORT: u16 = 6379;
+const DEFAULT_DATABASES: u64 = 16;
+
+/// `loglevel`: the least severe messages written to the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Verbose,
+    Notice,
+    Warning,
+    Nothing,
+}
+
+/// The parameters of the server which are not kept by the state they configure.
+#[derive(Debug)]
+pub struct ConfigState {
+    // the config file given at startup, rewritten by CONFIG REWRITE
+    file: RwLock<Option<PathBuf>>,
+    bind: RwLock<Vec<String>>,
+    port: AtomicU16,
+    // only the database 0 exists, the value is reported
+    databases: AtomicU64,
+    // the commands with the denyoom flag are rejected above it, 0 for no limit;
+    // nothing is evicted
+    maxmemory: AtomicU64,
+    // seconds before an idle client is disconnected, 0 to never disconnect it
+    timeout: AtomicU64,
+    loglevel: AtomicU8,
+}
+
+impl Default for ConfigState {
+    fn default() -> Self {
+        Self {
+            file: RwLock::new(None),
+            bind: RwLock::new(vec!["*".to_string(), "-::*".to_string()]),
+            port: AtomicU16::new(DEFAULT_PORT),
+            databases: AtomicU64::new(DEFAULT_DATABASES),
+            maxmemory: AtomicU64::new(0),
+            timeout: AtomicU64::new(0),
+            loglevel: AtomicU8::new(LogLevel::Notice as u8),
+        }
+    }
+}
+
+impl ConfigState {
+    pub fn file(&self) -> Option<PathBuf> {
+        self.file
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_file(&self, file: Option<PathBuf>) {
+        *self.file.write().unwrap_or_else(PoisonError::into_inner) = file;
+    }
+
+    /// The addresses to listen on, an address starting with '-' is skipped
+    /// when it is not available.
+    pub fn bind(&self) -> Vec<String> {
+        self.bind
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    pub fn databases(&self) -> u64 {
+        self.databases.load(Ordering::Relaxed)
+    }
+
+    pub fn maxmemory(&self) -> u64 {
+        self.maxmemory.load(Ordering::Relaxed)
+    }
+
+    /// The idle time after which a client is disconnected, `None` to never disconnect it.
+    pub fn timeout(&self) -> Option<Duration> {
+        match self.timeout.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    pub fn loglevel(&self) -> LogLevel {
+        LogLevel::from_u8(self.loglevel.load(Ordering::Relaxed))
+    }
+
+    pub fn set_loglevel(&self, level: LogLevel) {
+        self.loglevel.store(level as u8, Ordering::Relaxed);
+    }
+}
+
+impl LogLevel {
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Verbose,
+            2 => LogLevel::Notice,
+            3 => LogLevel::Warning,
+            _ => LogLevel::Nothing,
+        }
+    }
+
+    /// The most verbose level of the `tracing` events written at this level.
+    pub fn level_filter(&self) -> LevelFilter {
+        match self {
+            LogLevel::Debug => LevelFilter::TRACE,
+            LogLevel::Verbose => LevelFilter::DEBUG,
+            LogLevel::Notice => LevelFilter::INFO,
+            LogLevel::Warning => LevelFilter::WARN,
+            LogLevel::Nothing => LevelFilter::OFF,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "verbose" => Ok(LogLevel::Verbose),
+            "notice" => Ok(LogLevel::Notice),
+            "warning" => Ok(LogLevel::Warning),
+            "nothing" => Ok(LogLevel::Nothing),
+            _ => Err(
+                "argument(s) must be one of the following: debug, verbose, notice, warning, nothing"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Debug => write!(f, "debug"),
+            LogLevel::Verbose => write!(f, "verbose"),
+            LogLevel::Notice => write!(f, "notice"),
+            LogLevel::Warning => write!(f, "warning"),
+            LogLevel::Nothing => write!(f, "nothing"),
+        }
+    }
+}
+
+type Getter = fn(&Backend) -> String;
+type Setter = fn(&Backend, &str) -> Result<(), String>;
+type Applier = fn(&Backend) -> Result<(), String>;
+
+/// A parameter of the config file and of CONFIG GET/SET.
+#[derive(Debug)]
+pub(crate) struct ConfigParam {
+    pub name: &'static str,
+    pub alias: Option<&'static str>,
+    pub mutable: bool,
+    // the value of a server started without config, as returned by `get`
+    pub default: &'static str,
+    // the values of several arguments are separated by spaces
+    pub multi_arg: bool,
+    get: Getter,
+    set: Setter,
+    // the runtime effect of CONFIG SET, once all the values are set
+    apply: Option<Applier>,
+}
+
+impl ConfigParam {
+    const fn new(name: &'static str, default: &'static str, get: Getter, set: Setter) -> Self {
+        Self {
+            name,
+            alias: None,
+            mutable: true,
+            default,
+            multi_arg: false,
+            get,
+            set,
+            apply: None,
+        }
+    }
+
+    const fn immutable(mut self) -> Self {
+        self.mutable = false;
+        self
+    }
+
+    const fn alias(mut self, alias: &'static str) -> Self {
+        self.alias = Some(alias);
+        self
+    }
+
+    const fn multi_arg(mut self) -> Self {
+        self.multi_arg = true;
+        self
+    }
+
+    const fn apply(mut self, apply: Applier) -> Self {
+        self.apply = Some(apply);
+        self
+    }
+
+    pub fn get(&self, backend: &Backend) -> String {
+        (self.get)(backend)
+    }
+
+    pub fn set(&self, backend: &Backend, value: &str) -> Result<(), String> {
+        (self.set)(backend, value)
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .alias
+                .is_some_and(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+static PARAMS: &[ConfigParam] = &[
+    ConfigParam::new(
+        "bind",
+        "* -::*",
+        |backend| backend.config.bind().join(" "),
+        |backend, value| {
+            let addrs = value
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            if addrs.len() > 16 {
+                return Err("Too many bind addresses specified.".to_string());
+            }
+            *backend
+                .config
+                .bind
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = addrs;
+            Ok(())
+        },
+    )
+    .immutable()
+    .multi_arg(),
+    ConfigParam::new(
+        "port",
+        "6379",
+        |backend| backend.config.port().to_string(),
+        |backend, value| {
+            let port = parse_int(value, 0, u16::MAX as u64)?;
+            backend.config.port.store(port as u16, Ordering::Relaxed);
+            Ok(())
+        },
+    )
+    .immutable(),
+    ConfigParam::new(
+        "databases",
+        "16",
+        |backend| backend.config.databases().to_string(),
+        |backend, value| {
+            let databases = parse_int(value, 1, i32::MAX as u64)?;
+            backend.config.databases.store(databases, Ordering::Relaxed);
+            Ok(())
+        },
+    )
+    .immutable(),
+    ConfigParam::new(
+        "maxmemory",
+        "0",
+        |backend| backend.config.maxmemory().to_string(),
+        |backend, value| {
+            let maxmemory = parse_memory(value)?;
+            backend.config.maxmemory.store(maxmemory, Ordering::Relaxed);
+            Ok(())
+        },
+    ),
+    ConfigParam::new(
+        "timeout",
+        "0",
+        |backend| backend.config.timeout.load(Ordering::Relaxed).to_string(),
+        |backend, value| {
+            let timeout = parse_int(value, 0, i32::MAX as u64)?;
+            backend.config.timeout.store(timeout, Ordering::Relaxed);
+            Ok(())
+        },
+    ),
+    ConfigParam::new(
+        "loglevel",
+        "notice",
+        |backend| backend.config.loglevel().to_string(),
+        |backend, value| {
+            backend.config.set_loglevel(value.parse()?);
+            Ok(())
+        },
+    ),
+    ConfigParam::new(
+        "busy-reply-threshold",
+        "5000",
+        |backend| backend.scripts.time_limit().as_millis().to_string(),
+        |backend, value| {
+            let limit = parse_int(value, 0, i64::MAX as u64)?;
+            backend.scripts.set_time_limit(Duration::from_millis(limit));
+            Ok(())
+        },
+    )
+    .alias("lua-time-limit"),
+    ConfigParam::new(
+        "proto-max-bulk-len",
+        "536870912",
+        |_| RespLimits::current().max_bulk_len.to_string(),
+        |_, value| {
+            let len = parse_memory(value)?;
+            if len < 1024 * 1024 {
+                return Err(format!(
+                    "argument must be between {} and {} inclusive",
+                    1024 * 1024,
+                    i64::MAX
+                ));
+            }
+            RespLimits {
+                max_bulk_len: len as usize,
+                ..RespLimits::current()
+            }
+            .apply();
+            Ok(())
+        },
+    ),
+    // the files are relative to the working directory, as in Redis
+    ConfigParam::new(
+        "dir",
+        ".",
+        |_| {
+            std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default()
+        },
+        |_, value| std::env::set_current_dir(value).map_err(|e| e.to_string()),
+    ),
+    ConfigParam::new(
+        "dbfilename",
+        "dump.rdb",
+        |backend| backend.rdb.path().display().to_string(),
+        |backend, value| {
+            check_filename("dbfilename", value)?;
+            backend.rdb.set_path(value);
+            Ok(())
+        },
+    ),
+    ConfigParam::new(
+        "save",
+        "3600 1 300 100 60 10000",
+        |backend| {
+            let params = backend.rdb.save_params();
+            let params = params
+                .iter()
+                .map(|param| format!("{} {}", param.seconds, param.changes));
+            params.collect::<Vec<_>>().join(" ")
+        },
+        |backend, value| {
+            backend.rdb.set_save_params(parse_save_params(value)?);
+            Ok(())
+        },
+    )
+    .multi_arg(),
+    ConfigParam::new(
+        "appendonly",
+        "no",
+        |backend| yes_no(backend.aof.is_enabled()),
+        |backend, value| {
+            backend.aof.set_enabled(parse_bool(value)?);
+            Ok(())
+        },
+    )
+    .apply(|backend| {
+        let ret = match backend.aof.is_enabled() {
+            true => start_aof(backend),
+            false => stop_aof(backend),
+        };
+        ret.map_err(|e| e.to_string())
+    }),
+    ConfigParam::new(
+        "appendfsync",
+        "everysec",
+        |backend| backend.aof.fsync().to_string(),
+        |backend, value| {
+            backend.aof.set_fsync(value.parse::<AppendFsync>()?);
+            Ok(())
+        },
+    ),
+    ConfigParam::new(
+        "appendfilename",
+        "appendonly.aof",
+        |backend| backend.aof.filename(),
+        |backend, value| {
+            check_filename("appendfilename", value)?;
+            backend.aof.set_filename(value);
+            Ok(())
+        },
+    )
+    .immutable(),
+    ConfigParam::new(
+        "appenddirname",
+        "appendonlydir",
+        |backend| backend.aof.dir().display().to_string(),
+        |backend, value| {
+            check_filename("appenddirname", value)?;
+            backend.aof.set_dir(value);
+            Ok(())
+        },
+    )
+    .immutable(),
+];
+
+/// The parameter of the name or alias, regardless of its case.
+pub(crate) fn lookup_param(name: &str) -> Option<&'static ConfigParam> {
+    PARAMS.iter().find(|param| param.is_named(name))
+}
+
+/// CONFIG GET: the parameters matching any of the glob patterns, and their values.
+pub fn config_get(backend: &Backend, patterns: &[String]) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for param in PARAMS {
+        for name in std::iter::once(param.name).chain(param.alias) {
+            let matched = patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes(), true));
+            if matched {
+                values.insert(name.to_string(), param.get(backend));
+            }
+        }
+    }
+    values
+}
+
+/// CONFIG SET: sets all the parameters or none of them. The runtime effects are
+/// applied once all the values are valid, the previous values are restored if
+/// one of them fails.
+pub fn config_set(backend: &Backend, values: &[(String, String)]) -> Result<(), String> {
+    let failed = |name: &str, e: &str| {
+        format!(
+            "CONFIG SET failed (possibly related to argument '{}') - {}",
+            name, e
+        )
+    };
+
+    let mut params: Vec<(&ConfigParam, &str)> = Vec::with_capacity(values.len());
+    for (name, value) in values {
+        let param = lookup_param(name).ok_or_else(|| {
+            format!(
+                "Unknown option or number of arguments for CONFIG SET - '{}'",
+                name
+            )
+        })?;
+        if !param.mutable {
+            return Err(failed(name, "can't set immutable config"));
+        }
+        if params.iter().any(|(p, _)| p.name == param.name) {
+            return Err(failed(name, "duplicate parameter"));
+        }
+        params.push((param, value));
+    }
+
+    let old = params
+        .iter()
+        .map(|(param, _)| param.get(backend))
+        .collect::<Vec<_>>();
+    let restore = || {
+        for ((param, _), old) in params.iter().zip(&old) {
+            let _ = param.set(backend, old);
+        }
+    };
+    for (param, value) in &params {
+        if let Err(e) = param.set(backend, value) {
+            restore();
+            return Err(failed(param.name, &e));
+        }
+    }
+    // only the parameters whose value changed are applied
+    for ((param, _), old) in params.iter().zip(&old) {
+        let apply = match param.apply {
+            Some(apply) if param.get(backend) != *old => apply,
+            _ => continue,
+        };
+        if let Err(e) = apply(backend) {
+            restore();
+            return Err(failed(param.name, &e));
+        }
+    }
+    Ok(())
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+fn parse_int(value: &str, min: u64, max: u64) -> Result<u64, String> {
+    let n = value
+        .parse::<i64>()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    match u64::try_from(n) {
+        Ok(n) if (min..=max).contains(&n) => Ok(n),
+        _ => Err(format!(
+            "argument must be between {} and {} inclusive",
+            min, max
+        )),
+    }
+}
+
+/// A number of bytes with an optional unit: 1k is 1000 bytes, 1kb is 1024 bytes,
+/// and so on for m, mb, g and gb.
+pub(crate) fn parse_memory(value: &str) -> Result<u64, String> {
+    let invalid = || "argument must be a memory value".to_string();
+    let value = value.to_ascii_lowercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let mul: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(mul))
+        .filter(|n| *n <= i64::MAX as u64)
+        .ok_or_else(invalid)
+}
+
+// `save 3600 1 300 100`, or `save ""` to disable the snapshots
+fn parse_save_params(value: &str) -> Result<Vec<SaveParam>, String> {
+    let args = value.split_whitespace().collect::<Vec<_>>();
+    if args.len() % 2 != 0 {
+        return Err("Invalid save parameters".to_string());
+    }
+    args.chunks(2)
+        .map(|pair| match (pair[0].parse(), pair[1].parse()) {
+            (Ok(seconds), Ok(changes)) => Ok(SaveParam { seconds, changes }),
+            _ => Err("Invalid save parameters".to_string()),
+        })
+        .collect()
+}
+
+fn check_filename(name: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() || value.contains(['/', '\\']) {
+        return Err(format!("{} can't be a path, just a filename", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(backend: &Backend, values: &[(&str, &str)]) -> Result<(), String> {
+        let values = values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        config_set(backend, &values)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let backend = Backend::new();
+        for param in PARAMS.iter().filter(|param| param.name != "dir") {
+            assert_eq!(param.get(&backend), param.default, "{}", param.name);
+        }
+    }
+
+    #[test]
+    fn test_config_get() {
+        let backend = Backend::new();
+        let values = config_get(&backend, &["APPEND*".to_string(), "port".to_string()]);
+        assert_eq!(
+            values.keys().collect::<Vec<_>>(),
+            [
+                "appenddirname",
+                "appendfilename",
+                "appendfsync",
+                "appendonly",
+                "port"
+            ]
+        );
+        assert_eq!(values["appendonly"], "no");
+
+        let values = config_get(&backend, &["lua-*".to_string()]);
+        assert_eq!(values["lua-time-limit"], "5000");
+        assert!(config_get(&backend, &["nosuch".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_config_set() {
+        let backend = Backend::new();
+        set(
+            &backend,
+            &[
+                ("maxmemory", "1mb"),
+                ("save", "10 1"),
+                ("appendfsync", "ALWAYS"),
+                ("lua-time-limit", "100"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(backend.config.maxmemory(), 1024 * 1024);
+        assert_eq!(
+            backend.rdb.save_params(),
+            vec![SaveParam {
+                seconds: 10,
+                changes: 1
+            }]
+        );
+        assert_eq!(backend.aof.fsync(), AppendFsync::Always);
+        assert_eq!(backend.scripts.time_limit(), Duration::from_millis(100));
+
+        set(&backend, &[("save", "")]).unwrap();
+        assert!(backend.rdb.save_params().is_empty());
+    }
+
+    #[test]
+    fn test_config_set_errors() {
+        let backend = Backend::new();
+        let cases: &[(&[(&str, &str)], &str)] = &[
+            (
+                &[("nosuch", "1")],
+                "Unknown option or number of arguments for CONFIG SET - 'nosuch'",
+            ),
+            (
+                &[("port", "6380")],
+                "CONFIG SET failed (possibly related to argument 'port') - can't set immutable config",
+            ),
+            (
+                &[("timeout", "1"), ("TIMEOUT", "2")],
+                "CONFIG SET failed (possibly related to argument 'TIMEOUT') - duplicate parameter",
+            ),
+            (
+                &[("timeout", "10"), ("maxmemory", "1xb")],
+                "CONFIG SET failed (possibly related to argument 'maxmemory') - argument must be a memory value",
+            ),
+            (
+                &[("dbfilename", "../dump.rdb")],
+                "CONFIG SET failed (possibly related to argument 'dbfilename') - dbfilename can't be a path, just a filename",
+            ),
+            (
+                &[("save", "10")],
+                "CONFIG SET failed (possibly related to argument 'save') - Invalid save parameters",
+            ),
+        ];
+        for (values, err) in cases {
+            assert_eq!(set(&backend, values), Err(err.to_string()));
+        }
+        // the values set before the failure are restored
+        assert_eq!(backend.config.timeout(), None);
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("100"), Ok(100));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("1KB"), Ok(1024));
+        assert_eq!(parse_memory("2gb"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_memory("-1").is_err());
+        assert!(parse_memory("1tb").is_err());
+        assert!(parse_memory("").is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    check_aof, deserialize, dump_payload, parse_rdb, replay, serialize, write_value, AofError,
+    AofFile, AofFileType, Backend, BulkString, Manifest, RdbFile, RdbValue, RespArray, RespEncode,
+    RespFrame,
+};
+
+// the number of members written by a single SADD, as AOF_REWRITE_ITEMS_PER_CMD
+const AOF_ITEMS_PER_CMD: usize = 64;
+
+/// A persistence file given to the offline check tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Rdb(PathBuf),
+    // the files of the AOF, in loading order
+    Aof(Vec<PathBuf>),
+}
+
+impl Input {
+    /// An RDB file, a single AOF file, or a multi-part AOF given by its
+    /// directory or its manifest.
+    pub fn resolve(path: &Path) -> Result<Self, AofError> {
+        let manifest = if path.is_dir() {
+            let mut manifests = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "manifest"))
+                .collect::<Vec<_>>();
+            if manifests.len() != 1 {
+                return Err(AofError::InvalidManifest(format!(
+                    "expected a single manifest in {}",
+                    path.display()
+                )));
+            }
+            manifests.pop()
+        } else if path.extension().is_some_and(|ext| ext == "manifest") {
+            Some(path.to_path_buf())
+        } else {
+            None
+        };
+
+        if let Some(manifest) = manifest {
+            let dir = manifest.parent().unwrap_or(Path::new("."));
+            let manifest: Manifest = fs::read_to_string(&manifest)?.parse()?;
+            return Ok(Input::Aof(
+                manifest.files().map(|file| dir.join(&file.name)).collect(),
+            ));
+        }
+
+        let mut magic = Vec::new();
+        File::open(path)?.take(5).read_to_end(&mut magic)?;
+        if magic == b"REDIS" {
+            Ok(Input::Rdb(path.to_path_buf()))
+        } else {
+            Ok(Input::Aof(vec![path.to_path_buf()]))
+        }
+    }
+
+    pub fn format(&self) -> &'static str {
+        match self {
+            Input::Rdb(_) => "rdb",
+            Input::Aof(_) => "aof",
+        }
+    }
+}
+
+/// Reads the dataset of an RDB file, or of an AOF by replaying it. The
+/// incomplete tail of the last AOF file is skipped as the server does, and the
+/// keys of an AOF that already expired are not part of the dataset.
+pub fn read_dataset(input: &Input) -> Result<RdbFile, AofError> {
+    let files = match input {
+        Input::Rdb(path) => return Ok(parse_rdb(&fs::read(path)?)?),
+        Input::Aof(files) => files,
+    };
+
+    let backend = Backend::new();
+    for (i, path) in files.iter().enumerate() {
+        let data = fs::read(path)?;
+        if data.starts_with(b"REDIS") {
+            deserialize(&backend, &data)?;
+            continue;
+        }
+        let check = check_aof(&data);
+        if let Some(error) = check.error {
+            if !check.truncated || i + 1 < files.len() {
+                return Err(AofError::Invalid(format!("{}: {}", path.display(), error)));
+            }
+        }
+        replay(&backend, &data[..check.valid])
+            .map_err(|e| AofError::Invalid(format!("{}: {}", path.display(), e)))?;
+    }
+    Ok(parse_rdb(&serialize(&backend))?)
+}
+
+/// Rewrites a dataset as the commands of an AOF.
+pub fn write_aof(file: &RdbFile) -> Result<Vec<u8>, AofError> {
+    let mut buf = Vec::new();
+    let mut push = |args: Vec<BulkString>| {
+        let args = args.into_iter().map(|arg| Some(arg).into());
+        buf.extend(Some(RespArray::new(args.collect::<Vec<RespFrame>>())).encode());
+    };
+
+    for code in &file.functions {
+        push(vec!["function".into(), "load".into(), code.as_str().into()]);
+    }
+    for entry in &file.entries {
+        if entry.db != 0 {
+            return Err(AofError::Invalid(format!(
+                "the keys of db {} cannot be converted, only db 0 is supported",
+                entry.db
+            )));
+        }
+        let key = BulkString::new(entry.key.clone());
+        // there is no PEXPIREAT, a key with a TTL is restored with its expire time
+        if let Some(at) = entry.expire_at {
+            push(vec![
+                "restore".into(),
+                key,
+                at.to_string().into(),
+                BulkString::new(dump_payload(&entry.value)),
+                "absttl".into(),
+            ]);
+            continue;
+        }
+        match &entry.value {
+            RdbValue::Set(members) => {
+                for chunk in members.chunks(AOF_ITEMS_PER_CMD) {
+                    let mut args = vec!["sadd".into(), key.clone()];
+                    args.extend(chunk.iter().map(|member| BulkString::new(member.clone())));
+                    push(args);
+                }
+            }
+            RdbValue::Hash(fields) => {
+                for (field, value) in fields {
+                    push(vec![
+                        "hset".into(),
+                        key.clone(),
+                        BulkString::new(field.clone()),
+                        BulkString::new(value.clone()),
+                    ]);
+                }
+            }
+        }
+    }
+    Ok(buf)
+}
+
+/// Writes the commands as the base of a new multi-part AOF in `dir`.
+pub fn write_aof_dir(dir: &Path, filename: &str, data: &[u8]) -> Result<(), AofError> {
+    fs::create_dir_all(dir)?;
+    let base = AofFile {
+        name: format!("{}.1.base.aof", filename),
+        seq: 1,
+        kind: AofFileType::Base,
+    };
+    fs::write(dir.join(&base.name), data)?;
+    Manifest {
+        base: Some(base),
+        ..Default::default()
+    }
+    .persist(dir, filename)
+}
+
+/// The keys of a dataset with their type, size and TTL, as a JSON document.
+pub fn to_json(input: &Input, file: &RdbFile, now: u64) -> String {
+    let mut entries = file.entries.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| (a.db, &a.key).cmp(&(b.db, &b.key)));
+
+    let mut json = String::new();
+    let _ = write!(json, "{{\n  \"format\": \"{}\",\n", input.format());
+    if let Input::Rdb(_) = input {
+        let _ = write!(json, "  \"version\": {},\n  \"aux\": {{", file.version);
+        for (i, (key, value)) in file.aux.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            let _ = write!(json, "{}{}: {}", sep, json_string(key), json_string(value));
+        }
+        json.push_str("},\n");
+    }
+    let _ = writeln!(json, "  \"functions\": {},", file.functions.len());
+    json.push_str("  \"keys\": [");
+    for (i, entry) in entries.iter().enumerate() {
+        let mut value = Vec::new();
+        write_value(&mut value, &entry.value);
+        let (expire_at, ttl) = match entry.expire_at {
+            Some(at) => (at.to_string(), (at as i64 - now as i64).to_string()),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        let _ = write!(
+            json,
+            "{}\n    {{\"db\": {}, \"key\": {}, \"type\": \"{}\", \"size\": {}, \"bytes\": {}, \"ttl\": {}, \"expire_at\": {}}}",
+            if i == 0 { "" } else { "," },
+            entry.db,
+            json_string(&entry.key),
+            entry.value.type_name(),
+            entry.value.len(),
+            value.len(),
+            ttl,
+            expire_at
+        );
+    }
+    if !entries.is_empty() {
+        json.push_str("\n  ");
+    }
+    json.push_str("]\n}");
+    json
+}
+
+// keys and values are binary, the bytes that are not utf8 are replaced
+fn json_string(s: &[u8]) -> String {
+    let mut json = String::from("\"");
+    for c in String::from_utf8_lossy(s).chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+    use crate::{write_rdb, RdbEntry};
+
+    fn sample() -> RdbFile {
+        RdbFile {
+            version: 10,
+            aux: vec![(b"redis-ver".to_vec(), b"7.0.0".to_vec())],
+            functions: vec![],
+            entries: vec![
+                RdbEntry {
+                    db: 0,
+                    key: b"myhash".to_vec(),
+                    value: RdbValue::Hash(vec![(b"field".to_vec(), b"value".to_vec())]),
+                    expire_at: None,
+                },
+                RdbEntry {
+                    db: 0,
+                    key: b"myset".to_vec(),
+                    value: RdbValue::Set(vec![b"a".to_vec()]),
+                    expire_at: Some(u64::MAX / 2),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_convert_rdb_to_aof_and_back() -> Result<(), AofError> {
+        let rdb = temp_path("test-inspect-rdb");
+        fs::write(&rdb, write_rdb(&sample()))?;
+        let input = Input::resolve(&rdb)?;
+        assert_eq!(input, Input::Rdb(rdb.clone()));
+        let file = read_dataset(&input)?;
+        assert_eq!(file, sample());
+
+        let dir = temp_path("test-inspect-aof");
+        write_aof_dir(&dir, "appendonly.aof", &write_aof(&file)?)?;
+        let input = Input::resolve(&dir)?;
+        assert_eq!(
+            input,
+            Input::Aof(vec![dir.join("appendonly.aof.1.base.aof")])
+        );
+        let mut converted = read_dataset(&input)?;
+        converted.entries.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(converted.entries, sample().entries);
+
+        fs::remove_file(&rdb)?;
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_truncated_aof() -> Result<(), AofError> {
+        let path = temp_path("test-inspect-truncated.aof");
+        let mut data = write_aof(&sample())?;
+        data.extend_from_slice(b"*3\r\n$4\r\nsadd\r\n");
+        fs::write(&path, &data)?;
+        let input = Input::resolve(&path)?;
+        assert_eq!(read_dataset(&input)?.entries.len(), 2);
+
+        // a corrupted command is not skipped
+        let mut data = write_aof(&sample())?;
+        data.extend_from_slice(b":1\r\n");
+        fs::write(&path, &data)?;
+        assert!(read_dataset(&input).is_err());
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut file = sample();
+        file.entries[1].key = b"my\"set\n".to_vec();
+        file.entries[1].expire_at = Some(2500);
+        let json = to_json(&Input::Rdb(PathBuf::new()), &file, 1000);
+        assert_eq!(
+            json,
+            "{\n  \"format\": \"rdb\",\n  \"version\": 10,\n  \"aux\": {\"redis-ver\": \"7.0.0\"},\n  \"functions\": 0,\n  \"keys\": [\n    \
+             {\"db\": 0, \"key\": \"my\\\"set\\n\", \"type\": \"set\", \"size\": 1, \"bytes\": 4, \"ttl\": 1500, \"expire_at\": 2500},\n    \
+             {\"db\": 0, \"key\": \"myhash\", \"type\": \"hash\", \"size\": 1, \"bytes\": 14, \"ttl\": null, \"expire_at\": null}\n  ]\n}"
+        );
+    }
+}
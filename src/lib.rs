@@ -1,15 +1,45 @@
+mod aof;
 mod backend;
 mod cmd;
+mod config;
+mod inspect;
+mod memory;
+mod network;
+mod rdb;
 mod resp;
 mod respv2;
 mod respv3;
+mod respv4;
+mod scripting;
+mod session;
+#[cfg(test)]
+mod test_util;
+mod util;
 
+pub use aof::{
+    aof_cron, check_aof, load_aof, replay, rewrite, start_aof, stop_aof, AofCheck, AofError,
+    AofFile, AofFileType, AofState, AppendFsync, BatchGuard, Manifest,
+};
 pub use backend::*;
 pub use cmd::*;
+pub use config::{
+    config_get, config_set, load_config, rewrite_config, ConfigError, ConfigState, LogLevel,
+};
+pub use inspect::{read_dataset, to_json, write_aof, write_aof_dir, Input};
+pub use memory::{used_memory, CountingAllocator};
+pub use network::*;
 pub use resp::{
-    BulkString, RespArray, RespDecode, RespEncode, RespError, RespFrame, RespNull, SimpleError,
-    SimpleString,
+    BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespDecode, RespEncode, RespError,
+    RespFrame, RespLimits, RespNull, RespPush, RespSet, SimpleError, SimpleString, VerbatimString,
 };
 // pub use resp::*;
+pub use rdb::{
+    bgsave, deserialize, load_snapshot, parse_rdb, save, save_cron, save_on_shutdown, serialize,
+    write_rdb, write_value, RdbEntry, RdbError, RdbFile, RdbState, RdbValue, SaveParam,
+};
 pub use respv2::RespDecodeV2;
 pub use respv3::RespDecodeV3;
+pub use respv4::RespDecoderV4;
+pub use scripting::*;
+pub use session::Session;
+pub use util::{glob_match, now_ms};
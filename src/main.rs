@@ -1,34 +1,131 @@
-use std::net::SocketAddr;
-
 use anyhow::Result;
-use futures::SinkExt;
-use tokio_stream::StreamExt;
 
+use futures::future::try_join_all;
 use simple_redis::{
-    Backend, Command, CommandExecutor, RespDecodeV2, RespEncode, RespError, RespFrame,
+    aof_cron, load_aof, load_config, load_snapshot, save_cron, save_on_shutdown, start_aof,
+    stream_handler, Backend, CountingAllocator,
 };
-use tokio::net::{TcpListener, TcpStream};
-use tokio_util::codec::Framed;
-use tracing::{info, warn};
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+use tracing_subscriber::{filter::dynamic_filter_fn, prelude::*, EnvFilter};
+
+const USAGE: &str = "Usage: simple-redis [/path/to/redis.conf] [options]
+       simple-redis -v or --version
+       simple-redis -h or --help
+
+Examples:
+       simple-redis (run the server with the default config)
+       simple-redis /etc/redis/6379.conf
+       simple-redis --port 7777
+       simple-redis /etc/myredis.conf --loglevel verbose --save 60 1000";
 
-const ADDR: &str = "0.0.0.0:6379";
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Some("-v" | "--version") => {
+            println!("Simple-Redis server v={}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        _ => {}
+    }
 
     let backend = Backend::new();
+    init_tracing(&backend);
+    load_config(&backend, &args)?;
+    // the AOF has the most recent data, the snapshot is only loaded without it
+    if backend.aof().is_enabled() && load_aof(&backend)? {
+        info!("DB loaded from append only file");
+    } else if load_snapshot(&backend)? {
+        info!("DB loaded from disk");
+    }
+    if backend.aof().is_enabled() {
+        start_aof(&backend)?;
+    }
+    tokio::spawn(save_cron(backend.clone()));
+    tokio::spawn(aof_cron(backend.clone()));
+
+    let listeners = bind(&backend).await?;
+    let accept_loops = listeners
+        .into_iter()
+        .map(|listener| accept_loop(listener, backend.clone()));
+
+    tokio::select! {
+        ret = try_join_all(accept_loops) => {
+            ret?;
+        }
+        _ = shutdown_signal() => info!("Received shutdown signal, scheduling shutdown..."),
+    }
 
-    info!("Simple-Redis-Server is listening on {}", ADDR);
-    let listener = TcpListener::bind(ADDR).await?;
+    let ret = tokio::task::spawn_blocking(move || save_on_shutdown(&backend)).await?;
+    if let Err(e) = ret {
+        warn!("Error trying to save the DB, can't exit: {}", e);
+        return Err(e.into());
+    }
+    info!("Redis is now ready to exit, bye bye...");
+    Ok(())
+}
 
+// `RUST_LOG` takes precedence over `loglevel`
+fn init_tracing(backend: &Backend) {
+    match EnvFilter::try_from_default_env() {
+        Ok(filter) => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        Err(_) => {
+            let backend = backend.clone();
+            let filter = dynamic_filter_fn(move |meta, _| {
+                *meta.level() <= backend.config().loglevel().level_filter()
+            });
+            tracing_subscriber::registry()
+                .with(tracing_subscriber::fmt::layer().with_filter(filter))
+                .init();
+        }
+    }
+}
+
+// a listener for each address of `bind`, "*" is any IPv4 address and "::*"
+// any IPv6 address, an address starting with '-' is skipped if not available
+async fn bind(backend: &Backend) -> Result<Vec<TcpListener>> {
+    let port = backend.config().port();
+    let mut listeners = Vec::new();
+    for addr in backend.config().bind() {
+        let (addr, optional) = match addr.strip_prefix('-') {
+            Some(addr) => (addr.to_string(), true),
+            None => (addr, false),
+        };
+        let host = match addr.as_str() {
+            "*" => "0.0.0.0",
+            "::*" => "::",
+            addr => addr,
+        };
+        match TcpListener::bind((host, port)).await {
+            Ok(listener) => {
+                info!(
+                    "Simple-Redis-Server is listening on {}",
+                    listener.local_addr()?
+                );
+                listeners.push(listener);
+            }
+            Err(e) if optional => debug!("Skipping the address {}: {}", addr, e),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(listeners)
+}
+
+async fn accept_loop(listener: TcpListener, backend: Backend) -> Result<()> {
     loop {
         let (stream, raddr) = listener.accept().await?;
-        info!("Accepted connection from: {}", raddr);
+        debug!("Accepted connection from: {}", raddr);
         let backend = backend.clone();
         tokio::spawn(async move {
-            // println!(":?", backend);
-            match process_conn(stream, raddr, backend).await {
+            match stream_handler(stream, backend).await {
                 Ok(_) => {
                     info!("Connection from {} exited", raddr);
                 }
@@ -40,54 +137,24 @@ async fn main() -> Result<()> {
     }
 }
 
-async fn process_conn(stream: TcpStream, _: SocketAddr, backend: Backend) -> Result<()> {
-    let mut framed = Framed::new(stream, RespFrameCodec);
-    loop {
-        match framed.next().await {
-            Some(Ok(frame)) => {
-                info!("Received frame: {:?}", frame);
-                info!(
-                    "Received frame: {:?}",
-                    String::from_utf8(frame.clone().encode())
-                );
-
-                let cmd = Command::try_from(frame)?;
-                info!("Executing command: {:?}", cmd);
-
-                let frame = cmd.execute(&backend);
-                info!("Sending response: {:?}", frame);
-                framed.send(frame).await?;
-            }
-            Some(Err(e)) => return Err(e),
-            None => return Ok(()),
-        }
-    }
-}
-
-use tokio_util::codec::{Decoder, Encoder};
-
-#[derive(Debug)]
-pub struct RespFrameCodec;
-
-impl Encoder<RespFrame> for RespFrameCodec {
-    type Error = anyhow::Error;
-
-    fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encoded = item.encode();
-        dst.extend_from_slice(&encoded);
-        Ok(())
-    }
-}
-
-impl Decoder for RespFrameCodec {
-    type Item = RespFrame;
-    type Error = anyhow::Error;
-
-    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
-            Err(e) => Err(e.into()),
+// SIGINT, and SIGTERM on unix
+async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    {
+        let mut term =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(term) => term,
+                Err(_) => {
+                    let _ = ctrl_c.await;
+                    return;
+                }
+            };
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = term.recv() => {}
         }
     }
+    #[cfg(not(unix))]
+    let _ = ctrl_c.await;
 }
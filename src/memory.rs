@@ -0,0 +1,81 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// the bytes allocated and not freed yet, 0 until the allocator is installed
+static USED_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator counting the bytes in use, as zmalloc does for Redis.
+/// The server installs it as the `#[global_allocator]`, the library leaves the
+/// choice of the allocator to the programs embedding it.
+pub struct CountingAllocator;
+
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            USED_MEMORY.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            USED_MEMORY.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            // added first, the counter never goes below the bytes in use
+            USED_MEMORY.fetch_add(new_size, Ordering::Relaxed);
+            USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// The bytes allocated by the process, the `used_memory` of Redis, or its RSS
+/// when the `CountingAllocator` is not the global allocator.
+pub fn used_memory() -> usize {
+    match USED_MEMORY.load(Ordering::Relaxed) {
+        0 => rss_bytes() as usize,
+        used => used,
+    }
+}
+
+/// The resident set size of the process, from the VmRSS line of
+/// /proc/self/status, 0 where there is no procfs.
+pub fn rss_bytes() -> u64 {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+            let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+            Some(kb * 1024)
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_used_memory() {
+        let data = vec![1u8; 64 * 1024 * 1024];
+        assert!(used_memory() >= data.len());
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Result};
+use futures::SinkExt;
+use tokio::net::TcpStream;
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tracing::{debug, info};
+
+use crate::{Backend, RespDecoderV4, RespEncode, RespFrame, Session, SimpleError};
+
+#[derive(Debug)]
+pub struct RespFrameCodec {
+    // the client switched to RESP3 with HELLO 3, replies are sent as they are
+    resp3: bool,
+    // the frame being received, resumed on each read
+    decoder: RespDecoderV4,
+}
+
+impl Default for RespFrameCodec {
+    fn default() -> Self {
+        RespFrameCodec {
+            resp3: false,
+            decoder: RespDecoderV4::for_requests(),
+        }
+    }
+}
+
+impl RespFrameCodec {
+    pub fn set_protover(&mut self, protover: i64) {
+        self.resp3 = protover >= 3;
+    }
+}
+
+pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+    let mut session = Session::connect(backend.clone(), stream.peer_addr()?, stream.local_addr()?);
+    let client = session.client().clone();
+    let mut pushes = client
+        .take_pushes()
+        .ok_or_else(|| anyhow!("the pushes of the client are already taken"))?;
+    let mut framed = Framed::new(stream, RespFrameCodec::default());
+    loop {
+        let idle = async {
+            match backend.config().timeout() {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        let next = tokio::select! {
+            // a client killing itself gets its reply, then the connection is closed
+            biased;
+            _ = client.killed() => {
+                info!("Closing killed client");
+                return Ok(());
+            }
+            // invalidation messages and messages of the channels
+            Some(push) = pushes.recv() => {
+                debug!("Sending push: {:?}", push);
+                framed.send(push).await?;
+                continue;
+            }
+            next = framed.next() => next,
+            // as Redis, an idle client is closed without a reply
+            _ = idle => {
+                info!("Closing idle client");
+                return Ok(());
+            }
+        };
+        match next {
+            Some(Ok(frame)) => {
+                let buffer = framed.read_buffer();
+                client.set_query_buffer(buffer.len(), buffer.capacity() - buffer.len());
+                debug!("Received frame: {:?}", frame);
+                debug!(
+                    "Received frame: {:?}",
+                    String::from_utf8(frame.clone().encode())
+                );
+
+                // a rejected command is replied with its error, the connection is kept
+                let frame = session
+                    .process(frame)
+                    .unwrap_or_else(|e| SimpleError::from(e).into());
+                // the reply of HELLO is already sent with the new protocol
+                framed.codec_mut().set_protover(session.protover());
+                // the pushes raised by the command come ahead of its reply
+                while let Ok(push) = pushes.try_recv() {
+                    framed.feed(push).await?;
+                }
+                debug!("Sending response: {:?}", frame);
+                // the reply is pending in the output buffer until flushed
+                framed.feed(frame).await?;
+                client.set_output_buffer(framed.write_buffer().len());
+                framed.flush().await?;
+                client.set_output_buffer(framed.write_buffer().len());
+            }
+            Some(Err(e)) => {
+                // as Redis, the client is told why the connection is closed
+                let _ = framed
+                    .send(SimpleError::new(format!("ERR {}", e)).into())
+                    .await;
+                return Err(e);
+            }
+            None => return Ok(()),
+        }
+    }
+}
+
+impl Encoder<RespFrame> for RespFrameCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
+        let item = if self.resp3 { item } else { item.into_resp2() };
+        dst.reserve(item.size_hint());
+        item.encode_to(dst);
+        Ok(())
+    }
+}
+
+impl Decoder for RespFrameCodec {
+    type Item = RespFrame;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
+        Ok(self.decoder.decode(src)?)
+    }
+}
@@ -0,0 +1,297 @@
+mod snapshot;
+mod value;
+
+use bytes::{Buf, BufMut};
+use crc::{Crc, CRC_64_REDIS};
+use thiserror::Error;
+
+use crate::RespLimits;
+
+pub use snapshot::{
+    bgsave, deserialize, load_snapshot, parse_rdb, save, save_cron, save_on_shutdown, serialize,
+    write_rdb, RdbEntry, RdbFile, RdbState, SaveParam,
+};
+pub use value::{read_value, restore_value, value_of, write_value, RdbValue};
+
+// https://github.com/redis/redis/blob/7.0/src/rdb.h
+pub const RDB_VERSION: u16 = 10;
+// the format is read up to Redis 7.4, as long as the values have supported types
+pub const RDB_MAX_VERSION: u16 = 12;
+
+pub const RDB_OPCODE_SLOT_INFO: u8 = 244;
+pub const RDB_OPCODE_FUNCTION2: u8 = 245;
+pub const RDB_OPCODE_IDLE: u8 = 248;
+pub const RDB_OPCODE_FREQ: u8 = 249;
+pub const RDB_OPCODE_AUX: u8 = 250;
+pub const RDB_OPCODE_RESIZEDB: u8 = 251;
+pub const RDB_OPCODE_EXPIRETIME_MS: u8 = 252;
+pub const RDB_OPCODE_EXPIRETIME: u8 = 253;
+pub const RDB_OPCODE_SELECTDB: u8 = 254;
+pub const RDB_OPCODE_EOF: u8 = 255;
+
+const RDB_6BITLEN: u8 = 0;
+const RDB_14BITLEN: u8 = 1;
+const RDB_32BITLEN: u8 = 0x80;
+const RDB_64BITLEN: u8 = 0x81;
+const RDB_ENCVAL: u8 = 3;
+
+const RDB_ENC_INT8: u8 = 0;
+const RDB_ENC_INT16: u8 = 1;
+const RDB_ENC_INT32: u8 = 2;
+const RDB_ENC_LZF: u8 = 3;
+
+const CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_REDIS);
+
+#[derive(Error, Debug)]
+pub enum RdbError {
+    #[error("Unexpected end of RDB data")]
+    UnexpectedEof,
+    #[error("Invalid RDB data: {0}")]
+    Invalid(String),
+    #[error("payload version or checksum are wrong")]
+    BadFooter,
+    #[error("Wrong RDB checksum")]
+    BadChecksum,
+    #[error("Background save already in progress")]
+    InProgress,
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub fn crc64(data: &[u8]) -> u64 {
+    CRC64.checksum(data)
+}
+
+pub fn write_length(buf: &mut Vec<u8>, len: u64) {
+    if len < 1 << 6 {
+        buf.put_u8((RDB_6BITLEN << 6) | len as u8);
+    } else if len < 1 << 14 {
+        buf.put_u8((RDB_14BITLEN << 6) | (len >> 8) as u8);
+        buf.put_u8(len as u8);
+    } else if len <= u32::MAX as u64 {
+        buf.put_u8(RDB_32BITLEN);
+        buf.put_u32(len as u32);
+    } else {
+        buf.put_u8(RDB_64BITLEN);
+        buf.put_u64(len);
+    }
+}
+
+pub fn write_string(buf: &mut Vec<u8>, s: &[u8]) {
+    write_length(buf, s.len() as u64);
+    buf.extend_from_slice(s);
+}
+
+/// Appends the DUMP/RESTORE footer: the RDB version and a CRC64 of the whole payload.
+pub fn write_footer(buf: &mut Vec<u8>) {
+    buf.put_u16_le(RDB_VERSION);
+    let crc = crc64(buf);
+    buf.put_u64_le(crc);
+}
+
+/// Checks the footer written by `write_footer` and returns the payload without it.
+pub fn verify_footer(payload: &[u8]) -> Result<&[u8], RdbError> {
+    if payload.len() < 10 {
+        return Err(RdbError::BadFooter);
+    }
+    let (data, footer) = payload.split_at(payload.len() - 10);
+    let version = u16::from_le_bytes([footer[0], footer[1]]);
+    let crc = u64::from_le_bytes(footer[2..].try_into().expect("footer is 10 bytes"));
+    if version > RDB_MAX_VERSION || crc64(&payload[..payload.len() - 8]) != crc {
+        return Err(RdbError::BadFooter);
+    }
+    Ok(data)
+}
+
+pub fn read_u8(buf: &mut &[u8]) -> Result<u8, RdbError> {
+    if buf.is_empty() {
+        return Err(RdbError::UnexpectedEof);
+    }
+    Ok(buf.get_u8())
+}
+
+fn ensure(buf: &[u8], len: usize) -> Result<(), RdbError> {
+    if buf.len() < len {
+        return Err(RdbError::UnexpectedEof);
+    }
+    Ok(())
+}
+
+// returns the length, or the special encoding when the second value is true
+fn read_length_with_encoding(buf: &mut &[u8]) -> Result<(u64, bool), RdbError> {
+    let first = read_u8(buf)?;
+    match first >> 6 {
+        RDB_6BITLEN => Ok(((first & 0x3f) as u64, false)),
+        RDB_14BITLEN => Ok(((((first & 0x3f) as u64) << 8) | read_u8(buf)? as u64, false)),
+        RDB_ENCVAL => Ok(((first & 0x3f) as u64, true)),
+        _ => match first {
+            RDB_32BITLEN => {
+                ensure(buf, 4)?;
+                Ok((buf.get_u32() as u64, false))
+            }
+            RDB_64BITLEN => {
+                ensure(buf, 8)?;
+                Ok((buf.get_u64(), false))
+            }
+            _ => Err(RdbError::Invalid(format!(
+                "unknown length encoding {}",
+                first
+            ))),
+        },
+    }
+}
+
+pub fn read_length(buf: &mut &[u8]) -> Result<u64, RdbError> {
+    match read_length_with_encoding(buf)? {
+        (len, false) => Ok(len),
+        (_, true) => Err(RdbError::Invalid("unexpected encoded length".to_string())),
+    }
+}
+
+pub fn read_string(buf: &mut &[u8]) -> Result<Vec<u8>, RdbError> {
+    let (len, encoded) = read_length_with_encoding(buf)?;
+    if !encoded {
+        let len = len as usize;
+        ensure(buf, len)?;
+        let s = buf[..len].to_vec();
+        buf.advance(len);
+        return Ok(s);
+    }
+
+    if len as u8 == RDB_ENC_LZF {
+        let compressed_len = read_length(buf)? as usize;
+        let len = read_length(buf)? as usize;
+        ensure(buf, compressed_len)?;
+        let s = lzf_decompress(&buf[..compressed_len], len)?;
+        buf.advance(compressed_len);
+        return Ok(s);
+    }
+
+    let n = match len as u8 {
+        RDB_ENC_INT8 => read_u8(buf)? as i8 as i64,
+        RDB_ENC_INT16 => {
+            ensure(buf, 2)?;
+            buf.get_i16_le() as i64
+        }
+        RDB_ENC_INT32 => {
+            ensure(buf, 4)?;
+            buf.get_i32_le() as i64
+        }
+        enc => {
+            return Err(RdbError::Invalid(format!(
+                "unknown string encoding {}",
+                enc
+            )))
+        }
+    };
+    Ok(n.to_string().into_bytes())
+}
+
+// strings are compressed by Redis when rdbcompression is on
+fn lzf_decompress(input: &[u8], len: usize) -> Result<Vec<u8>, RdbError> {
+    let invalid = || RdbError::Invalid("invalid LZF compressed string".to_string());
+    if len > RespLimits::current().max_bulk_len {
+        return Err(RdbError::Invalid(format!(
+            "string length {} is above proto-max-bulk-len",
+            len
+        )));
+    }
+    // the declared length is not trusted until the data is decoded
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 1 << 5 {
+            // a run of ctrl + 1 literal bytes
+            let literal = input.get(i..i + ctrl + 1).ok_or_else(invalid)?;
+            if out.len() + literal.len() > len {
+                return Err(invalid());
+            }
+            out.extend_from_slice(literal);
+            i += ctrl + 1;
+        } else {
+            // a back reference of at least 3 bytes
+            let mut run = ctrl >> 5;
+            if run == 7 {
+                run += *input.get(i).ok_or_else(invalid)? as usize;
+                i += 1;
+            }
+            let offset = ((ctrl & 0x1f) << 8) + *input.get(i).ok_or_else(invalid)? as usize + 1;
+            i += 1;
+            let start = out.len().checked_sub(offset).ok_or_else(invalid)?;
+            if out.len() + run + 2 > len {
+                return Err(invalid());
+            }
+            // the reference may overlap the bytes it produces
+            for k in 0..run + 2 {
+                out.push(out[start + k]);
+            }
+        }
+    }
+    if out.len() != len {
+        return Err(invalid());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_roundtrip() -> Result<(), RdbError> {
+        for len in [
+            0,
+            63,
+            64,
+            16383,
+            16384,
+            u32::MAX as u64,
+            u32::MAX as u64 + 1,
+        ] {
+            let mut buf = Vec::new();
+            write_length(&mut buf, len);
+            let mut data = buf.as_slice();
+            assert_eq!(read_length(&mut data)?, len);
+            assert!(data.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_int_encoded_string() -> Result<(), RdbError> {
+        let mut data: &[u8] = &[0xc0, 0x7b, 0xc1, 0x39, 0x30, 0xc2, 0x87, 0xd6, 0x12, 0x00];
+        assert_eq!(read_string(&mut data)?, b"123");
+        assert_eq!(read_string(&mut data)?, b"12345");
+        assert_eq!(read_string(&mut data)?, b"1234567");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_lzf_string() -> Result<(), RdbError> {
+        // "a" followed by a back reference copying it 9 times
+        let mut data: &[u8] = &[0xc3, 5, 10, 0x00, b'a', 0xe0, 0x00, 0x00];
+        assert_eq!(read_string(&mut data)?, b"aaaaaaaaaa");
+        assert!(data.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_footer() -> Result<(), RdbError> {
+        let mut buf = Vec::new();
+        write_string(&mut buf, b"hello");
+        write_footer(&mut buf);
+        assert_eq!(verify_footer(&buf)?, b"\x05hello");
+
+        let last = buf.len() - 1;
+        buf[last] ^= 0xff;
+        assert!(matches!(verify_footer(&buf), Err(RdbError::BadFooter)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(crc64(b"123456789"), 0xe9c6d914c4b8d9ca);
+    }
+}
@@ -0,0 +1,660 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, PoisonError, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use bytes::BufMut;
+use tracing::{info, warn};
+
+use crate::{util::now_ms, Backend};
+
+use super::{
+    crc64, read_length, read_string, read_u8, read_value, restore_value, value_of, write_length,
+    write_string, write_value, RdbError, RdbValue, RDB_MAX_VERSION, RDB_OPCODE_AUX, RDB_OPCODE_EOF,
+    RDB_OPCODE_EXPIRETIME, RDB_OPCODE_EXPIRETIME_MS, RDB_OPCODE_FREQ, RDB_OPCODE_FUNCTION2,
+    RDB_OPCODE_IDLE, RDB_OPCODE_RESIZEDB, RDB_OPCODE_SELECTDB, RDB_OPCODE_SLOT_INFO, RDB_VERSION,
+};
+
+const DEFAULT_FILENAME: &str = "dump.rdb";
+// the version written when no function is saved, the latest one rdb-tools reads
+const RDB_VERSION_NO_FUNCTIONS: u16 = 9;
+// wait before retrying an automatic save that failed
+const BGSAVE_RETRY_DELAY: u64 = 5;
+const SAVE_CRON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `save <seconds> <changes>`: snapshot when at least `changes` writes happened
+/// in the last `seconds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveParam {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+/// Configuration and state of the RDB snapshots.
+#[derive(Debug)]
+pub struct RdbState {
+    path: RwLock<PathBuf>,
+    save_params: RwLock<Vec<SaveParam>>,
+    // unix time of the last successful save
+    last_save: AtomicU64,
+    last_bgsave_try: AtomicU64,
+    last_bgsave_ok: AtomicBool,
+    bgsave_in_progress: AtomicBool,
+    bgsave_scheduled: AtomicBool,
+    // every snapshot gets a generation, the file lock holds the generation on disk,
+    // so a slow BGSAVE never overwrites a more recent SAVE
+    generation: AtomicU64,
+    file_lock: Mutex<u64>,
+}
+
+impl Default for RdbState {
+    fn default() -> Self {
+        Self {
+            path: RwLock::new(PathBuf::from(DEFAULT_FILENAME)),
+            // the defaults of redis.conf
+            save_params: RwLock::new(vec![
+                SaveParam {
+                    seconds: 3600,
+                    changes: 1,
+                },
+                SaveParam {
+                    seconds: 300,
+                    changes: 100,
+                },
+                SaveParam {
+                    seconds: 60,
+                    changes: 10000,
+                },
+            ]),
+            last_save: AtomicU64::new(unix_time()),
+            last_bgsave_try: AtomicU64::new(0),
+            last_bgsave_ok: AtomicBool::new(true),
+            bgsave_in_progress: AtomicBool::new(false),
+            bgsave_scheduled: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+            file_lock: Mutex::new(0),
+        }
+    }
+}
+
+impl RdbState {
+    pub fn path(&self) -> PathBuf {
+        self.path
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_path(&self, path: impl Into<PathBuf>) {
+        *self.path.write().unwrap_or_else(PoisonError::into_inner) = path.into();
+    }
+
+    pub fn save_params(&self) -> Vec<SaveParam> {
+        self.save_params
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_save_params(&self, params: Vec<SaveParam>) {
+        *self
+            .save_params
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = params;
+    }
+
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn last_bgsave_ok(&self) -> bool {
+        self.last_bgsave_ok.load(Ordering::Relaxed)
+    }
+
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::Acquire)
+    }
+
+    /// Asks the save cron to start a BGSAVE once the running one completes.
+    pub fn schedule_bgsave(&self) {
+        self.bgsave_scheduled.store(true, Ordering::Relaxed);
+    }
+
+    fn should_save(&self, dirty: u64) -> bool {
+        if self.bgsave_in_progress() {
+            return false;
+        }
+        if self.bgsave_scheduled.load(Ordering::Relaxed) {
+            return true;
+        }
+        let now = unix_time();
+        let can_retry = self.last_bgsave_ok.load(Ordering::Relaxed)
+            || now.saturating_sub(self.last_bgsave_try.load(Ordering::Relaxed))
+                > BGSAVE_RETRY_DELAY;
+        can_retry
+            && self.save_params().iter().any(|param| {
+                dirty >= param.changes && now.saturating_sub(self.last_save()) >= param.seconds
+            })
+    }
+
+    // writes the snapshot unless a more recent one is already on disk,
+    // returns whether the file was written
+    fn write(&self, path: &Path, data: &[u8], generation: u64) -> io::Result<bool> {
+        let mut written = self
+            .file_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *written > generation {
+            return Ok(false);
+        }
+        let tmp = path.with_file_name(format!("temp-{}.rdb", std::process::id()));
+        let mut file = File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        *written = generation;
+        Ok(true)
+    }
+}
+
+/// Saves the dataset to disk in the foreground (SAVE). The caller must hold
+/// the exclusive lock of the backend.
+pub fn save(backend: &Backend) -> Result<(), RdbError> {
+    if backend.rdb.bgsave_in_progress() {
+        return Err(RdbError::InProgress);
+    }
+    save_now(backend)
+}
+
+/// Takes a snapshot of the dataset and writes it from a background thread (BGSAVE).
+/// The caller must hold the exclusive lock of the backend.
+pub fn bgsave(backend: &Backend) -> Result<(), RdbError> {
+    let rdb = &backend.rdb;
+    if rdb.bgsave_in_progress.swap(true, Ordering::AcqRel) {
+        return Err(RdbError::InProgress);
+    }
+    rdb.bgsave_scheduled.store(false, Ordering::Relaxed);
+    rdb.last_bgsave_try.store(unix_time(), Ordering::Relaxed);
+
+    // there is no fork, the snapshot is serialized while the dataset is locked
+    // and only the file is written in the background
+    let data = serialize(backend);
+    let dirty = backend.dirty();
+    let generation = rdb.generation.fetch_add(1, Ordering::Relaxed) + 1;
+    let path = rdb.path();
+
+    let backend = backend.clone();
+    thread::spawn(move || {
+        let rdb = &backend.rdb;
+        match rdb.write(&path, &data, generation) {
+            Ok(written) => {
+                if written {
+                    backend.clear_dirty(dirty);
+                    rdb.last_save.store(unix_time(), Ordering::Relaxed);
+                }
+                rdb.last_bgsave_ok.store(true, Ordering::Relaxed);
+                info!("Background saving terminated with success");
+            }
+            Err(e) => {
+                rdb.last_bgsave_ok.store(false, Ordering::Relaxed);
+                warn!("Background saving error: {}", e);
+            }
+        }
+        rdb.bgsave_in_progress.store(false, Ordering::Release);
+    });
+    Ok(())
+}
+
+fn save_now(backend: &Backend) -> Result<(), RdbError> {
+    let rdb = &backend.rdb;
+    let data = serialize(backend);
+    let dirty = backend.dirty();
+    let generation = rdb.generation.fetch_add(1, Ordering::Relaxed) + 1;
+    rdb.write(&rdb.path(), &data, generation)?;
+    backend.clear_dirty(dirty);
+    rdb.last_save.store(unix_time(), Ordering::Relaxed);
+    info!("DB saved on disk");
+    Ok(())
+}
+
+/// Loads the snapshot at startup, returns false if there is no file.
+pub fn load_snapshot(backend: &Backend) -> Result<bool, RdbError> {
+    let data = match fs::read(backend.rdb.path()) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    deserialize(backend, &data)?;
+    Ok(true)
+}
+
+/// Triggers a BGSAVE when one of the `save <seconds> <changes>` points is reached.
+pub async fn save_cron(backend: Backend) {
+    let mut interval = tokio::time::interval(SAVE_CRON_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !backend.rdb.should_save(backend.dirty()) {
+            continue;
+        }
+
+        let backend = backend.clone();
+        let ret = tokio::task::spawn_blocking(move || match backend.lock_exclusive() {
+            Some(_guard) => bgsave(&backend),
+            // a script is running past its time limit, retry on the next tick
+            None => Ok(()),
+        })
+        .await;
+        match ret {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Automatic background save failed: {}", e),
+            Err(e) => warn!("Automatic background save panicked: {}", e),
+        }
+    }
+}
+
+/// Saves the dataset before the server exits, when save points are configured.
+pub fn save_on_shutdown(backend: &Backend) -> Result<(), RdbError> {
+    if backend.rdb.save_params().is_empty() {
+        return Ok(());
+    }
+    info!("Saving the final RDB snapshot before exiting");
+    match backend.lock_exclusive() {
+        Some(_guard) => save_now(backend),
+        None => Err(RdbError::Io(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "a script is still running",
+        ))),
+    }
+}
+
+/// The content of an RDB file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RdbFile {
+    pub version: u16,
+    pub aux: Vec<(Vec<u8>, Vec<u8>)>,
+    // code of the function libraries
+    pub functions: Vec<String>,
+    pub entries: Vec<RdbEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RdbEntry {
+    pub db: u64,
+    pub key: Vec<u8>,
+    pub value: RdbValue,
+    // unix time in milliseconds
+    pub expire_at: Option<u64>,
+}
+
+/// Serializes the dataset in the RDB file format.
+pub fn serialize(backend: &Backend) -> Vec<u8> {
+    let now = now_ms();
+    let functions = backend
+        .functions
+        .list()
+        .into_iter()
+        .map(|library| library.code)
+        .collect::<Vec<_>>();
+
+    let mut entries = Vec::with_capacity(backend.hset.len() + backend.hmap.len());
+    let keys = backend.hset.iter().map(|entry| entry.key().clone());
+    let keys = keys.chain(backend.hmap.iter().map(|entry| entry.key().clone()));
+    for key in keys.collect::<Vec<_>>() {
+        let expire_at = backend.expire_at(&key);
+        if expire_at.is_some_and(|at| at <= now) {
+            continue;
+        }
+        if let Some(value) = value_of(backend, &key) {
+            entries.push(RdbEntry {
+                db: 0,
+                key: key.into_bytes(),
+                value,
+                expire_at,
+            });
+        }
+    }
+
+    let version = if functions.is_empty() {
+        RDB_VERSION_NO_FUNCTIONS
+    } else {
+        RDB_VERSION
+    };
+    write_rdb(&RdbFile {
+        version,
+        aux: vec![
+            (b"redis-ver".to_vec(), b"7.0.0".to_vec()),
+            (b"redis-bits".to_vec(), b"64".to_vec()),
+            (b"ctime".to_vec(), (now / 1000).to_string().into_bytes()),
+        ],
+        functions,
+        entries,
+    })
+}
+
+/// Writes an RDB file, with its checksum.
+pub fn write_rdb(file: &RdbFile) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(format!("REDIS{:04}", file.version).as_bytes());
+    for (key, value) in &file.aux {
+        buf.put_u8(RDB_OPCODE_AUX);
+        write_string(&mut buf, key);
+        write_string(&mut buf, value);
+    }
+    for code in &file.functions {
+        buf.put_u8(RDB_OPCODE_FUNCTION2);
+        write_string(&mut buf, code.as_bytes());
+    }
+
+    let mut db = None;
+    for (i, entry) in file.entries.iter().enumerate() {
+        if db != Some(entry.db) {
+            db = Some(entry.db);
+            let entries = file.entries[i..].iter().take_while(|e| e.db == entry.db);
+            let (size, expires) = entries.fold((0, 0), |(size, expires), e| {
+                (size + 1, expires + e.expire_at.is_some() as u64)
+            });
+            buf.put_u8(RDB_OPCODE_SELECTDB);
+            write_length(&mut buf, entry.db);
+            buf.put_u8(RDB_OPCODE_RESIZEDB);
+            write_length(&mut buf, size);
+            write_length(&mut buf, expires);
+        }
+        if let Some(expire_at) = entry.expire_at {
+            buf.put_u8(RDB_OPCODE_EXPIRETIME_MS);
+            buf.put_u64_le(expire_at);
+        }
+        let mut value = Vec::new();
+        write_value(&mut value, &entry.value);
+        // the type comes before the key
+        buf.put_u8(value[0]);
+        write_string(&mut buf, &entry.key);
+        buf.extend_from_slice(&value[1..]);
+    }
+
+    buf.put_u8(RDB_OPCODE_EOF);
+    let crc = crc64(&buf);
+    buf.put_u64_le(crc);
+    buf
+}
+
+/// Parses an RDB file and checks its checksum.
+pub fn parse_rdb(data: &[u8]) -> Result<RdbFile, RdbError> {
+    let version = data
+        .get(..9)
+        .and_then(|header| header.strip_prefix(b"REDIS"))
+        .and_then(|version| std::str::from_utf8(version).ok())
+        .and_then(|version| version.parse::<u16>().ok())
+        .ok_or_else(|| RdbError::Invalid("wrong signature trying to load DB".to_string()))?;
+    if version == 0 || version > RDB_MAX_VERSION {
+        return Err(RdbError::Invalid(format!(
+            "can't handle RDB format version {}",
+            version
+        )));
+    }
+
+    let mut file = RdbFile {
+        version,
+        ..Default::default()
+    };
+    let mut buf = &data[9..];
+    let mut db = 0;
+    let mut expire_at = None;
+    loop {
+        match read_u8(&mut buf)? {
+            RDB_OPCODE_EOF => break,
+            RDB_OPCODE_AUX => {
+                let key = read_string(&mut buf)?;
+                file.aux.push((key, read_string(&mut buf)?));
+            }
+            RDB_OPCODE_SELECTDB => db = read_length(&mut buf)?,
+            RDB_OPCODE_RESIZEDB => {
+                read_length(&mut buf)?;
+                read_length(&mut buf)?;
+            }
+            RDB_OPCODE_SLOT_INFO => {
+                for _ in 0..3 {
+                    read_length(&mut buf)?;
+                }
+            }
+            RDB_OPCODE_EXPIRETIME_MS => {
+                let time = buf.get(..8).ok_or(RdbError::UnexpectedEof)?;
+                expire_at = Some(u64::from_le_bytes(
+                    time.try_into().expect("slice is 8 bytes"),
+                ));
+                buf = &buf[8..];
+            }
+            RDB_OPCODE_EXPIRETIME => {
+                let time = buf.get(..4).ok_or(RdbError::UnexpectedEof)?;
+                let secs = u32::from_le_bytes(time.try_into().expect("slice is 4 bytes"));
+                expire_at = Some(secs as u64 * 1000);
+                buf = &buf[4..];
+            }
+            // the LRU and LFU information of the next key, without eviction it is not used
+            RDB_OPCODE_IDLE => {
+                read_length(&mut buf)?;
+            }
+            RDB_OPCODE_FREQ => {
+                read_u8(&mut buf)?;
+            }
+            RDB_OPCODE_FUNCTION2 => {
+                let code = String::from_utf8(read_string(&mut buf)?).map_err(|_| {
+                    RdbError::Invalid("function code is not valid utf8".to_string())
+                })?;
+                file.functions.push(code);
+            }
+            value_type => {
+                let key = read_string(&mut buf)?;
+                let value = read_value(value_type, &mut buf)?;
+                file.entries.push(RdbEntry {
+                    db,
+                    key,
+                    value,
+                    expire_at: expire_at.take(),
+                });
+            }
+        }
+    }
+
+    // the checksum is absent before version 5, and zero when disabled
+    if version >= 5 {
+        let crc = buf
+            .get(..8)
+            .map(|crc| u64::from_le_bytes(crc.try_into().expect("slice is 8 bytes")))
+            .ok_or(RdbError::UnexpectedEof)?;
+        if crc != 0 && crc != crc64(&data[..data.len() - buf.len()]) {
+            return Err(RdbError::BadChecksum);
+        }
+    }
+    Ok(file)
+}
+
+/// Loads an RDB file into the backend, the keys that already expired are skipped.
+pub fn deserialize(backend: &Backend, data: &[u8]) -> Result<(), RdbError> {
+    let file = parse_rdb(data)?;
+    for code in file.functions {
+        backend
+            .functions
+            .load(&backend.scripts, &code, false)
+            .map_err(RdbError::Invalid)?;
+    }
+
+    let now = now_ms();
+    for entry in file.entries {
+        if entry.expire_at.is_some_and(|at| at <= now) {
+            continue;
+        }
+        let key = String::from_utf8(entry.key)
+            .map_err(|_| RdbError::Invalid("key is not valid utf8".to_string()))?;
+        if let Some(at) = entry.expire_at {
+            backend.set_expire_at(key.clone(), at);
+        }
+        restore_value(backend, key, entry.value)?;
+    }
+    Ok(())
+}
+
+fn unix_time() -> u64 {
+    now_ms() / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::{DashMap, DashSet};
+
+    use crate::RespFrame;
+
+    use super::*;
+    use crate::test_util::temp_path;
+
+    const LIBRARY: &str = "#!lua name=mylib\nredis.register_function('f', function() return 1 end)";
+
+    fn backend_with_data() -> Backend {
+        let backend = Backend::new();
+        let set = DashSet::new();
+        set.insert(RespFrame::from(b"a"));
+        set.insert(RespFrame::from(b"b"));
+        backend.hset.insert("myset".to_string(), set);
+        let hash = DashMap::new();
+        hash.insert("field".to_string(), RespFrame::from(b"value"));
+        backend.hmap.insert("myhash".to_string(), hash);
+        backend
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() -> Result<(), RdbError> {
+        let backend = backend_with_data();
+        let data = serialize(&backend);
+        assert_eq!(&data[..9], b"REDIS0009");
+
+        let loaded = Backend::new();
+        deserialize(&loaded, &data)?;
+        assert_eq!(loaded.hset.get("myset").map(|s| s.len()), Some(2));
+        assert!(loaded
+            .hset
+            .get("myset")
+            .is_some_and(|s| s.contains(&RespFrame::from(b"a"))));
+        assert_eq!(
+            loaded
+                .hmap
+                .get("myhash")
+                .and_then(|h| h.get("field").map(|v| v.clone())),
+            Some(RespFrame::from(b"value"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_expires() -> Result<(), RdbError> {
+        let backend = backend_with_data();
+        let at = now_ms() + 60_000;
+        backend.set_expire_at("myset", at);
+        backend.set_expire_at("myhash", 1);
+        let file = parse_rdb(&serialize(&backend))?;
+        // the expired key is not saved
+        assert_eq!(file.entries.len(), 1);
+        assert_eq!(file.entries[0].key, b"myset");
+        assert_eq!(file.entries[0].expire_at, Some(at));
+        assert_eq!(parse_rdb(&write_rdb(&file))?, file);
+
+        let loaded = Backend::new();
+        deserialize(&loaded, &write_rdb(&file))?;
+        assert_eq!(loaded.expire_at("myset"), Some(at));
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_functions() -> Result<(), RdbError> {
+        let backend = Backend::new();
+        backend
+            .functions
+            .load(&backend.scripts, LIBRARY, false)
+            .map_err(RdbError::Invalid)?;
+        let data = serialize(&backend);
+        assert_eq!(&data[..9], b"REDIS0010");
+
+        let loaded = Backend::new();
+        deserialize(&loaded, &data)?;
+        assert_eq!(loaded.functions.list(), backend.functions.list());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        let backend = Backend::new();
+        let mut data = serialize(&backend_with_data());
+        assert!(matches!(
+            deserialize(&backend, &data[..data.len() - 4]),
+            Err(RdbError::UnexpectedEof)
+        ));
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        assert!(matches!(
+            deserialize(&backend, &data),
+            Err(RdbError::BadChecksum)
+        ));
+        assert!(matches!(
+            deserialize(&backend, b"REDIS0099\xff"),
+            Err(RdbError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<(), RdbError> {
+        let backend = backend_with_data();
+        let path = temp_path("test-save");
+        backend.rdb.set_path(&path);
+        backend.signal_modified_key("myset");
+        assert_eq!(backend.dirty(), 1);
+
+        save(&backend)?;
+        assert_eq!(backend.dirty(), 0);
+
+        let loaded = Backend::new();
+        loaded.rdb.set_path(&path);
+        assert!(load_snapshot(&loaded)?);
+        assert_eq!(loaded.hset.len(), 1);
+        assert_eq!(loaded.hmap.len(), 1);
+
+        fs::remove_file(&path)?;
+        assert!(!load_snapshot(&loaded)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bgsave() -> Result<(), RdbError> {
+        let backend = backend_with_data();
+        let path = temp_path("test-bgsave");
+        backend.rdb.set_path(&path);
+
+        bgsave(&backend)?;
+        while backend.rdb.bgsave_in_progress() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(path.exists());
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_save() {
+        let rdb = RdbState::default();
+        rdb.set_save_params(vec![SaveParam {
+            seconds: 0,
+            changes: 2,
+        }]);
+        assert!(!rdb.should_save(1));
+        assert!(rdb.should_save(2));
+
+        rdb.set_save_params(vec![]);
+        assert!(!rdb.should_save(100));
+        rdb.schedule_bgsave();
+        assert!(rdb.should_save(0));
+    }
+}
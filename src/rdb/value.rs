@@ -0,0 +1,414 @@
+use bytes::{Buf, BufMut};
+use dashmap::{DashMap, DashSet};
+
+use crate::{Backend, BulkString, RespEncode, RespFrame};
+
+use super::{read_length, read_string, write_length, write_string, RdbError};
+
+pub const RDB_TYPE_STRING: u8 = 0;
+pub const RDB_TYPE_SET: u8 = 2;
+pub const RDB_TYPE_HASH: u8 = 4;
+pub const RDB_TYPE_SET_INTSET: u8 = 11;
+pub const RDB_TYPE_HASH_ZIPLIST: u8 = 13;
+pub const RDB_TYPE_HASH_LISTPACK: u8 = 16;
+pub const RDB_TYPE_SET_LISTPACK: u8 = 20;
+
+const LP_EOF: u8 = 0xff;
+const ZIPLIST_END: u8 = 0xff;
+const ZIPLIST_BIG_PREVLEN: u8 = 0xfe;
+
+pub type HashFields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// A value in the RDB format, holding the types the store supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RdbValue {
+    Set(Vec<Vec<u8>>),
+    Hash(HashFields),
+}
+
+impl RdbValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RdbValue::Set(_) => "set",
+            RdbValue::Hash(_) => "hash",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            RdbValue::Set(members) => members.len(),
+            RdbValue::Hash(fields) => fields.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The value of a key in the store.
+pub fn value_of(backend: &Backend, key: &str) -> Option<RdbValue> {
+    if let Some(set) = backend.hset.get(key) {
+        return Some(RdbValue::Set(
+            set.iter().map(|member| frame_to_bytes(&member)).collect(),
+        ));
+    }
+    backend.hmap.get(key).map(|hash| {
+        RdbValue::Hash(
+            hash.iter()
+                .map(|field| {
+                    (
+                        field.key().as_bytes().to_vec(),
+                        frame_to_bytes(field.value()),
+                    )
+                })
+                .collect(),
+        )
+    })
+}
+
+/// Stores a value read from an RDB file or a RESTORE payload.
+pub fn restore_value(backend: &Backend, key: String, value: RdbValue) -> Result<(), RdbError> {
+    match value {
+        RdbValue::Set(members) => {
+            let set = DashSet::new();
+            for member in members {
+                set.insert(Some(BulkString::new(member)).into());
+            }
+            backend.hmap.remove(&key);
+            backend.hset.insert(key, set);
+        }
+        RdbValue::Hash(fields) => {
+            let hash = DashMap::new();
+            for (field, value) in fields {
+                let field = String::from_utf8(field)
+                    .map_err(|_| RdbError::Invalid("hash field is not valid utf8".to_string()))?;
+                hash.insert(field, Some(BulkString::new(value)).into());
+            }
+            backend.hset.remove(&key);
+            backend.hmap.insert(key, hash);
+        }
+    }
+    Ok(())
+}
+
+// values are stored as frames, only their content is saved
+fn frame_to_bytes(frame: &RespFrame) -> Vec<u8> {
+    match frame {
+        RespFrame::BulkString(Some(s)) => s.to_vec(),
+        RespFrame::SimpleString(s) => s.as_bytes().to_vec(),
+        RespFrame::Integer(n) => n.to_string().into_bytes(),
+        frame => frame.clone().encode(),
+    }
+}
+
+/// Writes the type and the value, always with the plain encodings.
+pub fn write_value(buf: &mut Vec<u8>, value: &RdbValue) {
+    match value {
+        RdbValue::Set(members) => {
+            buf.put_u8(RDB_TYPE_SET);
+            write_length(buf, members.len() as u64);
+            for member in members {
+                write_string(buf, member);
+            }
+        }
+        RdbValue::Hash(fields) => {
+            buf.put_u8(RDB_TYPE_HASH);
+            write_length(buf, fields.len() as u64);
+            for (field, value) in fields {
+                write_string(buf, field);
+                write_string(buf, value);
+            }
+        }
+    }
+}
+
+/// Reads a value of the given type, including the compact encodings Redis uses
+/// for small sets and hashes.
+pub fn read_value(value_type: u8, buf: &mut &[u8]) -> Result<RdbValue, RdbError> {
+    match value_type {
+        RDB_TYPE_SET => {
+            let len = read_length(buf)?;
+            let mut members = Vec::new();
+            for _ in 0..len {
+                members.push(read_string(buf)?);
+            }
+            Ok(RdbValue::Set(members))
+        }
+        RDB_TYPE_HASH => {
+            let len = read_length(buf)?;
+            let mut fields = Vec::new();
+            for _ in 0..len {
+                fields.push((read_string(buf)?, read_string(buf)?));
+            }
+            Ok(RdbValue::Hash(fields))
+        }
+        RDB_TYPE_SET_INTSET => Ok(RdbValue::Set(read_intset(&read_string(buf)?)?)),
+        RDB_TYPE_SET_LISTPACK => Ok(RdbValue::Set(read_listpack(&read_string(buf)?)?)),
+        RDB_TYPE_HASH_LISTPACK => Ok(RdbValue::Hash(pairs(read_listpack(&read_string(buf)?)?)?)),
+        RDB_TYPE_HASH_ZIPLIST => Ok(RdbValue::Hash(pairs(read_ziplist(&read_string(buf)?)?)?)),
+        RDB_TYPE_STRING => Err(RdbError::Invalid(
+            "string values are not supported".to_string(),
+        )),
+        _ => Err(RdbError::Invalid(format!(
+            "unsupported object type {}",
+            value_type
+        ))),
+    }
+}
+
+fn pairs(entries: Vec<Vec<u8>>) -> Result<HashFields, RdbError> {
+    if !entries.len().is_multiple_of(2) {
+        return Err(RdbError::Invalid("odd number of hash entries".to_string()));
+    }
+    let mut entries = entries.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(field), Some(value)) = (entries.next(), entries.next()) {
+        pairs.push((field, value));
+    }
+    Ok(pairs)
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], RdbError> {
+    if buf.len() < len {
+        return Err(RdbError::UnexpectedEof);
+    }
+    let (data, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(data)
+}
+
+// https://github.com/redis/redis/blob/7.0/src/intset.h
+fn read_intset(mut blob: &[u8]) -> Result<Vec<Vec<u8>>, RdbError> {
+    let header = take(&mut blob, 8)?;
+    let encoding = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if !matches!(encoding, 2 | 4 | 8) {
+        return Err(RdbError::Invalid(format!(
+            "unknown intset encoding {}",
+            encoding
+        )));
+    }
+
+    if len > blob.len() / encoding {
+        return Err(RdbError::UnexpectedEof);
+    }
+
+    let mut members = Vec::new();
+    for _ in 0..len {
+        let mut int = take(&mut blob, encoding)?;
+        let n = match encoding {
+            2 => int.get_i16_le() as i64,
+            4 => int.get_i32_le() as i64,
+            _ => int.get_i64_le(),
+        };
+        members.push(n.to_string().into_bytes());
+    }
+    Ok(members)
+}
+
+// https://github.com/antirez/listpack/blob/master/listpack.md
+fn read_listpack(mut blob: &[u8]) -> Result<Vec<Vec<u8>>, RdbError> {
+    // total bytes and number of elements
+    take(&mut blob, 6)?;
+
+    let mut entries = Vec::new();
+    loop {
+        let first = *blob.first().ok_or(RdbError::UnexpectedEof)?;
+        if first == LP_EOF {
+            return Ok(entries);
+        }
+        let start = blob.len();
+        let entry = match first {
+            // 7 bit unsigned int
+            0x00..=0x7f => {
+                take(&mut blob, 1)?;
+                (first as i64).to_string().into_bytes()
+            }
+            // 6 bit string length
+            0x80..=0xbf => {
+                take(&mut blob, 1)?;
+                take(&mut blob, (first & 0x3f) as usize)?.to_vec()
+            }
+            // 13 bit signed int
+            0xc0..=0xdf => {
+                let b = take(&mut blob, 2)?;
+                let n = (((b[0] & 0x1f) as i64) << 8) | b[1] as i64;
+                let n = if n >= 1 << 12 { n - (1 << 13) } else { n };
+                n.to_string().into_bytes()
+            }
+            // 12 bit string length
+            0xe0..=0xef => {
+                let b = take(&mut blob, 2)?;
+                let len = (((b[0] & 0x0f) as usize) << 8) | b[1] as usize;
+                take(&mut blob, len)?.to_vec()
+            }
+            // 32 bit string length
+            0xf0 => {
+                let mut b = take(&mut blob, 5)?;
+                b.advance(1);
+                let len = b.get_u32_le() as usize;
+                take(&mut blob, len)?.to_vec()
+            }
+            0xf1..=0xf4 => {
+                let size = match first {
+                    0xf1 => 2,
+                    0xf2 => 3,
+                    0xf3 => 4,
+                    _ => 8,
+                };
+                let b = &take(&mut blob, 1 + size)?[1..];
+                let mut n = 0i64;
+                for (i, byte) in b.iter().enumerate() {
+                    n |= (*byte as i64) << (8 * i);
+                }
+                // sign extend the 24 bit integers
+                let shift = 64 - 8 * size;
+                ((n << shift) >> shift).to_string().into_bytes()
+            }
+            _ => {
+                return Err(RdbError::Invalid(format!(
+                    "unknown listpack encoding {}",
+                    first
+                )))
+            }
+        };
+        // the backlen of the entry, which is only needed to iterate backwards
+        let len = start - blob.len();
+        take(&mut blob, backlen_size(len))?;
+        entries.push(entry);
+    }
+}
+
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16383 => 2,
+        16384..=2097151 => 3,
+        2097152..=268435455 => 4,
+        _ => 5,
+    }
+}
+
+// https://github.com/redis/redis/blob/6.2/src/ziplist.c, used by RDB files before 7.0
+fn read_ziplist(mut blob: &[u8]) -> Result<Vec<Vec<u8>>, RdbError> {
+    // total bytes, tail offset and number of entries
+    take(&mut blob, 10)?;
+
+    let mut entries = Vec::new();
+    loop {
+        let first = *blob.first().ok_or(RdbError::UnexpectedEof)?;
+        if first == ZIPLIST_END {
+            return Ok(entries);
+        }
+        // the length of the previous entry
+        take(&mut blob, if first == ZIPLIST_BIG_PREVLEN { 5 } else { 1 })?;
+
+        let encoding = take(&mut blob, 1)?[0];
+        let entry = match encoding >> 6 {
+            0 => take(&mut blob, (encoding & 0x3f) as usize)?.to_vec(),
+            1 => {
+                let len = (((encoding & 0x3f) as usize) << 8) | take(&mut blob, 1)?[0] as usize;
+                take(&mut blob, len)?.to_vec()
+            }
+            2 => {
+                let len = take(&mut blob, 4)?.get_u32() as usize;
+                take(&mut blob, len)?.to_vec()
+            }
+            _ => {
+                let n = match encoding {
+                    0xc0 => take(&mut blob, 2)?.get_i16_le() as i64,
+                    0xd0 => take(&mut blob, 4)?.get_i32_le() as i64,
+                    0xe0 => take(&mut blob, 8)?.get_i64_le(),
+                    0xf0 => {
+                        let b = take(&mut blob, 3)?;
+                        (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as i64
+                    }
+                    0xfe => take(&mut blob, 1)?[0] as i8 as i64,
+                    // 4 bit immediate integer between 0 and 12
+                    0xf1..=0xfd => (encoding & 0x0f) as i64 - 1,
+                    _ => {
+                        return Err(RdbError::Invalid(format!(
+                            "unknown ziplist encoding {}",
+                            encoding
+                        )))
+                    }
+                };
+                n.to_string().into_bytes()
+            }
+        };
+        entries.push(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_roundtrip() -> Result<(), RdbError> {
+        let values = [
+            RdbValue::Set(vec![b"a".to_vec(), b"b".to_vec()]),
+            RdbValue::Hash(vec![(b"field".to_vec(), b"value".to_vec())]),
+        ];
+        for value in values {
+            let mut buf = Vec::new();
+            write_value(&mut buf, &value);
+            let mut data = &buf[1..];
+            assert_eq!(read_value(buf[0], &mut data)?, value);
+            assert!(data.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_intset() -> Result<(), RdbError> {
+        // SADD s 1 2 -3 on Redis 7.0
+        let blob = [2, 0, 0, 0, 3, 0, 0, 0, 0xfd, 0xff, 1, 0, 2, 0];
+        let mut buf = vec![blob.len() as u8];
+        buf.extend_from_slice(&blob);
+        let value = read_value(RDB_TYPE_SET_INTSET, &mut buf.as_slice())?;
+        assert_eq!(
+            value,
+            RdbValue::Set(vec![b"-3".to_vec(), b"1".to_vec(), b"2".to_vec()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_listpack() -> Result<(), RdbError> {
+        // HSET h f v n 100000 on Redis 7.0
+        let blob = [
+            0x1a, 0, 0, 0, 4, 0, 0x81, b'f', 2, 0x81, b'v', 2, 0x81, b'n', 2, 0xf2, 0xa0, 0x86,
+            0x01, 4, 0xff,
+        ];
+        let entries = read_listpack(&blob)?;
+        assert_eq!(
+            entries,
+            vec![
+                b"f".to_vec(),
+                b"v".to_vec(),
+                b"n".to_vec(),
+                b"100000".to_vec()
+            ]
+        );
+        // 7 bit and negative 13 bit integers
+        let blob = [0x0b, 0, 0, 0, 2, 0, 0x05, 1, 0xdf, 0xff, 2, 0xff];
+        assert_eq!(read_listpack(&blob)?, vec![b"5".to_vec(), b"-1".to_vec()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ziplist() -> Result<(), RdbError> {
+        // HSET h f v n 12 on Redis 6.2
+        let blob = [
+            0x18, 0, 0, 0, 0x12, 0, 0, 0, 4, 0, 0, 1, b'f', 3, 1, b'v', 3, 1, b'n', 3, 0xfd, 0xff,
+        ];
+        let entries = read_ziplist(&blob)?;
+        assert_eq!(
+            entries,
+            vec![b"f".to_vec(), b"v".to_vec(), b"n".to_vec(), b"12".to_vec()]
+        );
+        Ok(())
+    }
+}
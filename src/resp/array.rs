@@ -1,9 +1,10 @@
 use std::ops::{Deref, DerefMut};
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
 
 use super::{
-    calc_total_length, parse_length, RespDecode, RespEncode, RespError, RespFrame, CRLF_LEN,
+    aggregate_size, calc_total_length, encode_aggregate, limits, parse_length, protocol_error,
+    RespDecode, RespEncode, RespError, RespFrame, CRLF_LEN,
 };
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
@@ -14,17 +15,17 @@ const NULL_ARRAY_STRING: &[u8; 5] = b"*-1\r\n";
 
 // - array: "*<number-of-elements>\r\n<element-1>...<element-n>"
 impl RespEncode for Option<RespArray> {
-    fn encode(self) -> Vec<u8> {
-        if let Some(s) = self {
-            // let mut buf = Vec::with_capacity(BUF_CAP);
-            let mut buf = Vec::new();
-            buf.extend_from_slice(&format!("*{}\r\n", s.0.len()).into_bytes());
-            for frame in s.0 {
-                buf.extend_from_slice(&frame.encode());
-            }
-            buf
-        } else {
-            NULL_ARRAY_STRING.to_vec()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        match self {
+            Some(s) => encode_aggregate(buf, Self::PREFIX, &s.0),
+            None => buf.put_slice(NULL_ARRAY_STRING),
+        }
+    }
+
+    fn size_hint(&self) -> usize {
+        match self {
+            Some(s) => aggregate_size(Self::PREFIX, &s.0),
+            None => NULL_ARRAY_STRING.len(),
         }
     }
 }
@@ -40,7 +41,7 @@ impl RespDecode for Option<RespArray> {
         } else {
             let (end, len) = parse_length(buf, Self::PREFIX)?;
 
-            let len = len as usize;
+            let len = limits::elements(len as i64).map_err(protocol_error)?;
             let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
 
             // println!("len={},total_len={}", len, total_len);
@@ -65,7 +66,7 @@ impl RespDecode for Option<RespArray> {
         if len == -1 {
             Ok(5)
         } else {
-            let len = len as usize;
+            let len = limits::elements(len as i64).map_err(protocol_error)?;
             calc_total_length(buf, end, len, Self::PREFIX)
         }
     }
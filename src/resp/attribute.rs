@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use bytes::{Buf, BytesMut};
+
+use super::{
+    calc_total_length, encode_entries, entries_size, parse_elements, RespDecode, RespEncode,
+    RespError, RespFrame, CRLF_LEN,
+};
+
+// auxiliary data sent before a reply, a client that does not use it reads the reply alone
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct RespAttribute {
+    pub(crate) attributes: BTreeMap<RespFrame, RespFrame>,
+    pub(crate) data: Box<RespFrame>,
+}
+
+impl RespAttribute {
+    pub fn new(attributes: BTreeMap<RespFrame, RespFrame>, data: impl Into<RespFrame>) -> Self {
+        RespAttribute {
+            attributes,
+            data: Box::new(data.into()),
+        }
+    }
+
+    pub fn attributes(&self) -> &BTreeMap<RespFrame, RespFrame> {
+        &self.attributes
+    }
+
+    pub fn data(&self) -> &RespFrame {
+        &self.data
+    }
+
+    pub fn into_data(self) -> RespFrame {
+        *self.data
+    }
+}
+
+// - attribute: "|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><reply>"
+impl RespEncode for RespAttribute {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        encode_entries(buf, Self::PREFIX, &self.attributes);
+        self.data.encode_to(buf);
+    }
+
+    fn size_hint(&self) -> usize {
+        entries_size(Self::PREFIX, &self.attributes) + self.data.size_hint()
+    }
+}
+
+impl RespDecode for RespAttribute {
+    const PREFIX: &'static str = "|";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let total_len = Self::expect_length(buf)?;
+        if buf.len() < total_len {
+            return Err(RespError::NotComplete);
+        }
+
+        let (end, len) = parse_elements(buf, Self::PREFIX)?;
+        buf.advance(end + CRLF_LEN);
+        let mut attributes = BTreeMap::new();
+        for _ in 0..len {
+            let key = RespFrame::decode(buf)?;
+            let value = RespFrame::decode(buf)?;
+            attributes.insert(key, value);
+        }
+        let data = RespFrame::decode(buf)?;
+        Ok(RespAttribute::new(attributes, data))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let (end, len) = parse_elements(buf, Self::PREFIX)?;
+        calc_total_length(buf, end, len, Self::PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RespArray, SimpleString};
+    use anyhow::Result;
+
+    fn attribute() -> RespAttribute {
+        let mut attributes = BTreeMap::new();
+        attributes.insert(SimpleString::new("ttl").into(), 3600.into());
+        RespAttribute::new(attributes, Some(RespArray::new([1.into(), 2.into()])))
+    }
+
+    #[test]
+    fn test_attribute_encode() {
+        let frame: RespFrame = attribute().into();
+        assert_eq!(frame.encode(), b"|1\r\n+ttl\r\n:3600\r\n*2\r\n:1\r\n:2\r\n");
+    }
+
+    #[test]
+    fn test_attribute_decode() -> Result<()> {
+        let mut buf = BytesMut::from("|1\r\n+ttl\r\n:3600\r\n*2\r\n:1\r\n:2\r\n");
+        assert_eq!(RespAttribute::expect_length(&buf), Ok(29));
+        let frame = RespAttribute::decode(&mut buf)?;
+        assert_eq!(frame, attribute());
+        assert!(buf.is_empty());
+
+        // the reply is part of the frame
+        buf.extend_from_slice(b"|1\r\n+ttl\r\n:3600\r\n");
+        assert_eq!(RespAttribute::decode(&mut buf), Err(RespError::NotComplete));
+        Ok(())
+    }
+}
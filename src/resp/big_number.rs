@@ -0,0 +1,88 @@
+use std::ops::Deref;
+
+use bytes::{BufMut, BytesMut};
+
+use super::{extract_simple_frame_data, RespDecode, RespEncode, RespError, CRLF, CRLF_LEN};
+
+// kept as text, the value may not fit in any integer type
+#[derive(Debug, Clone, Hash, Ord, PartialEq, Eq, PartialOrd)]
+pub struct BigNumber(pub(crate) String);
+
+impl BigNumber {
+    pub fn new(s: impl Into<String>) -> Self {
+        BigNumber(s.into())
+    }
+}
+
+impl Deref for BigNumber {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// - big number: "([+|-]<number>\r\n"
+impl RespEncode for BigNumber {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.put_slice(Self::PREFIX.as_bytes());
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(CRLF);
+    }
+
+    fn size_hint(&self) -> usize {
+        Self::PREFIX.len() + self.0.len() + CRLF_LEN
+    }
+}
+
+impl RespDecode for BigNumber {
+    const PREFIX: &'static str = "(";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        let s = String::from_utf8_lossy(&buf[Self::PREFIX.len()..end]).to_string();
+        let digits = s.strip_prefix(['+', '-']).unwrap_or(&s);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(RespError::InvalidFrame(format!(
+                "invalid big number: {}",
+                s
+            )));
+        }
+        let _ = buf.split_to(end + CRLF_LEN);
+        Ok(BigNumber::new(s))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        Ok(end + CRLF_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_big_number_encode() {
+        let frame: RespFrame = BigNumber::new("3492890328409238509324850943850943825024385").into();
+        assert_eq!(
+            frame.encode(),
+            b"(3492890328409238509324850943850943825024385\r\n"
+        );
+    }
+
+    #[test]
+    fn test_big_number_decode() -> Result<()> {
+        let mut buf = BytesMut::from("(-3492890328409238509324850943850943825024385\r\n");
+        let frame = BigNumber::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            BigNumber::new("-3492890328409238509324850943850943825024385")
+        );
+
+        let mut buf = BytesMut::from("(12a\r\n");
+        assert!(BigNumber::decode(&mut buf).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::ops::Deref;
+
+use bytes::BytesMut;
+
+use super::{blob_length, blob_size, decode_blob, encode_blob, RespDecode, RespEncode, RespError};
+
+// an error whose message may hold CRLF or binary data
+#[derive(Debug, Clone, Hash, Ord, PartialEq, Eq, PartialOrd)]
+pub struct BlobError(pub(crate) Vec<u8>);
+
+impl BlobError {
+    pub fn new(s: impl Into<Vec<u8>>) -> Self {
+        BlobError(s.into())
+    }
+}
+
+impl Deref for BlobError {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// - bulk error: "!<length>\r\n<error>\r\n"
+impl RespEncode for BlobError {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        encode_blob(buf, Self::PREFIX, &self.0)
+    }
+
+    fn size_hint(&self) -> usize {
+        blob_size(Self::PREFIX, self.0.len())
+    }
+}
+
+impl RespDecode for BlobError {
+    const PREFIX: &'static str = "!";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Ok(BlobError(decode_blob(buf, Self::PREFIX)?))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        blob_length(buf, Self::PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_blob_error_encode() {
+        let frame: RespFrame = BlobError::new("SYNTAX invalid syntax").into();
+        assert_eq!(frame.encode(), b"!21\r\nSYNTAX invalid syntax\r\n");
+    }
+
+    #[test]
+    fn test_blob_error_decode() -> Result<()> {
+        let mut buf = BytesMut::from("!8\r\nERR a\r\nb\r\n");
+        assert_eq!(BlobError::expect_length(&buf), Ok(14));
+        let frame = BlobError::decode(&mut buf)?;
+        assert_eq!(frame, BlobError::new("ERR a\r\nb"));
+
+        buf.extend_from_slice(b"!8\r\nERR");
+        assert_eq!(BlobError::decode(&mut buf), Err(RespError::NotComplete));
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 use super::{RespDecode, RespEncode, RespError};
 
@@ -6,8 +6,12 @@ use super::extract_fixed_data;
 
 // - boolean: "#<t|f>\r\n"
 impl RespEncode for bool {
-    fn encode(self) -> Vec<u8> {
-        format!("#{}\r\n", if self { "t" } else { "f" }).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.put_slice(if *self { b"#t\r\n" } else { b"#f\r\n" });
+    }
+
+    fn size_hint(&self) -> usize {
+        4
     }
 }
 
@@ -1,8 +1,11 @@
 use std::{fmt, ops::Deref};
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
-use super::{parse_length, RespDecode, RespEncode, RespError, CRLF_LEN};
+use super::{
+    blob_size, encode_blob, limits, parse_length, protocol_error, RespDecode, RespEncode,
+    RespError, CRLF_LEN,
+};
 
 // #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 // pub struct RespNullBulkString;
@@ -26,8 +29,9 @@ use super::{parse_length, RespDecode, RespEncode, RespError, CRLF_LEN};
 //     }
 // }
 
+// the data is a handle to the buffer the frame was decoded from, cloning it is cheap
 #[derive(Debug, Clone, Hash, PartialEq, Ord, Eq, PartialOrd)]
-pub struct BulkString(pub(crate) Vec<u8>);
+pub struct BulkString(pub(crate) Bytes);
 
 // pub struct BulkString(pub(crate) Option<Vec<u8>>);
 
@@ -47,15 +51,17 @@ const NULL_BULK_STRING: &[u8; 5] = b"$-1\r\n";
 
 // - bulk string: "$<length>\r\n<data>\r\n"
 impl RespEncode for Option<BulkString> {
-    fn encode(self) -> Vec<u8> {
-        if let Some(s) = self {
-            let mut buf = Vec::with_capacity(s.len() + 16);
-            buf.extend_from_slice(&format!("${}\r\n", s.len()).into_bytes());
-            buf.extend_from_slice(&s);
-            buf.extend_from_slice(b"\r\n");
-            buf
-        } else {
-            NULL_BULK_STRING.to_vec()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        match self {
+            Some(s) => encode_blob(buf, Self::PREFIX, s),
+            None => buf.put_slice(NULL_BULK_STRING),
+        }
+    }
+
+    fn size_hint(&self) -> usize {
+        match self {
+            Some(s) => blob_size(Self::PREFIX, s.len()),
+            None => NULL_BULK_STRING.len(),
         }
     }
 }
@@ -68,7 +74,7 @@ impl RespDecode for Option<BulkString> {
             Ok(None)
         } else {
             let (end, len) = parse_length(buf, Self::PREFIX)?;
-            let len = len as usize;
+            let len = limits::bulk_len(len as i64).map_err(protocol_error)?;
             let remained = &buf[end + CRLF_LEN..];
             if remained.len() < len + CRLF_LEN {
                 return Err(RespError::NotComplete);
@@ -76,8 +82,9 @@ impl RespDecode for Option<BulkString> {
 
             buf.advance(end + CRLF_LEN);
 
-            let data = buf.split_to(len + CRLF_LEN);
-            Ok(Some(BulkString::new(data[..len].to_vec())))
+            let data = buf.split_to(len).freeze();
+            buf.advance(CRLF_LEN);
+            Ok(Some(BulkString(data)))
         }
     }
 
@@ -86,7 +93,7 @@ impl RespDecode for Option<BulkString> {
         if len == -1 {
             Ok(5)
         } else {
-            let len = len as usize;
+            let len = limits::bulk_len(len as i64).map_err(protocol_error)?;
             Ok(end + CRLF_LEN + len + CRLF_LEN)
         }
     }
@@ -94,7 +101,11 @@ impl RespDecode for Option<BulkString> {
 
 impl BulkString {
     pub fn new(s: impl Into<Vec<u8>>) -> Self {
-        BulkString(s.into())
+        BulkString(s.into().into())
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0.into()
     }
 }
 
@@ -105,34 +116,40 @@ impl AsRef<[u8]> for BulkString {
 }
 
 impl Deref for BulkString {
-    type Target = Vec<u8>;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
+impl From<Bytes> for BulkString {
+    fn from(s: Bytes) -> Self {
+        BulkString(s)
+    }
+}
+
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(s.as_bytes().to_vec())
+        BulkString(Bytes::copy_from_slice(s.as_bytes()))
     }
 }
 
 impl From<String> for BulkString {
     fn from(s: String) -> Self {
-        BulkString(s.into_bytes())
+        BulkString(s.into())
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
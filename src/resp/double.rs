@@ -1,20 +1,24 @@
+use std::fmt::Write;
+
 use bytes::BytesMut;
 
 use super::{extract_simple_frame_data, Double, RespDecode, RespEncode, RespError, CRLF_LEN};
 
 // - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
 impl RespEncode for Double {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(32);
-        let ret = if self.abs() > 1e+8 || self.abs() < 1e-8 {
-            format!(",{:+e}\r\n", self.0)
+    fn encode_to(&self, buf: &mut BytesMut) {
+        // writing to a BytesMut never fails, it grows as needed
+        let _ = if self.abs() > 1e+8 || self.abs() < 1e-8 {
+            write!(buf, ",{:+e}\r\n", self.0)
         } else {
             let sign = if self.0 < 0.0 { "" } else { "+" };
-            format!(",{}{}\r\n", sign, self.0)
+            write!(buf, ",{}{}\r\n", sign, self.0)
         };
+    }
 
-        buf.extend_from_slice(&ret.into_bytes());
-        buf
+    // ",-1.7976931348623157e308\r\n" is the longest double
+    fn size_hint(&self) -> usize {
+        32
     }
 }
 
@@ -3,7 +3,10 @@ use std::{collections::BTreeMap, hash::Hash};
 use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
 
-use crate::{BulkString, RespArray, RespNull, SimpleError, SimpleString};
+use crate::{
+    BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespNull, RespPush, RespSet,
+    SimpleError, SimpleString, VerbatimString,
+};
 
 use super::{Double, RespDecode, RespError};
 
@@ -19,6 +22,12 @@ pub enum RespFrame {
     Array(Option<RespArray>),
     Double(Double),
     Map(BTreeMap<RespFrame, RespFrame>),
+    Set(RespSet),
+    Push(RespPush),
+    BigNumber(BigNumber),
+    Verbatim(VerbatimString),
+    BlobError(BlobError),
+    Attribute(RespAttribute),
 }
 
 // impl RespEncode for RespFrame {
@@ -29,6 +38,58 @@ pub enum RespFrame {
 //     }
 // }
 
+impl RespFrame {
+    /// The reply sent to a RESP2 client for a RESP3 reply, as Redis does when
+    /// the connection did not switch protocol with `HELLO 3`.
+    pub fn into_resp2(self) -> RespFrame {
+        match self {
+            RespFrame::Null(_) => RespFrame::BulkString(None),
+            RespFrame::Boolean(b) => RespFrame::Integer(b as i64),
+            RespFrame::Double(d) => {
+                let s = if d.is_nan() {
+                    "nan".to_string()
+                } else {
+                    d.0.to_string()
+                };
+                Some(BulkString::from(s)).into()
+            }
+            RespFrame::Array(Some(array)) => Some(RespArray::new(
+                array
+                    .0
+                    .into_iter()
+                    .map(RespFrame::into_resp2)
+                    .collect::<Vec<_>>(),
+            ))
+            .into(),
+            RespFrame::Map(map) => {
+                let mut array = Vec::with_capacity(map.len() * 2);
+                for (key, value) in map {
+                    array.push(key.into_resp2());
+                    array.push(value.into_resp2());
+                }
+                Some(RespArray::new(array)).into()
+            }
+            RespFrame::Set(RespSet(frames)) | RespFrame::Push(RespPush(frames)) => {
+                Some(RespArray::new(
+                    frames
+                        .into_iter()
+                        .map(RespFrame::into_resp2)
+                        .collect::<Vec<_>>(),
+                ))
+                .into()
+            }
+            RespFrame::BigNumber(n) => Some(BulkString::from(n.0)).into(),
+            RespFrame::Verbatim(s) => Some(BulkString::new(s.data)).into(),
+            // a simple error cannot hold a newline
+            RespFrame::BlobError(e) => {
+                SimpleError::new(String::from_utf8_lossy(&e.0).replace(['\r', '\n'], " ")).into()
+            }
+            RespFrame::Attribute(attribute) => attribute.into_data().into_resp2(),
+            frame => frame,
+        }
+    }
+}
+
 impl From<&str> for RespFrame {
     fn from(s: &str) -> Self {
         SimpleString(s.to_string()).into()
@@ -37,13 +98,13 @@ impl From<&str> for RespFrame {
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        Some(BulkString(s.to_vec())).into()
+        Some(BulkString::from(s)).into()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for RespFrame {
     fn from(s: &[u8; N]) -> Self {
-        Some(BulkString(s.to_vec())).into()
+        Some(BulkString::from(s)).into()
     }
 }
 
@@ -70,6 +131,17 @@ impl RespDecode for RespFrame {
                 Ok(frame) => Ok(frame.into()),
                 Err(e) => Err(e),
             },
+            Some(b':') => Ok(i64::decode(buf)?.into()),
+            Some(b'_') => Ok(RespNull::decode(buf)?.into()),
+            Some(b'#') => Ok(bool::decode(buf)?.into()),
+            Some(b',') => Ok(Double(f64::decode(buf)?).into()),
+            Some(b'%') => Ok(BTreeMap::<RespFrame, RespFrame>::decode(buf)?.into()),
+            Some(b'~') => Ok(RespSet::decode(buf)?.into()),
+            Some(b'>') => Ok(RespPush::decode(buf)?.into()),
+            Some(b'(') => Ok(BigNumber::decode(buf)?.into()),
+            Some(b'=') => Ok(VerbatimString::decode(buf)?.into()),
+            Some(b'!') => Ok(BlobError::decode(buf)?.into()),
+            Some(b'|') => Ok(RespAttribute::decode(buf)?.into()),
             None => Err(RespError::NotComplete),
             _ => Err(RespError::InvalidFrameType(format!(
                 "expect_length: unknown frame type: {:?}",
@@ -85,7 +157,22 @@ impl RespDecode for RespFrame {
             Some(b'$') => Option::<BulkString>::expect_length(buf),
             Some(b'+') => SimpleString::expect_length(buf),
             Some(b'-') => SimpleError::expect_length(buf),
-            _ => Err(RespError::NotComplete),
+            Some(b':') => i64::expect_length(buf),
+            Some(b'_') => RespNull::expect_length(buf),
+            Some(b'#') => bool::expect_length(buf),
+            Some(b',') => f64::expect_length(buf),
+            Some(b'%') => BTreeMap::<RespFrame, RespFrame>::expect_length(buf),
+            Some(b'~') => RespSet::expect_length(buf),
+            Some(b'>') => RespPush::expect_length(buf),
+            Some(b'(') => BigNumber::expect_length(buf),
+            Some(b'=') => VerbatimString::expect_length(buf),
+            Some(b'!') => BlobError::expect_length(buf),
+            Some(b'|') => RespAttribute::expect_length(buf),
+            None => Err(RespError::NotComplete),
+            _ => Err(RespError::InvalidFrameType(format!(
+                "expect_length: unknown frame type: {:?}",
+                buf
+            ))),
         }
     }
 }
@@ -93,6 +180,7 @@ impl RespDecode for RespFrame {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::RespEncode;
 
     #[test]
     fn respv2_should_work() {
@@ -103,4 +191,70 @@ mod tests {
         let resp = RespFrame::decode(&mut buf);
         assert!(resp.is_ok());
     }
+
+    #[test]
+    fn resp3_frames_round_trip() -> anyhow::Result<()> {
+        let mut attributes = BTreeMap::new();
+        attributes.insert(SimpleString::new("key-popularity").into(), 1.into());
+        let frames: Vec<RespFrame> = vec![
+            RespSet::new([1.into(), SimpleString::new("a").into()]).into(),
+            RespPush::new([
+                Some(BulkString::from("message")).into(),
+                Some(BulkString::from("channel")).into(),
+            ])
+            .into(),
+            BigNumber::new("-3492890328409238509324850943850943825024385").into(),
+            VerbatimString::new(*b"txt", b"Some string".to_vec()).into(),
+            BlobError::new(b"SYNTAX invalid syntax".to_vec()).into(),
+            RespAttribute::new(attributes, RespSet::new([true.into()])).into(),
+            RespNull.into(),
+            Double(1.5).into(),
+        ];
+
+        let mut buf = BytesMut::new();
+        for frame in frames.clone() {
+            buf.extend_from_slice(&frame.encode());
+        }
+        for frame in frames {
+            let len = RespFrame::expect_length(&buf)?;
+            assert_eq!(RespFrame::decode(&mut buf)?, frame);
+            assert!(len > 0);
+        }
+        assert!(buf.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn resp3_frames_into_resp2() {
+        let mut map = BTreeMap::new();
+        map.insert(b"proto".into(), 3.into());
+        map.insert(b"flag".into(), true.into());
+        let frame: RespFrame = Some(RespArray::new(vec![
+            map.into(),
+            RespNull.into(),
+            Double(1.5).into(),
+            RespSet::new([false.into()]).into(),
+            VerbatimString::new(*b"txt", "hello").into(),
+            BlobError::new("ERR a\r\nb").into(),
+        ]))
+        .into();
+        assert_eq!(
+            frame.into_resp2(),
+            Some(RespArray::new(vec![
+                Some(RespArray::new(vec![
+                    b"flag".into(),
+                    1.into(),
+                    b"proto".into(),
+                    3.into()
+                ]))
+                .into(),
+                RespFrame::BulkString(None),
+                b"1.5".into(),
+                Some(RespArray::new(vec![0.into()])).into(),
+                b"hello".into(),
+                SimpleError::new("ERR a  b").into(),
+            ]))
+            .into()
+        );
+    }
 }
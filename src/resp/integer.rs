@@ -1,11 +1,18 @@
 use bytes::BytesMut;
 
-use super::{extract_simple_frame_data, RespDecode, RespEncode, RespError, CRLF_LEN};
+use super::{
+    encode_number, extract_simple_frame_data, number_size, RespDecode, RespEncode, RespError,
+    CRLF_LEN,
+};
 
 // - integer: ":[<+|->]<value>\r\n"
 impl RespEncode for i64 {
-    fn encode(self) -> Vec<u8> {
-        format!(":{}\r\n", self).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        encode_number(buf, Self::PREFIX, *self)
+    }
+
+    fn size_hint(&self) -> usize {
+        number_size(Self::PREFIX, *self)
     }
 }
 
@@ -38,6 +45,12 @@ mod tests {
 
         let frame: RespFrame = (-123).into();
         assert_eq!(frame.encode(), b":-123\r\n");
+
+        for n in [0, 9, 10, -1, i64::MAX, i64::MIN] {
+            let frame: RespFrame = n.into();
+            assert_eq!(frame.size_hint(), format!(":{}\r\n", n).len());
+            assert_eq!(frame.encode(), format!(":{}\r\n", n).into_bytes());
+        }
     }
 
     #[test]
@@ -0,0 +1,128 @@
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// the labels of the protocol errors, "invalid <label>"
+pub(crate) const BULK_LENGTH: &str = "bulk length";
+pub(crate) const MULTIBULK_LENGTH: &str = "multibulk length";
+pub(crate) const NESTING_DEPTH: &str = "nesting depth";
+pub(crate) const FRAME_TYPE: &str = "frame type";
+
+static MAX_BULK_LEN: AtomicUsize = AtomicUsize::new(RespLimits::DEFAULT_MAX_BULK_LEN);
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(RespLimits::DEFAULT_MAX_DEPTH);
+static MAX_ELEMENTS: AtomicUsize = AtomicUsize::new(RespLimits::DEFAULT_MAX_ELEMENTS);
+
+thread_local! {
+    // the aggregate frames being measured by the decoder on this thread
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The limits of the frames accepted by all the decoders, a frame over a
+/// limit is a protocol error instead of a frame waiting for more data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RespLimits {
+    // proto-max-bulk-len, the length of a bulk string, a verbatim string or a blob error
+    pub max_bulk_len: usize,
+    // the aggregate frames nested in each other
+    pub max_depth: usize,
+    // the elements of an array, a set or a push, the entries of a map or an attribute
+    pub max_elements: usize,
+}
+
+impl RespLimits {
+    pub const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+    pub const DEFAULT_MAX_DEPTH: usize = 128;
+    pub const DEFAULT_MAX_ELEMENTS: usize = i32::MAX as usize;
+
+    pub fn current() -> Self {
+        RespLimits {
+            max_bulk_len: MAX_BULK_LEN.load(Ordering::Relaxed),
+            max_depth: MAX_DEPTH.load(Ordering::Relaxed),
+            max_elements: MAX_ELEMENTS.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Applies the limits to the frames decoded from now on.
+    pub fn apply(self) {
+        MAX_BULK_LEN.store(self.max_bulk_len, Ordering::Relaxed);
+        MAX_DEPTH.store(self.max_depth, Ordering::Relaxed);
+        MAX_ELEMENTS.store(self.max_elements, Ordering::Relaxed);
+    }
+}
+
+impl Default for RespLimits {
+    fn default() -> Self {
+        RespLimits {
+            max_bulk_len: Self::DEFAULT_MAX_BULK_LEN,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_elements: Self::DEFAULT_MAX_ELEMENTS,
+        }
+    }
+}
+
+// the length of a non-null bulk string
+pub(crate) fn bulk_len(len: i64) -> Result<usize, &'static str> {
+    match usize::try_from(len) {
+        Ok(len) if len <= MAX_BULK_LEN.load(Ordering::Relaxed) => Ok(len),
+        _ => Err(BULK_LENGTH),
+    }
+}
+
+// the number of elements of a non-null aggregate frame
+pub(crate) fn elements(len: i64) -> Result<usize, &'static str> {
+    match usize::try_from(len) {
+        Ok(len) if len <= MAX_ELEMENTS.load(Ordering::Relaxed) => Ok(len),
+        _ => Err(MULTIBULK_LENGTH),
+    }
+}
+
+// the depth of an aggregate frame nested in others, the outermost is 1
+pub(crate) fn depth(depth: usize) -> Result<usize, &'static str> {
+    if depth > MAX_DEPTH.load(Ordering::Relaxed) {
+        return Err(NESTING_DEPTH);
+    }
+    Ok(depth)
+}
+
+/// Held while the elements of an aggregate frame are measured, the frames
+/// cannot be nested deeper than the stack of the decoder allows.
+pub(crate) struct DepthGuard(());
+
+impl DepthGuard {
+    pub(crate) fn enter() -> Result<Self, &'static str> {
+        let depth = depth(DEPTH.get() + 1)?;
+        DEPTH.set(depth);
+        Ok(DepthGuard(()))
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.set(DEPTH.get() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(RespLimits::current(), RespLimits::default());
+        assert_eq!(bulk_len(-1), Err(BULK_LENGTH));
+        assert_eq!(bulk_len(512 * 1024 * 1024), Ok(512 * 1024 * 1024));
+        assert_eq!(bulk_len(512 * 1024 * 1024 + 1), Err(BULK_LENGTH));
+        assert_eq!(elements(i32::MAX as i64 + 1), Err(MULTIBULK_LENGTH));
+        assert_eq!(depth(128), Ok(128));
+        assert_eq!(depth(129), Err(NESTING_DEPTH));
+
+        let guards = (0..RespLimits::DEFAULT_MAX_DEPTH)
+            .map(|_| DepthGuard::enter())
+            .collect::<Result<Vec<_>, _>>();
+        assert!(guards.is_ok());
+        assert!(DepthGuard::enter().is_err());
+        drop(guards);
+        assert!(DepthGuard::enter().is_ok());
+    }
+}
@@ -1,23 +1,89 @@
 use std::collections::BTreeMap;
 
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 
-use super::{RespDecode, RespEncode, RespError, RespFrame};
+use super::{
+    calc_total_length, encode_entries, entries_size, parse_elements, RespDecode, RespEncode,
+    RespError, RespFrame, CRLF_LEN,
+};
 
 // - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
 impl RespEncode for BTreeMap<RespFrame, RespFrame> {
-    fn encode(self) -> Vec<u8> {
-        todo!()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        encode_entries(buf, Self::PREFIX, self)
+    }
+
+    fn size_hint(&self) -> usize {
+        entries_size(Self::PREFIX, self)
     }
 }
 
+// the keys can be any frame, not only simple strings
 impl RespDecode for BTreeMap<RespFrame, RespFrame> {
-    const PREFIX: &'static str = ":";
-    fn decode(_buf: &mut BytesMut) -> Result<Self, RespError> {
-        todo!()
+    const PREFIX: &'static str = "%";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let total_len = Self::expect_length(buf)?;
+        if buf.len() < total_len {
+            return Err(RespError::NotComplete);
+        }
+
+        let (end, len) = parse_elements(buf, Self::PREFIX)?;
+        buf.advance(end + CRLF_LEN);
+        let mut map = BTreeMap::new();
+        for _ in 0..len {
+            let key = RespFrame::decode(buf)?;
+            let value = RespFrame::decode(buf)?;
+            map.insert(key, value);
+        }
+        Ok(map)
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        todo!()
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let (end, len) = parse_elements(buf, Self::PREFIX)?;
+        calc_total_length(buf, end, len, Self::PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, SimpleString};
+    use anyhow::Result;
+
+    #[test]
+    fn test_map_encode() {
+        let mut map = BTreeMap::new();
+        map.insert(SimpleString::new("first").into(), 1.into());
+        map.insert(Some(BulkString::from("second")).into(), true.into());
+        let frame: RespFrame = map.into();
+        assert_eq!(
+            frame.encode(),
+            b"%2\r\n+first\r\n:1\r\n$6\r\nsecond\r\n#t\r\n"
+        );
+
+        let frame: RespFrame = BTreeMap::new().into();
+        assert_eq!(frame.encode(), b"%0\r\n");
+    }
+
+    #[test]
+    fn test_map_decode() -> Result<()> {
+        let mut buf =
+            BytesMut::from("%3\r\n+first\r\n:1\r\n$6\r\nsecond\r\n#t\r\n:3\r\n*1\r\n:3\r\n");
+        assert_eq!(
+            BTreeMap::<RespFrame, RespFrame>::expect_length(&buf),
+            Ok(44)
+        );
+        let frame = BTreeMap::<RespFrame, RespFrame>::decode(&mut buf)?;
+        let mut map = BTreeMap::new();
+        map.insert(SimpleString::new("first").into(), 1.into());
+        map.insert(Some(BulkString::from("second")).into(), true.into());
+        map.insert(3.into(), Some(RespArray::new(vec![3.into()])).into());
+        assert_eq!(frame, map);
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"%1\r\n$3\r\nkey\r\n");
+        let ret = BTreeMap::<RespFrame, RespFrame>::decode(&mut buf);
+        assert_eq!(ret, Err(RespError::NotComplete));
+        Ok(())
     }
 }
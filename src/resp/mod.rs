@@ -1,32 +1,46 @@
 mod array;
+mod attribute;
+mod big_number;
+mod blob_error;
 mod bool;
 mod bulk_string;
 mod double;
 mod frame;
 mod integer;
+pub(crate) mod limits;
 mod map;
 mod null;
+mod push;
+mod set;
 mod simple_error;
 mod simple_string;
+mod verbatim;
 
 use std::{hash::Hasher, ops::Deref};
 
 pub use array::RespArray;
+pub use attribute::RespAttribute;
+pub use big_number::BigNumber;
+pub use blob_error::BlobError;
 pub use bulk_string::BulkString;
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
 use enum_dispatch::enum_dispatch;
 pub use frame::*;
+pub use limits::RespLimits;
 pub use null::*;
+pub use push::RespPush;
+pub use set::RespSet;
 pub use simple_error::*;
 pub use simple_string::*;
 use std::collections::BTreeMap;
 use std::hash::Hash;
 use thiserror::Error;
+pub use verbatim::VerbatimString;
 
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct Double(pub f64);
 
 impl Eq for Double {}
@@ -37,11 +51,16 @@ impl Hash for Double {
     }
 }
 
+// NaN is a valid double frame, doubles are compared by their bits as they are hashed
+impl PartialEq for Double {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
 impl Ord for Double {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0
-            .partial_cmp(&other.0)
-            .expect("OrderedFloat cannot compare NaN values")
+        self.0.total_cmp(&other.0)
     }
 }
 
@@ -61,7 +80,21 @@ impl Deref for Double {
 
 #[enum_dispatch(RespEncode)]
 pub trait RespEncode {
-    fn encode(self) -> Vec<u8>;
+    /// Writes the frame at the end of the buffer.
+    fn encode_to(&self, buf: &mut BytesMut);
+
+    /// The size of the encoded frame, to reserve the buffer before encoding
+    /// it. It is exact but for doubles, whose size is an upper bound.
+    fn size_hint(&self) -> usize;
+
+    fn encode(self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = BytesMut::with_capacity(self.size_hint());
+        self.encode_to(&mut buf);
+        buf.into()
+    }
 }
 
 pub trait RespDecode: Sized {
@@ -85,6 +118,12 @@ pub enum RespError {
     Utf8Error(#[from] std::string::FromUtf8Error),
     #[error("Parse float error: {0}")]
     ParseFloatError(#[from] std::num::ParseFloatError),
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+}
+
+pub(crate) fn protocol_error(label: &str) -> RespError {
+    RespError::Protocol(format!("invalid {}", label))
 }
 
 // utility functions
@@ -129,7 +168,7 @@ fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespErro
 // find nth CRLF in the buffer
 fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
     let mut count = 0;
-    for i in 1..buf.len() - 1 {
+    for i in 1..buf.len().saturating_sub(1) {
         if buf[i] == b'\r' && buf[i + 1] == b'\n' {
             count += 1;
             if count == nth {
@@ -148,33 +187,147 @@ fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, isize), RespError> {
     Ok((end, s.parse()?))
 }
 
+// the number of elements of an aggregate frame, within the limits
+fn parse_elements(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
+    let (end, len) = parse_length(buf, prefix)?;
+    let len = limits::elements(len as i64).map_err(protocol_error)?;
+    Ok((end, len))
+}
+
 fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
-    match prefix {
-        "*" | "~" => {
-            // find nth CRLF in the buffer, for array and set, we need to find 1 CRLF for each element
-            for _ in 0..len {
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
-            }
-            Ok(total)
-        }
-        "%" => {
-            // find nth CRLF in the buffer. For map, we need to find 2 CRLF for each key-value pair
-            for _ in 0..len {
-                let len = SimpleString::expect_length(data)?;
+    let count = match prefix {
+        // find nth CRLF in the buffer, for array, set and push, we need to find 1 CRLF for each element
+        "*" | "~" | ">" => len,
+        // a map has 2 frames for each key-value pair, the keys can be any frame
+        "%" => len * 2,
+        // an attribute is followed by the reply it is attached to
+        "|" => len * 2 + 1,
+        _ => return Ok(len + CRLF_LEN),
+    };
 
-                data = &data[len..];
-                total += len;
+    let _guard = limits::DepthGuard::enter().map_err(protocol_error)?;
+    for _ in 0..count {
+        let len = RespFrame::expect_length(data)?;
+        if len > data.len() {
+            return Err(RespError::NotComplete);
+        }
+        data = &data[len..];
+        total += len;
+    }
+    Ok(total)
+}
 
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
-            }
-            Ok(total)
+// - "<prefix><number>\r\n", an integer or the header of the frames with a length
+fn encode_number(buf: &mut BytesMut, prefix: &str, n: i64) {
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+    let mut m = n.unsigned_abs();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (m % 10) as u8;
+        m /= 10;
+        if m == 0 {
+            break;
         }
-        _ => Ok(len + CRLF_LEN),
     }
+
+    buf.put_slice(prefix.as_bytes());
+    if n < 0 {
+        buf.put_u8(b'-');
+    }
+    buf.put_slice(&digits[i..]);
+    buf.put_slice(CRLF);
+}
+
+fn number_size(prefix: &str, n: i64) -> usize {
+    let mut digits = 1;
+    let mut m = n.unsigned_abs();
+    while m >= 10 {
+        m /= 10;
+        digits += 1;
+    }
+    prefix.len() + (n < 0) as usize + digits + CRLF_LEN
+}
+
+// - "<prefix><number-of-elements>\r\n<element-1>...<element-n>", shared by array, set and push
+fn encode_aggregate(buf: &mut BytesMut, prefix: &str, frames: &[RespFrame]) {
+    encode_number(buf, prefix, frames.len() as i64);
+    for frame in frames {
+        frame.encode_to(buf);
+    }
+}
+
+fn aggregate_size(prefix: &str, frames: &[RespFrame]) -> usize {
+    let size = number_size(prefix, frames.len() as i64);
+    size + frames.iter().map(RespFrame::size_hint).sum::<usize>()
+}
+
+fn decode_aggregate(buf: &mut BytesMut, prefix: &str) -> Result<Vec<RespFrame>, RespError> {
+    let (end, len) = parse_elements(buf, prefix)?;
+    let total_len = calc_total_length(buf, end, len, prefix)?;
+    if buf.len() < total_len {
+        return Err(RespError::NotComplete);
+    }
+
+    buf.advance(end + CRLF_LEN);
+    let mut frames = Vec::with_capacity(len);
+    for _ in 0..len {
+        frames.push(RespFrame::decode(buf)?);
+    }
+    Ok(frames)
+}
+
+fn aggregate_length(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
+    let (end, len) = parse_elements(buf, prefix)?;
+    calc_total_length(buf, end, len, prefix)
+}
+
+// - "<prefix><number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>", shared by map and attribute
+fn encode_entries(buf: &mut BytesMut, prefix: &str, entries: &BTreeMap<RespFrame, RespFrame>) {
+    encode_number(buf, prefix, entries.len() as i64);
+    for (key, value) in entries {
+        key.encode_to(buf);
+        value.encode_to(buf);
+    }
+}
+
+fn entries_size(prefix: &str, entries: &BTreeMap<RespFrame, RespFrame>) -> usize {
+    let size = number_size(prefix, entries.len() as i64);
+    let entries = entries.iter().map(|(k, v)| k.size_hint() + v.size_hint());
+    size + entries.sum::<usize>()
+}
+
+// - "<prefix><length>\r\n<data>\r\n", shared by bulk string, bulk error and verbatim string
+fn encode_blob(buf: &mut BytesMut, prefix: &str, data: &[u8]) {
+    encode_number(buf, prefix, data.len() as i64);
+    buf.put_slice(data);
+    buf.put_slice(CRLF);
+}
+
+fn blob_size(prefix: &str, len: usize) -> usize {
+    number_size(prefix, len as i64) + len + CRLF_LEN
+}
+
+fn decode_blob(buf: &mut BytesMut, prefix: &str) -> Result<Vec<u8>, RespError> {
+    let len = blob_length(buf, prefix)?;
+    if buf.len() < len {
+        return Err(RespError::NotComplete);
+    }
+    let data = buf.split_to(len);
+    let start = find_crlf(&data, 1).ok_or(RespError::NotComplete)? + CRLF_LEN;
+    if !data.ends_with(CRLF) {
+        return Err(RespError::InvalidFrame(format!(
+            "expect CRLF after the {} bytes of data",
+            len - start - CRLF_LEN
+        )));
+    }
+    Ok(data[start..len - CRLF_LEN].to_vec())
+}
+
+fn blob_length(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
+    let (end, len) = parse_length(buf, prefix)?;
+    let len = limits::bulk_len(len as i64).map_err(protocol_error)?;
+    Ok(end + CRLF_LEN + len + CRLF_LEN)
 }
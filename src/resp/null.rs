@@ -1,4 +1,4 @@
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 use super::{extract_fixed_data, RespDecode, RespEncode, RespError};
 
@@ -14,8 +14,12 @@ impl RespNull {
 // - null: "_\r\n"
 // RESP 3 新特性，Redis Insight不支持
 impl RespEncode for RespNull {
-    fn encode(self) -> Vec<u8> {
-        b"_\r\n".to_vec()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.put_slice(b"_\r\n");
+    }
+
+    fn size_hint(&self) -> usize {
+        3
     }
 }
 
@@ -0,0 +1,79 @@
+use std::ops::{Deref, DerefMut};
+
+use bytes::BytesMut;
+
+use super::{
+    aggregate_length, aggregate_size, decode_aggregate, encode_aggregate, RespDecode, RespEncode,
+    RespError, RespFrame,
+};
+
+// out-of-band data sent by the server, such as pub/sub messages and invalidations
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct RespPush(pub(crate) Vec<RespFrame>);
+
+// - push: "><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespEncode for RespPush {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        encode_aggregate(buf, Self::PREFIX, &self.0)
+    }
+
+    fn size_hint(&self) -> usize {
+        aggregate_size(Self::PREFIX, &self.0)
+    }
+}
+
+impl RespDecode for RespPush {
+    const PREFIX: &'static str = ">";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Ok(RespPush(decode_aggregate(buf, Self::PREFIX)?))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, Self::PREFIX)
+    }
+}
+
+impl RespPush {
+    pub fn new(s: impl Into<Vec<RespFrame>>) -> Self {
+        RespPush(s.into())
+    }
+}
+
+impl Deref for RespPush {
+    type Target = Vec<RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RespPush {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_push_encode() {
+        let frame: RespFrame = RespPush::new([b"a".into(), 1.into()]).into();
+        assert_eq!(frame.encode(), b">2\r\n$1\r\na\r\n:1\r\n");
+    }
+
+    #[test]
+    fn test_push_decode() -> Result<()> {
+        let mut buf = BytesMut::from(">2\r\n$1\r\na\r\n:1\r\n");
+        assert_eq!(RespPush::expect_length(&buf), Ok(15));
+
+        let frame = RespPush::decode(&mut buf)?;
+        assert_eq!(frame, RespPush::new([b"a".into(), 1.into()]));
+
+        buf.extend_from_slice(b">2\r\n$1\r\na\r\n");
+        assert_eq!(RespPush::decode(&mut buf), Err(RespError::NotComplete));
+        Ok(())
+    }
+}
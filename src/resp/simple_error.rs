@@ -1,4 +1,4 @@
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 use std::ops::Deref;
 
 use anyhow::Result;
@@ -34,8 +34,14 @@ impl Deref for SimpleError {
 }
 
 impl RespEncode for SimpleError {
-    fn encode(self) -> Vec<u8> {
-        format!("-{}\r\n", self.0).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.put_slice(Self::PREFIX.as_bytes());
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(b"\r\n");
+    }
+
+    fn size_hint(&self) -> usize {
+        Self::PREFIX.len() + self.0.len() + CRLF_LEN
     }
 }
 
@@ -1,6 +1,6 @@
 use std::ops::Deref;
 
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 use super::{RespDecode, RespEncode, RespError};
 
@@ -43,8 +43,14 @@ impl Deref for SimpleString {
 
 // - simple string: "+OK\r\n"
 impl RespEncode for SimpleString {
-    fn encode(self) -> Vec<u8> {
-        format!("+{}\r\n", self.0).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.put_slice(Self::PREFIX.as_bytes());
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(b"\r\n");
+    }
+
+    fn size_hint(&self) -> usize {
+        Self::PREFIX.len() + self.0.len() + CRLF_LEN
     }
 }
 
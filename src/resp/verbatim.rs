@@ -0,0 +1,88 @@
+use bytes::{BufMut, BytesMut};
+
+use super::{
+    blob_length, blob_size, decode_blob, encode_number, RespDecode, RespEncode, RespError,
+};
+
+// a string with a hint of its format for clients displaying it, "txt" or "mkd"
+#[derive(Debug, Clone, Hash, Ord, PartialEq, Eq, PartialOrd)]
+pub struct VerbatimString {
+    pub(crate) format: [u8; 3],
+    pub(crate) data: Vec<u8>,
+}
+
+impl VerbatimString {
+    pub fn new(format: [u8; 3], data: impl Into<Vec<u8>>) -> Self {
+        VerbatimString {
+            format,
+            data: data.into(),
+        }
+    }
+
+    pub fn format(&self) -> &[u8] {
+        &self.format
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+// - verbatim string: "=<length>\r\n<format>:<data>\r\n"
+impl RespEncode for VerbatimString {
+    // the data is preceded by its format and a colon
+    fn encode_to(&self, buf: &mut BytesMut) {
+        encode_number(buf, Self::PREFIX, (self.data.len() + 4) as i64);
+        buf.put_slice(&self.format);
+        buf.put_u8(b':');
+        buf.put_slice(&self.data);
+        buf.put_slice(b"\r\n");
+    }
+
+    fn size_hint(&self) -> usize {
+        blob_size(Self::PREFIX, self.data.len() + 4)
+    }
+}
+
+impl RespDecode for VerbatimString {
+    const PREFIX: &'static str = "=";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let mut data = decode_blob(buf, Self::PREFIX)?;
+        if data.len() < 4 || data[3] != b':' {
+            return Err(RespError::InvalidFrame(
+                "verbatim string must start with the format".to_string(),
+            ));
+        }
+        let format = [data[0], data[1], data[2]];
+        Ok(VerbatimString::new(format, data.split_off(4)))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        blob_length(buf, Self::PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_verbatim_encode() {
+        let frame: RespFrame = VerbatimString::new(*b"txt", "Some string").into();
+        assert_eq!(frame.encode(), b"=15\r\ntxt:Some string\r\n");
+    }
+
+    #[test]
+    fn test_verbatim_decode() -> Result<()> {
+        let mut buf = BytesMut::from("=15\r\ntxt:Some string\r\n");
+        assert_eq!(VerbatimString::expect_length(&buf), Ok(22));
+        let frame = VerbatimString::decode(&mut buf)?;
+        assert_eq!(frame, VerbatimString::new(*b"txt", "Some string"));
+
+        let mut buf = BytesMut::from("=3\r\ntxt\r\n");
+        assert!(VerbatimString::decode(&mut buf).is_err());
+        Ok(())
+    }
+}
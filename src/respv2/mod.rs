@@ -1,5 +1,6 @@
 use crate::{RespError, RespFrame};
 use bytes::BytesMut;
+use winnow::error::ErrMode;
 
 mod parse;
 
@@ -14,8 +15,11 @@ pub trait RespDecodeV2: Sized {
 impl RespDecodeV2 for RespFrame {
     fn decode(buf: &mut bytes::BytesMut) -> Result<Self, crate::RespError> {
         let len = Self::expect_length(buf)?;
-        let data = buf.split_to(len);
-        parse_resp(&mut data.as_ref()).map_err(|e| RespError::InvalidFrame(e.to_string()))
+        if len > buf.len() {
+            return Err(RespError::NotComplete);
+        }
+        let data = buf.split_to(len).freeze();
+        parse_frame(&data).map_err(|e| RespError::InvalidFrame(e.to_string()))
     }
 
     fn expect_length(input: &[u8]) -> Result<usize, crate::RespError> {
@@ -28,6 +32,7 @@ impl RespDecodeV2 for RespFrame {
                 let len = stop - start;
                 Ok(len)
             }
+            Err(ErrMode::Cut(e)) => Err(RespError::Protocol(e.to_string())),
             Err(_) => Err(RespError::NotComplete),
         }
     }
@@ -35,7 +40,12 @@ impl RespDecodeV2 for RespFrame {
 
 #[cfg(test)]
 mod tests {
-    use crate::{BulkString, RespArray, RespNull, SimpleError, SimpleString};
+    use std::collections::BTreeMap;
+
+    use crate::{
+        BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespEncode, RespNull, RespPush,
+        RespSet, SimpleError, SimpleString, VerbatimString,
+    };
 
     use super::*;
 
@@ -136,4 +146,35 @@ mod tests {
             assert_eq!(excepted, result.unwrap());
         }
     }
+
+    #[test]
+    fn respv2_decode_resp3_frames_should_work() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert(SimpleString::new("ttl").into(), 10.into());
+        let frames: Vec<RespFrame> = vec![
+            RespSet::new([1.into(), SimpleString::new("a").into()]).into(),
+            RespPush::new([b"message".into(), b"channel".into()]).into(),
+            BigNumber::new("-3492890328409238509324850943850943825024385").into(),
+            VerbatimString::new(*b"txt", "Some string").into(),
+            BlobError::new("SYNTAX invalid").into(),
+            RespAttribute::new(attributes, RespSet::new([true.into()])).into(),
+        ];
+
+        let mut buf = BytesMut::new();
+        for frame in frames.clone() {
+            buf.extend_from_slice(&frame.encode());
+        }
+        for excepted in frames {
+            assert_eq!(excepted, RespFrame::decode(&mut buf).unwrap());
+        }
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::from("|1\r\n+ttl\r\n:10\r\n");
+        assert_eq!(
+            RespError::NotComplete,
+            RespFrame::decode(&mut buf).unwrap_err()
+        );
+        let mut buf = BytesMut::from("=5\r\ntxt\r\n\r\n");
+        assert!(RespFrame::decode(&mut buf).is_err());
+    }
 }
@@ -1,15 +1,33 @@
 use std::collections::BTreeMap;
 
-use crate::resp::Double;
-use crate::{BulkString, RespArray, RespFrame, RespNull, SimpleError, SimpleString};
+use bytes::Bytes;
+
+use crate::resp::{limits, Double};
+use crate::{
+    BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespFrame, RespNull, RespPush,
+    RespSet, SimpleError, SimpleString, VerbatimString,
+};
 use winnow::ascii::{crlf, dec_int, digit1, float};
-use winnow::combinator::{alt, dispatch, fail, terminated};
+use winnow::combinator::{alt, cut_err, dispatch, fail, opt, terminated};
+use winnow::error::StrContext;
+use winnow::stream::Stateful;
 use winnow::token::{any, take};
 use winnow::{token::take_until, PResult, Parser};
 
 const CRLF: &[u8] = b"\r\n";
 
-pub fn parse_resp(input: &mut &[u8]) -> PResult<RespFrame> {
+// the frames are parsed from a slice of the buffer kept in the state, so that
+// the bulk strings are handles to the buffer instead of copies of it
+type Input<'a> = Stateful<&'a [u8], &'a Bytes>;
+
+pub fn parse_frame(data: &Bytes) -> PResult<RespFrame> {
+    parse_resp(&mut Input {
+        input: data,
+        state: data,
+    })
+}
+
+fn parse_resp(input: &mut Input) -> PResult<RespFrame> {
     // match take(1usize).parse_next(input)? {
     //     b"+" => simple_string(input).map(RespFrame::SimpleString),
     //     b"-" => simple_error(input).map(RespFrame::Error),
@@ -26,6 +44,12 @@ pub fn parse_resp(input: &mut &[u8]) -> PResult<RespFrame> {
         b'#' => boolean.map(RespFrame::Boolean),
         b',' => double.map(RespFrame::Double),
         b'%' => map.map(RespFrame::Map),
+        b'~' => frames.map(|frames| RespFrame::Set(RespSet::new(frames))),
+        b'>' => frames.map(|frames| RespFrame::Push(RespPush::new(frames))),
+        b'(' => big_number.map(RespFrame::BigNumber),
+        b'=' => verbatim.map(RespFrame::Verbatim),
+        b'!' => blob.map(|data| RespFrame::BlobError(BlobError::new(data))),
+        b'|' => attribute.map(RespFrame::Attribute),
         _ => fail::<_, _, _>,
     }
     .parse_next(input)
@@ -40,67 +64,83 @@ pub fn parse_length(input: &mut &[u8]) -> PResult<()> {
         b':' => simple_parse,
         b'#' => simple_parse,
         b',' => simple_parse,
+        b'(' => simple_parse,
         b'*' => array_length,
+        b'~' => array_length,
+        b'>' => array_length,
         b'%' => map_length,
+        b'|' => (map_length, parse_length).value(()),
         b'$' => bulk_string_length,
-        _ => fail::<_, _, _>,
+        b'=' => bulk_string_length,
+        b'!' => bulk_string_length,
+        _ => cut_err(fail).context(StrContext::Label(limits::FRAME_TYPE)),
     }
     .parse_next(input)
 }
 
-fn simple_string(input: &mut &[u8]) -> PResult<SimpleString> {
+// a frame over the limits is invalid, it is not waiting for more data
+fn check<T>(input: &mut &[u8], ret: Result<T, &'static str>) -> PResult<T> {
+    match ret {
+        Ok(v) => Ok(v),
+        Err(label) => cut_err(fail)
+            .context(StrContext::Label(label))
+            .parse_next(input),
+    }
+}
+
+fn simple_string(input: &mut Input) -> PResult<SimpleString> {
     Ok(SimpleString::new(parse_string(input)?))
 }
 
-fn error(input: &mut &[u8]) -> PResult<SimpleError> {
+fn error(input: &mut Input) -> PResult<SimpleError> {
     Ok(SimpleError::new(parse_string(input)?))
 }
 
 // - boolean: "#t\r\n"
-fn boolean(input: &mut &[u8]) -> PResult<bool> {
-    let b = alt(('t', 'f')).parse_next(input)?;
+fn boolean(input: &mut Input) -> PResult<bool> {
+    let b = terminated(alt(('t', 'f')), crlf).parse_next(input)?;
     Ok(b == 't')
 }
 
 // - float: ",3.14\r\n"
-fn double(input: &mut &[u8]) -> PResult<Double> {
+fn double(input: &mut Input) -> PResult<Double> {
     terminated(float, CRLF).map(Double).parse_next(input)
 }
 
 // _\r\n
-fn null(input: &mut &[u8]) -> PResult<RespNull> {
+fn null(input: &mut Input) -> PResult<RespNull> {
     crlf(input)?;
     Ok(RespNull)
 }
 
 // :[<+|->]<value>\r\n
-fn integer(input: &mut &[u8]) -> PResult<i64> {
-    dec_int(input)
+fn integer(input: &mut Input) -> PResult<i64> {
+    terminated(dec_int, crlf).parse_next(input)
 }
 
 // $5\r\nhello\r\n
 // $0\r\n\r\n
 // $-1\r\n
-fn bulk_string(input: &mut &[u8]) -> PResult<Option<BulkString>> {
+fn bulk_string(input: &mut Input) -> PResult<Option<BulkString>> {
     let len: i64 = dec_int(input)?;
     crlf(input)?;
     if len == -1 {
         return Ok(None);
     }
-    Ok(Some(BulkString::new(parse_string(input)?)))
+    // the content is binary, its length is given by the prefix
+    let data = terminated(take(len as usize), crlf).parse_next(input)?;
+    Ok(Some(input.state.slice_ref(data).into()))
 }
 
 fn bulk_string_length(input: &mut &[u8]) -> PResult<()> {
     let len: i64 = dec_int(input)?;
     crlf(input)?;
     if len == -1 {
-    } else if len == 0 {
-        crlf(input)?;
-    } else {
-        take(len as usize).value(()).parse_next(input)?;
-        crlf(input)?;
+        return Ok(());
     }
-
+    let len = check(input, limits::bulk_len(len))?;
+    take(len).value(()).parse_next(input)?;
+    crlf(input)?;
     Ok(())
     // if len > -1 {
     //     crlf(input)?;
@@ -113,7 +153,7 @@ fn bulk_string_length(input: &mut &[u8]) -> PResult<()> {
 // *3\r\n$4\r\necho\r\n$5\r\nhello\r\n+OK\r\n
 // *0\r\n
 // *-1\r\n
-fn array(input: &mut &[u8]) -> PResult<Option<RespArray>> {
+fn array(input: &mut Input) -> PResult<Option<RespArray>> {
     let len: i64 = dec_int(input)?;
     crlf(input)?;
     if len == -1 {
@@ -130,17 +170,58 @@ fn array(input: &mut &[u8]) -> PResult<Option<RespArray>> {
 fn array_length(input: &mut &[u8]) -> PResult<()> {
     let len: i64 = dec_int(input)?;
     crlf(input)?;
-    if len > 0 {
-        for _ in 0..len {
-            parse_length(input)?
-        }
+    if len == -1 {
+        return Ok(());
+    }
+    let len = check(input, limits::elements(len))?;
+    let _guard = check(input, limits::DepthGuard::enter())?;
+    for _ in 0..len {
+        parse_length(input)?
     }
 
     Ok(())
 }
 
+// the elements of a set or a push, which are never null
+fn frames(input: &mut Input) -> PResult<Vec<RespFrame>> {
+    let len: u32 = digit1.parse_to().parse_next(input)?;
+    crlf(input)?;
+    let mut frames = Vec::new();
+    for _ in 0..len {
+        frames.push(parse_resp(input)?);
+    }
+    Ok(frames)
+}
+
+fn big_number(input: &mut Input) -> PResult<BigNumber> {
+    terminated((opt(alt(('+', '-'))), digit1).take(), crlf)
+        .map(|v| BigNumber::new(String::from_utf8_lossy(v)))
+        .parse_next(input)
+}
+
+// the data of a blob error or a verbatim string, binary safe as a bulk string
+fn blob(input: &mut Input) -> PResult<Vec<u8>> {
+    let len: u32 = digit1.parse_to().parse_next(input)?;
+    crlf(input)?;
+    terminated(take(len as usize), crlf)
+        .map(|v: &[u8]| v.to_vec())
+        .parse_next(input)
+}
+
+fn verbatim(input: &mut Input) -> PResult<VerbatimString> {
+    blob.verify(|data: &[u8]| data.len() >= 4 && data[3] == b':')
+        .map(|data| VerbatimString::new([data[0], data[1], data[2]], &data[4..]))
+        .parse_next(input)
+}
+
+fn attribute(input: &mut Input) -> PResult<RespAttribute> {
+    let attributes = map(input)?;
+    let data = parse_resp(input)?;
+    Ok(RespAttribute::new(attributes, data))
+}
+
 // "%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n"
-fn map(input: &mut &[u8]) -> PResult<BTreeMap<RespFrame, RespFrame>> {
+fn map(input: &mut Input) -> PResult<BTreeMap<RespFrame, RespFrame>> {
     let len: u32 = digit1.parse_to().parse_next(input)?;
     crlf(input)?;
     let mut map = BTreeMap::new();
@@ -153,8 +234,10 @@ fn map(input: &mut &[u8]) -> PResult<BTreeMap<RespFrame, RespFrame>> {
 }
 
 fn map_length(input: &mut &[u8]) -> PResult<()> {
-    let len: u32 = digit1.parse_to().parse_next(input)?;
+    let len: i64 = dec_int(input)?;
     crlf(input)?;
+    let len = check(input, limits::elements(len))?;
+    let _guard = check(input, limits::DepthGuard::enter())?;
     for _ in 0..len {
         parse_length(input)?;
         parse_length(input)?;
@@ -163,7 +246,7 @@ fn map_length(input: &mut &[u8]) -> PResult<()> {
     Ok(())
 }
 
-fn parse_string(input: &mut &[u8]) -> PResult<String> {
+fn parse_string(input: &mut Input) -> PResult<String> {
     terminated(take_until(0.., CRLF), CRLF)
         .map(|v| String::from_utf8_lossy(v).to_string())
         .parse_next(input)
@@ -176,21 +259,28 @@ mod tests {
     #[test]
     fn respv2_simple_string_should_work() {
         let s = b"+OK\r\n";
-        let resp = parse_resp(&mut s.as_ref()).unwrap();
+        let resp = parse_frame(&Bytes::from_static(s)).unwrap();
         assert_eq!(RespFrame::SimpleString(SimpleString::new("OK")), resp)
     }
 
     #[test]
     fn respv2_simple_string_should_fail() {
         let s = b"+OK\r";
-        let resp = parse_resp(&mut s.as_ref());
+        let resp = parse_frame(&Bytes::from_static(s));
         assert!(resp.is_err());
     }
 
+    #[test]
+    fn respv2_binary_bulk_string_should_work() {
+        let s = b"$5\r\na\r\n\x96b\r\n";
+        let resp = parse_frame(&Bytes::from_static(s)).unwrap();
+        assert_eq!(RespFrame::from(Some(BulkString::new(b"a\r\n\x96b"))), resp)
+    }
+
     #[test]
     fn respv2_map_should_work() {
         let s = b"%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n";
-        let resp = parse_resp(&mut s.as_ref());
+        let resp = parse_frame(&Bytes::from_static(s));
         let mut map = BTreeMap::new();
         map.insert(
             SimpleString::new("hello").into(),
@@ -204,7 +294,7 @@ mod tests {
         assert_eq!(RespFrame::Map(map), resp.unwrap());
 
         let s = b"%0\r\n";
-        let resp = parse_resp(&mut s.as_ref());
+        let resp = parse_frame(&Bytes::from_static(s));
         assert!(resp.is_ok());
         assert_eq!(RespFrame::Map(BTreeMap::new()), resp.unwrap())
     }
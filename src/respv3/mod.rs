@@ -1,4 +1,4 @@
-use crate::{RespError, RespFrame};
+use crate::{resp::limits, RespError, RespFrame};
 use bytes::BytesMut;
 
 mod parse;
@@ -6,7 +6,8 @@ mod parse;
 use parse::*;
 use winnow::{
     ascii::{crlf, dec_int},
-    combinator::terminated,
+    combinator::{cut_err, fail, terminated},
+    error::{ErrMode, StrContext},
     token::{take, take_until},
     PResult, Parser,
 };
@@ -22,13 +23,17 @@ pub trait RespDecodeV3: Sized {
 impl RespDecodeV3 for RespFrame {
     fn decode(buf: &mut bytes::BytesMut) -> Result<Self, crate::RespError> {
         let len = Self::expect_length(buf)?;
-        let data = buf.split_to(len);
-        parse_resp(&mut data.as_ref()).map_err(|e| RespError::InvalidFrame(e.to_string()))
+        if len > buf.len() {
+            return Err(RespError::NotComplete);
+        }
+        let data = buf.split_to(len).freeze();
+        parse_frame(&data).map_err(|e| RespError::InvalidFrame(e.to_string()))
     }
 
     fn expect_length(input: &[u8]) -> Result<usize, RespError> {
         match expect_length_inner(input) {
             Ok(v) => Ok(v.1),
+            Err(ErrMode::Cut(e)) => Err(RespError::Protocol(e.to_string())),
             Err(_) => Err(RespError::NotComplete),
         }
     }
@@ -42,16 +47,36 @@ fn expect_length_inner(input: &[u8]) -> PResult<(&[u8], usize)> {
         (i, b":") => cal_util_crlf(i)?,
         (i, b"#") => cal_util_crlf(i)?,
         (i, b",") => cal_util_crlf(i)?,
+        (i, b"(") => cal_util_crlf(i)?,
         (i, b"$") => cal_by_len(i)?,
+        (i, b"=") => cal_by_len(i)?,
+        (i, b"!") => cal_by_len(i)?,
         (i, b"*") => cal_array(i)?,
+        (i, b"~") => cal_array(i)?,
+        (i, b">") => cal_array(i)?,
         (i, b"%") => cal_map(i)?,
-        _ => {
-            unreachable!()
+        (i, b"|") => {
+            // the attributes are followed by the reply
+            let (remain, len) = cal_map(i)?;
+            let (remain, size) = expect_length_inner(remain)?;
+            (remain, len + size)
         }
+        (i, _) => check(i, Err(limits::FRAME_TYPE))?,
     };
     Ok((remain, len + 1))
 }
 
+// a frame over the limits is invalid, it is not waiting for more data
+fn check<T>(input: &[u8], ret: Result<T, &'static str>) -> PResult<T> {
+    match ret {
+        Ok(v) => Ok(v),
+        Err(label) => cut_err(fail)
+            .context(StrContext::Label(label))
+            .parse_peek(input)
+            .map(|(_, v)| v),
+    }
+}
+
 // \r\n
 #[inline]
 fn cal_crlf(input: &[u8]) -> PResult<(&[u8], usize)> {
@@ -66,26 +91,23 @@ fn cal_util_crlf(input: &[u8]) -> PResult<(&[u8], usize)> {
     Ok((remain, found.len() + 2))
 }
 
+// the length of a bulk or an aggregate frame, with the number of bytes of its digits
 #[inline]
-fn cal_by_len(input: &[u8]) -> PResult<(&[u8], usize)> {
+fn cal_len(input: &[u8]) -> PResult<(&[u8], i64, usize)> {
     let (remain, len): (&[u8], i64) = dec_int.parse_peek(input)?;
+    Ok((remain, len, input.len() - remain.len()))
+}
 
+#[inline]
+fn cal_by_len(input: &[u8]) -> PResult<(&[u8], usize)> {
+    let (remain, len, digits) = cal_len(input)?;
     if len == -1 {
-        let mut total = 2;
         let (remain, size) = cal_crlf(remain)?;
-        total += size;
-        return Ok((remain, total));
-    } else if len == 0 {
-        let mut total = 1;
-        let (remain, size) = cal_crlf(remain)?;
-        total += size;
-        let (remain, size) = cal_crlf(remain)?;
-        total += size;
-        return Ok((remain, total));
+        return Ok((remain, digits + size));
     }
 
-    let len = len as usize;
-    let mut total = len / 10 + 1;
+    let len = check(remain, limits::bulk_len(len))?;
+    let mut total = digits;
     let (remain, size) = cal_crlf(remain)?;
     total += size;
     let (remain, found) = take(len).parse_peek(remain)?;
@@ -97,17 +119,15 @@ fn cal_by_len(input: &[u8]) -> PResult<(&[u8], usize)> {
 
 #[inline]
 fn cal_array(input: &[u8]) -> PResult<(&[u8], usize)> {
-    let (remain, len): (&[u8], i64) = dec_int.parse_peek(input.as_ref())?;
-    if len == 0 {
-        let (remain, size) = cal_crlf(remain)?;
-        return Ok((remain, size + 1));
-    } else if len == -1 {
+    let (remain, len, digits) = cal_len(input)?;
+    if len == -1 {
         let (remain, size) = cal_crlf(remain)?;
-        return Ok((remain, size + 2));
+        return Ok((remain, digits + size));
     }
 
-    let len = len as usize;
-    let mut total = len / 10 + 1;
+    let len = check(remain, limits::elements(len))?;
+    let _guard = check(remain, limits::DepthGuard::enter())?;
+    let mut total = digits;
     let (mut r1, size) = cal_crlf(remain)?;
     total += size;
 
@@ -124,17 +144,10 @@ fn cal_array(input: &[u8]) -> PResult<(&[u8], usize)> {
 fn cal_map(input: &[u8]) -> PResult<(&[u8], usize)> {
     // 2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n
 
-    let (remain, len): (&[u8], i64) = dec_int.parse_peek(input.as_ref())?;
-    if len == 0 {
-        let (remain, size) = cal_crlf(remain)?;
-        return Ok((remain, size + 1));
-    } else if len == -1 {
-        let (remain, size) = cal_crlf(remain)?;
-        return Ok((remain, size + 2));
-    }
-
-    let len = len as usize;
-    let mut total = len / 10 + 1;
+    let (remain, len, digits) = cal_len(input)?;
+    let len = check(remain, limits::elements(len))?;
+    let _guard = check(remain, limits::DepthGuard::enter())?;
+    let mut total = digits;
     let (mut r1, size) = cal_crlf(remain)?;
     total += size;
 
@@ -152,7 +165,12 @@ fn cal_map(input: &[u8]) -> PResult<(&[u8], usize)> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{BulkString, RespArray, RespNull, SimpleError, SimpleString};
+    use std::collections::BTreeMap;
+
+    use crate::{
+        BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespEncode, RespNull, RespPush,
+        RespSet, SimpleError, SimpleString, VerbatimString,
+    };
 
     use super::*;
 
@@ -214,11 +232,19 @@ mod tests {
 
     #[test]
     fn respv3_decode_bulk_string_should_work() {
-        let test_cases = ["$5\r\nhello\r\n", "$0\r\n\r\n", "$-1\r\n"];
+        let test_cases: [&[u8]; 5] = [
+            b"$5\r\nhello\r\n",
+            b"$0\r\n\r\n",
+            b"$-1\r\n",
+            b"$5\r\na\r\n\x96b\r\n",
+            b"$12\r\nhello\r\nworld\r\n",
+        ];
         let test_expecteds = [
             RespFrame::BulkString(Some(BulkString::new("hello"))),
             RespFrame::BulkString(Some(BulkString::new(""))),
             RespFrame::BulkString(None),
+            RespFrame::BulkString(Some(BulkString::new(b"a\r\n\x96b"))),
+            RespFrame::BulkString(Some(BulkString::new("hello\r\nworld"))),
         ];
 
         for (&test, excepted) in test_cases.iter().zip(test_expecteds) {
@@ -253,4 +279,35 @@ mod tests {
             assert_eq!(excepted, result.unwrap());
         }
     }
+
+    #[test]
+    fn respv3_decode_resp3_frames_should_work() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert(SimpleString::new("ttl").into(), 10.into());
+        let frames: Vec<RespFrame> = vec![
+            RespSet::new([1.into(), SimpleString::new("a").into()]).into(),
+            RespPush::new([b"message".into(), b"channel".into()]).into(),
+            BigNumber::new("-3492890328409238509324850943850943825024385").into(),
+            VerbatimString::new(*b"txt", "Some string").into(),
+            BlobError::new("SYNTAX invalid").into(),
+            RespAttribute::new(attributes, RespSet::new([true.into()])).into(),
+        ];
+
+        let mut buf = BytesMut::new();
+        for frame in frames.clone() {
+            buf.extend_from_slice(&frame.encode());
+        }
+        for excepted in frames {
+            assert_eq!(excepted, RespFrame::decode(&mut buf).unwrap());
+        }
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::from("|1\r\n+ttl\r\n:10\r\n");
+        assert_eq!(
+            RespError::NotComplete,
+            RespFrame::decode(&mut buf).unwrap_err()
+        );
+        let mut buf = BytesMut::from("=5\r\ntxt\r\n\r\n");
+        assert!(RespFrame::decode(&mut buf).is_err());
+    }
 }
@@ -0,0 +1,129 @@
+// An inline command is a line of arguments separated by spaces, as typed in
+// telnet or netcat: `hset key field "a value\n"`. An argument is quoted as in
+// `sdssplitargs` of Redis:
+// - in double quotes, "\xHH" is a byte in hex, "\n", "\r", "\t", "\b", "\a"
+//   are control characters and "\<c>" is c
+// - in single quotes, "\'" is a quote and the other characters are as they are
+// - a closing quote must be followed by a space or the end of the line
+
+/// Splits an inline command into its arguments, `None` on unbalanced quotes.
+pub(crate) fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut p = 0;
+    loop {
+        while p < line.len() && is_space(line[p]) {
+            p += 1;
+        }
+        if p == line.len() {
+            return Some(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut quote = None;
+        loop {
+            let c = line.get(p).copied();
+            let next = line.get(p + 1).copied();
+            match (quote, c) {
+                // unterminated quotes
+                (Some(_), None) => return None,
+                (Some(b'"'), Some(b'\\')) if next == Some(b'x') && hex(line, p + 2).is_some() => {
+                    arg.push(hex(line, p + 2)?);
+                    p += 3;
+                }
+                (Some(b'"'), Some(b'\\')) if next.is_some() => {
+                    arg.push(match next? {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        c => c,
+                    });
+                    p += 1;
+                }
+                (Some(b'\''), Some(b'\\')) if next == Some(b'\'') => {
+                    arg.push(b'\'');
+                    p += 1;
+                }
+                (Some(q), Some(c)) if c == q => {
+                    if next.is_some_and(|c| !is_space(c)) {
+                        return None;
+                    }
+                    p += 1;
+                    break;
+                }
+                (Some(_), Some(c)) => arg.push(c),
+                (None, None | Some(b' ' | b'\n' | b'\r' | b'\t')) => break,
+                (None, Some(c @ (b'"' | b'\''))) => quote = Some(c),
+                (None, Some(c)) => arg.push(c),
+            }
+            p += 1;
+        }
+        args.push(arg);
+    }
+}
+
+// the spaces of isspace() in C
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c)
+}
+
+// the byte of the 2 hex digits at the position
+fn hex(line: &[u8], p: usize) -> Option<u8> {
+    let digits = line.get(p..p + 2)?;
+    let digits = std::str::from_utf8(digits).ok()?;
+    if !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Option<Vec<String>> {
+        let args = split_args(line.as_bytes())?;
+        Some(
+            args.into_iter()
+                .map(|arg| String::from_utf8_lossy(&arg).to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(args(""), Some(vec![]));
+        assert_eq!(args(" \t "), Some(vec![]));
+        assert_eq!(
+            args("  hset key  field\tvalue "),
+            Some(vec![
+                "hset".into(),
+                "key".into(),
+                "field".into(),
+                "value".into()
+            ])
+        );
+        assert_eq!(
+            args(r#"echo "hello world" 'it''s'"#),
+            None,
+            "a closing quote must be followed by a space"
+        );
+        assert_eq!(
+            args(r#"echo "a\"b\n\x41\x4g" 'it\'s \n' """#),
+            Some(vec![
+                "echo".into(),
+                "a\"b\nAx4g".into(),
+                "it's \\n".into(),
+                "".into()
+            ])
+        );
+        assert_eq!(
+            args(r#"echo ab"cd ef""#),
+            Some(vec!["echo".into(), "abcd ef".into()])
+        );
+        assert_eq!(args(r#"echo "unbalanced"#), None);
+        assert_eq!(args(r#"echo 'unbalanced"#), None);
+        assert_eq!(args(r#"echo "trailing\"#), None);
+    }
+}
@@ -0,0 +1,543 @@
+mod inline;
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::resp::{limits, protocol_error, Double};
+use crate::{
+    BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespError, RespFrame, RespNull,
+    RespPush, RespSet, SimpleError, SimpleString, VerbatimString,
+};
+
+pub(crate) use inline::split_args;
+
+const CRLF: &[u8] = b"\r\n";
+
+// the longest inline command, as the query buffer of a Redis client
+const INLINE_MAX_SIZE: usize = 64 * 1024;
+
+/// A decoder keeping the part of a frame decoded so far between calls, so
+/// that a frame split into many reads is decoded in a single pass instead of
+/// from its first byte on every read.
+///
+/// The bytes of an incomplete frame are consumed from the buffer, the same
+/// decoder must be called again with the rest of them.
+#[derive(Debug, Default)]
+pub struct RespDecoderV4 {
+    // the aggregate frames waiting for their elements, the innermost last
+    stack: Vec<Aggregate>,
+    // the prefix and the length of the bulk frame whose data is awaited
+    bulk: Option<(u8, usize)>,
+    // the bytes of the buffer already searched for the end of the line
+    scanned: usize,
+    // a request which is not an array is an inline command
+    inline: bool,
+}
+
+#[derive(Debug)]
+struct Aggregate {
+    prefix: u8,
+    // the frames of the aggregate, 2 for each entry of a map
+    len: usize,
+    frames: Vec<RespFrame>,
+}
+
+impl RespDecoderV4 {
+    /// A decoder of the requests of clients, which send arrays of bulk strings
+    /// or inline commands as typed in telnet: `hset key field "a value"`.
+    pub fn for_requests() -> Self {
+        RespDecoderV4 {
+            inline: true,
+            ..Default::default()
+        }
+    }
+
+    /// Decodes the next frame of the buffer, `None` until all of its bytes
+    /// are received. The decoder is reset on error.
+    pub fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, RespError> {
+        let ret = loop {
+            match self.decode_frame(buf) {
+                // as Redis, the null and empty requests are skipped
+                Ok(Some(RespFrame::Array(None))) if self.inline => continue,
+                Ok(Some(RespFrame::Array(Some(args)))) if self.inline && args.is_empty() => {
+                    continue
+                }
+                ret => break ret,
+            }
+        };
+        if ret.is_err() {
+            *self = RespDecoderV4 {
+                inline: self.inline,
+                ..Default::default()
+            };
+        }
+        ret
+    }
+
+    /// Whether a frame is partially decoded.
+    pub fn is_pending(&self) -> bool {
+        !self.stack.is_empty() || self.bulk.is_some() || self.scanned > 0
+    }
+
+    fn decode_frame(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, RespError> {
+        while self.inline && self.stack.is_empty() && self.bulk.is_none() {
+            match buf.first() {
+                None => return Ok(None),
+                Some(b'*') => break,
+                Some(_) => match self.inline_command(buf)? {
+                    // an empty line is skipped
+                    Some(args) if args.is_empty() => continue,
+                    Some(args) => {
+                        let args = args
+                            .into_iter()
+                            .map(|arg| Some(BulkString::new(arg)).into())
+                            .collect::<Vec<RespFrame>>();
+                        return Ok(Some(Some(RespArray::new(args)).into()));
+                    }
+                    None => return Ok(None),
+                },
+            }
+        }
+
+        loop {
+            let frame = match self.bulk {
+                Some((prefix, len)) => match bulk(buf, prefix, len)? {
+                    Some(frame) => {
+                        self.bulk = None;
+                        frame
+                    }
+                    None => return Ok(None),
+                },
+                None => {
+                    let Some(line) = self.line(buf)? else {
+                        return Ok(None);
+                    };
+                    match self.header(&line)? {
+                        Some(frame) => frame,
+                        None => continue,
+                    }
+                }
+            };
+
+            if let Some(frame) = self.push(frame) {
+                return Ok(Some(frame));
+            }
+        }
+    }
+
+    // the next line of the buffer without its CRLF, only the bytes received
+    // since the last call are searched
+    fn line(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, RespError> {
+        let start = self.scanned.min(buf.len()).saturating_sub(1);
+        match buf[start..].windows(CRLF.len()).position(|w| w == CRLF) {
+            Some(pos) => {
+                let line = buf.split_to(start + pos);
+                buf.advance(CRLF.len());
+                self.scanned = 0;
+                Ok(Some(line))
+            }
+            // as Redis, a header is not longer than an inline command, the
+            // simple strings and errors of the replies are not limited
+            None if buf.len() > INLINE_MAX_SIZE
+                && (self.inline || !matches!(buf[0], b'+' | b'-')) =>
+            {
+                Err(too_big_line(buf[0]))
+            }
+            None => {
+                self.scanned = buf.len();
+                Ok(None)
+            }
+        }
+    }
+
+    // the arguments of an inline command, ended by a newline with or without a CR
+    fn inline_command(&mut self, buf: &mut BytesMut) -> Result<Option<Vec<Vec<u8>>>, RespError> {
+        let start = self.scanned.min(buf.len());
+        let Some(pos) = buf[start..].iter().position(|&c| c == b'\n') else {
+            if buf.len() > INLINE_MAX_SIZE {
+                return Err(RespError::Protocol("too big inline request".to_string()));
+            }
+            self.scanned = buf.len();
+            return Ok(None);
+        };
+
+        let line = buf.split_to(start + pos + 1);
+        self.scanned = 0;
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let args = inline::split_args(line)
+            .ok_or_else(|| RespError::Protocol("unbalanced quotes in request".to_string()))?;
+        Ok(Some(args))
+    }
+
+    // the frame of a line, or none when the frame goes on after the line
+    fn header(&mut self, line: &[u8]) -> Result<Option<RespFrame>, RespError> {
+        let Some((&prefix, data)) = line.split_first() else {
+            return Err(protocol_error(limits::FRAME_TYPE));
+        };
+
+        // the arguments of a request are bulk strings
+        if self.inline && !self.stack.is_empty() {
+            match prefix {
+                b'$' if data == b"-1" => return Err(protocol_error(limits::BULK_LENGTH)),
+                b'$' => {}
+                _ => {
+                    return Err(RespError::Protocol(format!(
+                        "expected '$', got '{}'",
+                        prefix as char
+                    )))
+                }
+            }
+        }
+
+        let frame = match prefix {
+            b'+' => SimpleString::new(String::from_utf8_lossy(data)).into(),
+            b'-' => SimpleError::new(String::from_utf8_lossy(data)).into(),
+            b':' => RespFrame::Integer(number(line, data)?),
+            b'_' if data.is_empty() => RespNull.into(),
+            b'#' if data == b"t" => true.into(),
+            b'#' if data == b"f" => false.into(),
+            b',' => Double(number(line, data)?).into(),
+            b'(' if is_big_number(data) => BigNumber::new(String::from_utf8_lossy(data)).into(),
+            b'$' if data == b"-1" => RespFrame::BulkString(None),
+            b'*' if data == b"-1" => RespFrame::Array(None),
+            b'$' | b'=' | b'!' => {
+                let len = length(data, limits::BULK_LENGTH).and_then(limits::bulk_len);
+                self.bulk = Some((prefix, len.map_err(protocol_error)?));
+                return Ok(None);
+            }
+            b'*' | b'~' | b'>' | b'%' | b'|' => {
+                let len = length(data, limits::MULTIBULK_LENGTH).and_then(limits::elements);
+                let len = match (prefix, len.map_err(protocol_error)?) {
+                    (b'%', len) => len * 2,
+                    (b'|', len) => len * 2 + 1,
+                    (_, len) => len,
+                };
+                let aggregate = Aggregate {
+                    prefix,
+                    len,
+                    frames: Vec::with_capacity(len.min(1024)),
+                };
+                if len == 0 {
+                    aggregate.into_frame()
+                } else {
+                    limits::depth(self.stack.len() + 1).map_err(protocol_error)?;
+                    self.stack.push(aggregate);
+                    return Ok(None);
+                }
+            }
+            b'_' | b'#' | b'(' => return Err(invalid(line)),
+            _ => return Err(protocol_error(limits::FRAME_TYPE)),
+        };
+        Ok(Some(frame))
+    }
+
+    // adds a frame to the aggregate it belongs to, the aggregates it completes
+    // are added to theirs in turn, a frame of the top level is returned
+    fn push(&mut self, mut frame: RespFrame) -> Option<RespFrame> {
+        while let Some(aggregate) = self.stack.last_mut() {
+            aggregate.frames.push(frame);
+            if aggregate.frames.len() < aggregate.len {
+                return None;
+            }
+            frame = self.stack.pop()?.into_frame();
+        }
+        Some(frame)
+    }
+}
+
+impl Aggregate {
+    fn into_frame(mut self) -> RespFrame {
+        match self.prefix {
+            b'*' => Some(RespArray::new(self.frames)).into(),
+            b'~' => RespSet::new(self.frames).into(),
+            b'>' => RespPush::new(self.frames).into(),
+            b'%' => entries(self.frames).into(),
+            _ => {
+                // the attributes are followed by the reply they are attached to
+                let data = self.frames.pop().unwrap_or(RespFrame::BulkString(None));
+                RespAttribute::new(entries(self.frames), data).into()
+            }
+        }
+    }
+}
+
+// the data of a bulk string, a blob error or a verbatim string, sliced out of the buffer
+fn bulk(buf: &mut BytesMut, prefix: u8, len: usize) -> Result<Option<RespFrame>, RespError> {
+    if buf.len() < len + CRLF.len() {
+        // a large value is received into a single allocation
+        buf.reserve(len + CRLF.len() - buf.len());
+        return Ok(None);
+    }
+    if &buf[len..len + CRLF.len()] != CRLF {
+        return Err(RespError::InvalidFrame(format!(
+            "expect CRLF after the {} bytes of data",
+            len
+        )));
+    }
+
+    let data = buf.split_to(len).freeze();
+    buf.advance(CRLF.len());
+    let frame = match prefix {
+        b'$' => Some(BulkString::from(data)).into(),
+        b'!' => BlobError::new(data).into(),
+        _ => verbatim(data)?.into(),
+    };
+    Ok(Some(frame))
+}
+
+// "=15\r\ntxt:Some string\r\n", the format is followed by a colon
+fn verbatim(data: Bytes) -> Result<VerbatimString, RespError> {
+    match &data[..] {
+        [a, b, c, b':', s @ ..] => Ok(VerbatimString::new([*a, *b, *c], s)),
+        _ => Err(RespError::InvalidFrame(format!(
+            "expect a verbatim string, got: {:?}",
+            data
+        ))),
+    }
+}
+
+fn entries(frames: Vec<RespFrame>) -> BTreeMap<RespFrame, RespFrame> {
+    let mut frames = frames.into_iter();
+    let mut map = BTreeMap::new();
+    while let (Some(key), Some(value)) = (frames.next(), frames.next()) {
+        map.insert(key, value);
+    }
+    map
+}
+
+fn number<T: FromStr>(line: &[u8], data: &[u8]) -> Result<T, RespError> {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid(line))
+}
+
+fn length(data: &[u8], label: &'static str) -> Result<i64, &'static str> {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(label)
+}
+
+fn is_big_number(data: &[u8]) -> bool {
+    let digits = data.strip_prefix(b"-").or(data.strip_prefix(b"+"));
+    let digits = digits.unwrap_or(data);
+    !digits.is_empty() && digits.iter().all(u8::is_ascii_digit)
+}
+
+fn too_big_line(prefix: u8) -> RespError {
+    let line = match prefix {
+        b'$' | b'=' | b'!' => "bulk count string",
+        b'*' | b'~' | b'>' | b'%' | b'|' => "mbulk count string",
+        _ => "line",
+    };
+    RespError::Protocol(format!("too big {}", line))
+}
+
+fn invalid(line: &[u8]) -> RespError {
+    RespError::InvalidFrame(format!("invalid line: {:?}", String::from_utf8_lossy(line)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespEncode;
+
+    fn decode_all(data: &[u8], chunk: usize) -> Result<Vec<RespFrame>, RespError> {
+        let mut decoder = RespDecoderV4::default();
+        let mut buf = BytesMut::new();
+        let mut frames = Vec::new();
+        for chunk in data.chunks(chunk) {
+            buf.extend_from_slice(chunk);
+            while let Some(frame) = decoder.decode(&mut buf)? {
+                frames.push(frame);
+            }
+        }
+        assert!(!decoder.is_pending());
+        Ok(frames)
+    }
+
+    #[test]
+    fn respv4_decode_frames_should_work() {
+        let frames: Vec<RespFrame> = vec![
+            SimpleString::new("OK").into(),
+            SimpleError::new("ERR unknown command").into(),
+            RespFrame::Integer(-123),
+            RespNull.into(),
+            true.into(),
+            Double(1.5).into(),
+            BigNumber::new("-3492890328409238509324850943850943825024385").into(),
+            Some(BulkString::new(b"a\r\n\x96b")).into(),
+            RespFrame::BulkString(None),
+            Some(BulkString::new("")).into(),
+            RespFrame::Array(None),
+            Some(RespArray::new(vec![])).into(),
+            BlobError::new("SYNTAX invalid syntax").into(),
+            VerbatimString::new(*b"txt", "Some string").into(),
+            Some(RespArray::new(vec![
+                Some(RespArray::new(vec![RespFrame::Integer(1)])).into(),
+                RespSet::new(vec![SimpleString::new("a").into()]).into(),
+            ]))
+            .into(),
+            RespPush::new(vec![SimpleString::new("message").into()]).into(),
+            BTreeMap::from([(
+                SimpleString::new("first").into(),
+                RespFrame::Double(Double(f64::NAN)),
+            )])
+            .into(),
+            RespAttribute::new(
+                BTreeMap::from([(SimpleString::new("ttl").into(), RespFrame::Integer(3600))]),
+                Some(RespArray::new(vec![RespFrame::Integer(1)])),
+            )
+            .into(),
+        ];
+        let data = frames
+            .iter()
+            .flat_map(|frame| frame.clone().encode())
+            .collect::<Vec<_>>();
+
+        for chunk in [1, 2, 3, 7, data.len()] {
+            assert_eq!(decode_all(&data, chunk), Ok(frames.clone()));
+        }
+    }
+
+    #[test]
+    fn respv4_decode_incomplete_frame_should_resume() {
+        let mut decoder = RespDecoderV4::default();
+        let mut buf = BytesMut::from("*2\r\n$5\r\nhel");
+        assert_eq!(decoder.decode(&mut buf), Ok(None));
+        assert!(decoder.is_pending());
+        // the consumed bytes are not decoded again
+        assert_eq!(&buf[..], b"hel");
+
+        buf.extend_from_slice(b"lo\r\n:1");
+        assert_eq!(decoder.decode(&mut buf), Ok(None));
+        assert_eq!(&buf[..], b":1");
+
+        buf.extend_from_slice(b"\r\n+OK\r\n");
+        let frame = decoder.decode(&mut buf);
+        assert_eq!(
+            frame,
+            Ok(Some(
+                Some(RespArray::new(vec![
+                    Some(BulkString::new("hello")).into(),
+                    RespFrame::Integer(1),
+                ]))
+                .into()
+            ))
+        );
+        assert_eq!(
+            decoder.decode(&mut buf),
+            Ok(Some(SimpleString::new("OK").into()))
+        );
+        assert!(!decoder.is_pending());
+    }
+
+    #[test]
+    fn respv4_decode_invalid_frame_should_fail() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"x\r\n", "Protocol error: invalid frame type"),
+            (b"$-2\r\n", "Protocol error: invalid bulk length"),
+            (b"$abc\r\n", "Protocol error: invalid bulk length"),
+            (b"%-1\r\n", "Protocol error: invalid multibulk length"),
+            (b"$536870913\r\n", "Protocol error: invalid bulk length"),
+            (b":1x\r\n", "Invalid frame: invalid line: \":1x\""),
+            (b"#x\r\n", "Invalid frame: invalid line: \"#x\""),
+            (
+                b"$2\r\nabc\r\n",
+                "Invalid frame: expect CRLF after the 2 bytes of data",
+            ),
+        ];
+        for (data, err) in cases {
+            let mut decoder = RespDecoderV4::default();
+            let ret = decoder.decode(&mut BytesMut::from(*data));
+            assert_eq!(ret.unwrap_err().to_string(), *err);
+            assert!(!decoder.is_pending());
+        }
+
+        let too_deep = [b"*1\r\n".repeat(129), b":1\r\n".to_vec()].concat();
+        let ret = RespDecoderV4::default().decode(&mut BytesMut::from(&too_deep[..]));
+        assert_eq!(ret, Err(protocol_error(limits::NESTING_DEPTH)));
+    }
+
+    #[test]
+    fn respv4_decode_inline_command_should_work() {
+        let command = |args: &[&str]| -> RespFrame {
+            let args = args.iter().map(|&arg| Some(BulkString::new(arg)).into());
+            Some(RespArray::new(args.collect::<Vec<RespFrame>>())).into()
+        };
+
+        let mut decoder = RespDecoderV4::for_requests();
+        let mut buf = BytesMut::from("echo \"hello world\"\r\n\r\n  \nhset k f 'v'");
+        assert_eq!(
+            decoder.decode(&mut buf),
+            Ok(Some(command(&["echo", "hello world"])))
+        );
+        // the empty lines are skipped, the last line is not complete
+        assert_eq!(decoder.decode(&mut buf), Ok(None));
+        assert!(decoder.is_pending());
+
+        buf.extend_from_slice(b"\n*2\r\n$4\r\necho\r\n$2\r\nhi\r\n");
+        assert_eq!(
+            decoder.decode(&mut buf),
+            Ok(Some(command(&["hset", "k", "f", "v"])))
+        );
+        assert_eq!(decoder.decode(&mut buf), Ok(Some(command(&["echo", "hi"]))));
+
+        let mut buf = BytesMut::from("echo \"unbalanced\r\n");
+        let err = decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Protocol error: unbalanced quotes in request"
+        );
+
+        let mut buf = BytesMut::from(&[b'a'; INLINE_MAX_SIZE + 1][..]);
+        let err = decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(err.to_string(), "Protocol error: too big inline request");
+
+        // the arguments are bulk strings, the null and empty requests are skipped
+        let mut buf = BytesMut::from("*-1\r\n*0\r\necho hi\r\n*2\r\n$4\r\necho\r\n:1\r\n");
+        assert_eq!(decoder.decode(&mut buf), Ok(Some(command(&["echo", "hi"]))));
+        let err = decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(err.to_string(), "Protocol error: expected '$', got ':'");
+        let mut buf = BytesMut::from("*1\r\n$-1\r\n");
+        let err = decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+
+        // the replies of a server are not inline commands
+        let mut buf = BytesMut::from("+OK\r\n");
+        let frame = RespDecoderV4::default().decode(&mut buf);
+        assert_eq!(frame, Ok(Some(SimpleString::new("OK").into())));
+    }
+
+    #[test]
+    fn respv4_decode_too_big_line_should_fail() {
+        let cases: [(&[u8], &str); 3] = [
+            (b"*", "Protocol error: too big mbulk count string"),
+            (b"*1\r\n$", "Protocol error: too big bulk count string"),
+            (b"*1\r\n:", "Protocol error: too big line"),
+        ];
+        for (header, err) in cases {
+            let mut decoder = RespDecoderV4::for_requests();
+            let mut buf = BytesMut::from(header);
+            buf.extend_from_slice(&[b'1'; INLINE_MAX_SIZE / 2]);
+            assert_eq!(decoder.decode(&mut buf), Ok(None));
+            buf.extend_from_slice(&[b'1'; INLINE_MAX_SIZE / 2 + 1]);
+            let ret = decoder.decode(&mut buf);
+            assert_eq!(ret.unwrap_err().to_string(), err);
+            assert!(!decoder.is_pending());
+        }
+
+        // a long simple string of a reply is waited for
+        let mut decoder = RespDecoderV4::default();
+        let mut buf = BytesMut::from(&b"+"[..]);
+        buf.extend_from_slice(&[b'a'; INLINE_MAX_SIZE + 1]);
+        assert_eq!(decoder.decode(&mut buf), Ok(None));
+        buf.extend_from_slice(CRLF);
+        let frame = decoder.decode(&mut buf);
+        let data = String::from_utf8(vec![b'a'; INLINE_MAX_SIZE + 1]).unwrap();
+        assert_eq!(frame, Ok(Some(SimpleString::new(data).into())));
+    }
+}
@@ -0,0 +1,160 @@
+use mlua::{Lua, Result as LuaResult, Table, Value};
+
+use crate::{BulkString, RespArray, RespFrame, RespPush, SimpleError, SimpleString};
+
+// Conversion rules follow https://redis.io/docs/interact/programmability/lua-api/
+
+/// Converts a command reply into the Lua value returned by `redis.call`.
+pub(crate) fn resp_to_lua<'lua>(lua: &'lua Lua, frame: RespFrame) -> LuaResult<Value<'lua>> {
+    let value = match frame {
+        RespFrame::Integer(n) => Value::Integer(n),
+        RespFrame::BulkString(Some(s)) => Value::String(lua.create_string(s.as_ref())?),
+        RespFrame::BulkString(None) | RespFrame::Array(None) | RespFrame::Null(_) => {
+            Value::Boolean(false)
+        }
+        RespFrame::Array(Some(RespArray(frames))) | RespFrame::Push(RespPush(frames)) => {
+            let table = lua.create_table_with_capacity(frames.len(), 0)?;
+            for (i, frame) in frames.into_iter().enumerate() {
+                table.raw_set(i + 1, resp_to_lua(lua, frame)?)?;
+            }
+            Value::Table(table)
+        }
+        RespFrame::SimpleString(s) => Value::Table(single_field_table(lua, "ok", &s)?),
+        RespFrame::Error(e) => Value::Table(single_field_table(lua, "err", &e)?),
+        RespFrame::Boolean(b) => Value::Boolean(b),
+        RespFrame::Double(d) => {
+            let table = lua.create_table()?;
+            table.raw_set("double", d.0)?;
+            Value::Table(table)
+        }
+        RespFrame::Map(map) => {
+            let inner = lua.create_table()?;
+            for (k, v) in map {
+                inner.raw_set(resp_to_lua(lua, k)?, resp_to_lua(lua, v)?)?;
+            }
+            let table = lua.create_table()?;
+            table.raw_set("map", inner)?;
+            Value::Table(table)
+        }
+        RespFrame::Set(set) => {
+            let inner = lua.create_table()?;
+            for member in set.0 {
+                inner.raw_set(resp_to_lua(lua, member)?, true)?;
+            }
+            let table = lua.create_table()?;
+            table.raw_set("set", inner)?;
+            Value::Table(table)
+        }
+        RespFrame::BigNumber(n) => Value::Table(single_field_table(lua, "big_number", &n.0)?),
+        RespFrame::Verbatim(s) => {
+            let inner = lua.create_table()?;
+            inner.raw_set("format", lua.create_string(s.format())?)?;
+            inner.raw_set("string", lua.create_string(s.data())?)?;
+            let table = lua.create_table()?;
+            table.raw_set("verbatim_string", inner)?;
+            Value::Table(table)
+        }
+        RespFrame::BlobError(e) => {
+            let table = lua.create_table()?;
+            table.raw_set("err", lua.create_string(&e.0)?)?;
+            Value::Table(table)
+        }
+        // the attributes are not visible to scripts
+        RespFrame::Attribute(attribute) => resp_to_lua(lua, attribute.into_data())?,
+    };
+    Ok(value)
+}
+
+/// Converts the value returned by a script into the reply sent to the client.
+pub(crate) fn lua_to_resp(value: Value) -> RespFrame {
+    match value {
+        Value::Integer(n) => n.into(),
+        // Lua numbers are truncated to integers, as Redis does
+        Value::Number(n) => (n as i64).into(),
+        Value::String(s) => Some(BulkString::new(s.as_bytes().to_vec())).into(),
+        Value::Boolean(true) => 1.into(),
+        Value::Table(table) => table_to_resp(table),
+        _ => RespFrame::BulkString(None),
+    }
+}
+
+fn table_to_resp(table: Table) -> RespFrame {
+    // as Redis, the newlines of the replies become spaces
+    if let Ok(Value::String(err)) = table.raw_get::<_, Value>("err") {
+        return SimpleError::new(err.to_string_lossy().replace(['\r', '\n'], " ")).into();
+    }
+    if let Ok(Value::String(ok)) = table.raw_get::<_, Value>("ok") {
+        return SimpleString::new(ok.to_string_lossy().replace(['\r', '\n'], " ")).into();
+    }
+
+    // the array stops at the first nil, like the Lua length operator
+    let mut array = RespArray::new(vec![]);
+    for i in 1.. {
+        match table.raw_get::<_, Value>(i) {
+            Ok(Value::Nil) | Err(_) => break,
+            Ok(value) => array.push(lua_to_resp(value)),
+        }
+    }
+    Some(array).into()
+}
+
+pub(crate) fn single_field_table<'lua>(
+    lua: &'lua Lua,
+    field: &str,
+    value: &str,
+) -> LuaResult<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.raw_set(field, value)?;
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_resp_to_lua_to_resp() -> Result<()> {
+        let lua = Lua::new();
+        let frames: Vec<RespFrame> = vec![
+            10.into(),
+            b"hello".into(),
+            SimpleString::new("OK").into(),
+            SimpleError::new("ERR failed").into(),
+            Some(RespArray::new(vec![1.into(), b"a".into()])).into(),
+        ];
+        for frame in frames {
+            let value = resp_to_lua(&lua, frame.clone())?;
+            assert_eq!(lua_to_resp(value), frame);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lua_to_resp() -> Result<()> {
+        let lua = Lua::new();
+        let value: Value = lua
+            .load("return {1, 2.9, 'a', true, false, nil, 4}")
+            .eval()?;
+        assert_eq!(
+            lua_to_resp(value),
+            Some(RespArray::new(vec![
+                1.into(),
+                2.into(),
+                b"a".into(),
+                1.into(),
+                RespFrame::BulkString(None),
+            ]))
+            .into()
+        );
+
+        let value: Value = lua.load("return {ok = 'a\\r\\n:1\\r\\nb'}").eval()?;
+        assert_eq!(lua_to_resp(value), SimpleString::new("a  :1  b").into());
+        let value: Value = lua.load("return {err = 'x\\r\\n+OK'}").eval()?;
+        assert_eq!(lua_to_resp(value), SimpleError::new("x  +OK").into());
+
+        let value = resp_to_lua(&lua, RespFrame::BulkString(None))?;
+        assert_eq!(value, Value::Boolean(false));
+        Ok(())
+    }
+}
@@ -0,0 +1,581 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+use mlua::{Function, Lua, MultiValue, Table, Value};
+
+use crate::{
+    rdb::{self, RdbError},
+    Backend, BulkString, ExecError, RespFrame,
+};
+
+use super::{
+    convert::lua_to_resp, create_array, create_lua, disable_data_access, enable_data_access,
+    error_reply, set_kill_hook, ReplyError, ScriptRegistry,
+};
+
+// registry key of the table holding the callbacks registered by a library
+const LIBRARY_FUNCTIONS: &str = "__library_functions";
+
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Library {
+    pub name: String,
+    pub engine: String,
+    pub code: String,
+    pub functions: BTreeMap<String, FunctionInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestorePolicy {
+    Append,
+    Replace,
+    Flush,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Libraries {
+    libraries: BTreeMap<String, Library>,
+    // function name -> library name
+    functions: HashMap<String, String>,
+    // library name -> the Lua state where its functions are registered
+    states: HashMap<String, Arc<Mutex<Lua>>>,
+}
+
+/// The libraries loaded with FUNCTION LOAD.
+#[derive(Debug, Default)]
+pub struct FunctionRegistry {
+    inner: RwLock<Libraries>,
+}
+
+impl FunctionInfo {
+    pub fn is_read_only(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "no-writes")
+    }
+}
+
+impl Libraries {
+    fn insert(&mut self, (library, lua): (Library, Lua), replace: bool) -> Result<(), String> {
+        if self.libraries.contains_key(&library.name) {
+            if !replace {
+                return Err(format!("ERR Library '{}' already exists", library.name));
+            }
+            self.remove(&library.name);
+        }
+        for name in library.functions.keys() {
+            if self.functions.contains_key(name) {
+                return Err(format!("ERR Function {} already exists", name));
+            }
+        }
+        for name in library.functions.keys() {
+            self.functions.insert(name.clone(), library.name.clone());
+        }
+        self.states
+            .insert(library.name.clone(), Arc::new(Mutex::new(lua)));
+        self.libraries.insert(library.name.clone(), library);
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Library> {
+        let library = self.libraries.remove(name)?;
+        self.states.remove(name);
+        for function in library.functions.keys() {
+            self.functions.remove(function);
+        }
+        Some(library)
+    }
+}
+
+impl FunctionRegistry {
+    /// Loads a library and returns its name. The library code runs as a
+    /// script of the registry, it may be stopped with FUNCTION KILL.
+    pub fn load(
+        &self,
+        scripts: &ScriptRegistry,
+        code: &str,
+        replace: bool,
+    ) -> Result<String, String> {
+        let library = compile_library(scripts, code)?;
+        let name = library.0.name.clone();
+        let mut inner = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        // work on a copy, so a failing load leaves the registry untouched
+        let mut libraries = inner.clone();
+        libraries.insert(library, replace)?;
+        *inner = libraries;
+        Ok(name)
+    }
+
+    pub fn delete(&self, name: &str) -> bool {
+        let mut inner = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        inner.remove(name).is_some()
+    }
+
+    pub fn flush(&self) {
+        *self.inner.write().unwrap_or_else(PoisonError::into_inner) = Libraries::default();
+    }
+
+    pub fn list(&self) -> Vec<Library> {
+        let inner = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        inner.libraries.values().cloned().collect()
+    }
+
+    // returns the Lua state of the library and the function metadata
+    fn get(&self, function: &str) -> Option<(Arc<Mutex<Lua>>, FunctionInfo)> {
+        let inner = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        let name = inner.functions.get(function)?;
+        let library = inner.libraries.get(name)?;
+        Some((
+            inner.states.get(name)?.clone(),
+            library.functions.get(function)?.clone(),
+        ))
+    }
+
+    /// Serializes all libraries in the format of FUNCTION DUMP.
+    pub fn dump(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for library in self.list() {
+            buf.push(rdb::RDB_OPCODE_FUNCTION2);
+            rdb::write_string(&mut buf, library.code.as_bytes());
+        }
+        rdb::write_footer(&mut buf);
+        buf
+    }
+
+    pub fn restore(
+        &self,
+        scripts: &ScriptRegistry,
+        payload: &[u8],
+        policy: RestorePolicy,
+    ) -> Result<(), String> {
+        let mut data = rdb::verify_footer(payload).map_err(|e| format!("ERR {}", e))?;
+        let mut libraries = Vec::new();
+        while !data.is_empty() {
+            let code = read_function(&mut data).map_err(|e| format!("ERR {}", e))?;
+            libraries.push(compile_library(scripts, &code)?);
+        }
+
+        let mut inner = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        let mut new = match policy {
+            RestorePolicy::Flush => Libraries::default(),
+            _ => inner.clone(),
+        };
+        for library in libraries {
+            new.insert(library, policy == RestorePolicy::Replace)?;
+        }
+        *inner = new;
+        Ok(())
+    }
+}
+
+/// Reads a library code stored with `RDB_OPCODE_FUNCTION2`.
+pub(crate) fn read_function(data: &mut &[u8]) -> Result<String, RdbError> {
+    if rdb::read_u8(data)? != rdb::RDB_OPCODE_FUNCTION2 {
+        return Err(RdbError::Invalid(
+            "given type is not a function".to_string(),
+        ));
+    }
+    String::from_utf8(rdb::read_string(data)?)
+        .map_err(|_| RdbError::Invalid("function code is not valid utf8".to_string()))
+}
+
+/// Calls a function with FCALL or FCALL_RO. As for EVAL, the caller must hold
+/// the exclusive lock of the backend.
+pub fn call_function(
+    backend: &Backend,
+    name: &str,
+    keys: Vec<BulkString>,
+    args: Vec<BulkString>,
+    read_only: bool,
+) -> Result<RespFrame, ExecError> {
+    let (lua, info) = match backend.functions.get(name) {
+        Some(function) => function,
+        None => return Err(ExecError::err("Function not found")),
+    };
+    if read_only && !info.is_read_only() {
+        return Err(ExecError::err(
+            "Can not execute a script with write flag using *_ro command.",
+        ));
+    }
+
+    let running = backend.scripts.begin();
+    let _batch = backend.aof.batch();
+    let lua = lua.lock().unwrap_or_else(PoisonError::into_inner);
+    let ret = enable_data_access(&lua, backend, &running, info.is_read_only()).and_then(|_| {
+        let functions: Table = lua.named_registry_value(LIBRARY_FUNCTIONS)?;
+        let function: Function = functions.raw_get(name)?;
+        let value: Value = function.call((create_array(&lua, keys)?, create_array(&lua, args)?))?;
+        Ok(lua_to_resp(value))
+    });
+    // also when the call failed, the state is kept for the next calls
+    let disabled = disable_data_access(&lua);
+    let ret = ret.and_then(|frame| disabled.map(|_| frame));
+
+    match ret {
+        Ok(RespFrame::Error(e)) => Err(e.into()),
+        Ok(frame) => Ok(frame),
+        Err(e) => Err(ExecError::Reply(format!(
+            "{} script: {}",
+            error_reply(&e),
+            name
+        ))),
+    }
+}
+
+// runs the library code once, its functions stay registered in the returned Lua state
+fn compile_library(scripts: &ScriptRegistry, code: &str) -> Result<(Library, Lua), String> {
+    let (engine, name, body) = parse_metadata(code)?;
+    let running = scripts.begin();
+    let (functions, lua) = create_lua()
+        .and_then(|lua| {
+            set_kill_hook(&lua, &running);
+            let functions = register_functions(&lua, body)?;
+            lua.remove_hook();
+            Ok((functions, lua))
+        })
+        .map_err(|e| error_reply(&e))?;
+    if functions.is_empty() {
+        return Err("ERR No functions registered".to_string());
+    }
+
+    let library = Library {
+        name,
+        engine,
+        code: code.to_string(),
+        functions: functions.into_iter().map(|f| (f.name.clone(), f)).collect(),
+    };
+    Ok((library, lua))
+}
+
+// "#!lua name=mylib\n<code>" -> (engine, library name, code)
+fn parse_metadata(code: &str) -> Result<(String, String, &str), String> {
+    let (first, body) = code.split_once('\n').unwrap_or((code, ""));
+    let shebang = first
+        .strip_prefix("#!")
+        .ok_or_else(|| "ERR Missing library metadata".to_string())?;
+
+    let mut parts = shebang.split_whitespace();
+    let engine = parts.next().unwrap_or_default();
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(format!("ERR Engine '{}' not found", engine));
+    }
+
+    let mut name = None;
+    for part in parts {
+        match part.split_once('=') {
+            Some(("name", value)) => name = Some(value.to_string()),
+            _ => return Err(format!("ERR Invalid metadata value given: {}", part)),
+        }
+    }
+    let name = name.ok_or_else(|| "ERR Library name was not given".to_string())?;
+    if !is_valid_name(&name) {
+        return Err("ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".to_string());
+    }
+    Ok(("LUA".to_string(), name, body))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_')
+}
+
+// runs the library code, which registers its functions with redis.register_function
+fn register_functions(lua: &Lua, body: &str) -> mlua::Result<Vec<FunctionInfo>> {
+    lua.set_named_registry_value(LIBRARY_FUNCTIONS, lua.create_table()?)?;
+    let registered = Arc::new(Mutex::new(Vec::<FunctionInfo>::new()));
+
+    let redis: Table = lua.globals().get("redis")?;
+    let functions = registered.clone();
+    redis.set(
+        "register_function",
+        lua.create_function(move |lua, args: MultiValue| {
+            let (info, callback) = parse_registration(args)?;
+            let mut functions = functions.lock().unwrap_or_else(PoisonError::into_inner);
+            if functions.iter().any(|f| f.name == info.name) {
+                return Err(reply_error("ERR Function already exists in the library"));
+            }
+            let table: Table = lua.named_registry_value(LIBRARY_FUNCTIONS)?;
+            table.raw_set(info.name.as_str(), callback)?;
+            functions.push(info);
+            Ok(())
+        })?,
+    )?;
+
+    lua.load(body).set_name("@user_function").exec()?;
+    // functions can only be registered while the library is loading
+    redis.set("register_function", Value::Nil)?;
+
+    let functions = registered
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    Ok(functions)
+}
+
+// redis.register_function('name', callback)
+// redis.register_function{function_name='name', callback=callback, flags={...}, description='...'}
+fn parse_registration(args: MultiValue) -> mlua::Result<(FunctionInfo, Function)> {
+    let mut args = args.into_iter();
+    let (name, callback, flags, description) = match (args.next(), args.next()) {
+        (Some(Value::String(name)), Some(Value::Function(callback))) => {
+            (name.to_str()?.to_string(), callback, vec![], None)
+        }
+        (Some(Value::Table(table)), None) => {
+            let mut name = None;
+            let mut callback = None;
+            let mut flags = vec![];
+            let mut description = None;
+            for pair in table.pairs::<String, Value>() {
+                match pair? {
+                    (key, Value::String(s)) if key == "function_name" => {
+                        name = Some(s.to_str()?.to_string())
+                    }
+                    (key, Value::Function(f)) if key == "callback" => callback = Some(f),
+                    (key, Value::String(s)) if key == "description" => {
+                        description = Some(s.to_str()?.to_string())
+                    }
+                    (key, Value::Table(t)) if key == "flags" => {
+                        for flag in t.sequence_values::<String>() {
+                            flags.push(flag?);
+                        }
+                    }
+                    _ => {
+                        return Err(reply_error(
+                            "ERR unknown argument given to redis.register_function",
+                        ))
+                    }
+                }
+            }
+            match (name, callback) {
+                (Some(name), Some(callback)) => (name, callback, flags, description),
+                (None, _) => {
+                    return Err(reply_error(
+                        "ERR redis.register_function must get a function name argument",
+                    ))
+                }
+                (_, None) => {
+                    return Err(reply_error(
+                        "ERR redis.register_function must get a callback argument",
+                    ))
+                }
+            }
+        }
+        _ => {
+            return Err(reply_error(
+                "ERR wrong arguments given to redis.register_function",
+            ))
+        }
+    };
+
+    if !is_valid_name(&name) {
+        return Err(reply_error("ERR Function names can only contain letters, numbers, or underscores(_) and must be at least one character long"));
+    }
+    if let Some(flag) = flags.iter().find(|f| !FUNCTION_FLAGS.contains(&f.as_str())) {
+        return Err(reply_error(&format!("ERR unknown flag given: {}", flag)));
+    }
+
+    let info = FunctionInfo {
+        name,
+        description,
+        flags,
+    };
+    Ok((info, callback))
+}
+
+fn reply_error(msg: &str) -> mlua::Error {
+    mlua::Error::external(ReplyError(msg.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY: &str = "#!lua name=mylib
+local function add(keys, args)
+  return redis.call('sadd', keys[1], args[1])
+end
+local function check(keys, args)
+  return redis.call('sismember', keys[1], args[1])
+end
+redis.register_function('myadd', add)
+redis.register_function{function_name='mycheck', callback=check, flags={'no-writes'}}
+";
+
+    fn keys(keys: &[&str]) -> Vec<BulkString> {
+        keys.iter().map(|&k| k.into()).collect()
+    }
+
+    #[test]
+    fn test_load_library() {
+        let scripts = ScriptRegistry::default();
+        let registry = FunctionRegistry::default();
+        assert_eq!(
+            registry.load(&scripts, LIBRARY, false),
+            Ok("mylib".to_string())
+        );
+
+        let libraries = registry.list();
+        assert_eq!(libraries.len(), 1);
+        assert_eq!(libraries[0].engine, "LUA");
+        assert_eq!(
+            libraries[0].functions.keys().collect::<Vec<_>>(),
+            vec!["myadd", "mycheck"]
+        );
+        assert!(libraries[0].functions["mycheck"].is_read_only());
+
+        assert_eq!(
+            registry.load(&scripts, LIBRARY, false),
+            Err("ERR Library 'mylib' already exists".to_string())
+        );
+        assert!(registry.load(&scripts, LIBRARY, true).is_ok());
+
+        let other = LIBRARY.replace("name=mylib", "name=other");
+        assert_eq!(
+            registry.load(&scripts, &other, false),
+            Err("ERR Function myadd already exists".to_string())
+        );
+
+        assert!(registry.delete("mylib"));
+        assert!(!registry.delete("mylib"));
+        assert!(registry.load(&scripts, &other, false).is_ok());
+    }
+
+    #[test]
+    fn test_load_library_errors() {
+        let scripts = ScriptRegistry::default();
+        let registry = FunctionRegistry::default();
+        let cases = [
+            ("return 1", "ERR Missing library metadata"),
+            ("#!js name=lib\n", "ERR Engine 'js' not found"),
+            ("#!lua\n", "ERR Library name was not given"),
+            ("#!lua name=lib foo=bar\n", "ERR Invalid metadata value given: foo=bar"),
+            ("#!lua name=lib\nlocal a = 1", "ERR No functions registered"),
+            (
+                "#!lua name=lib\nredis.register_function{function_name='f', callback=function() end, flags={'bad'}}",
+                "ERR unknown flag given: bad",
+            ),
+        ];
+        for (code, err) in cases {
+            assert_eq!(registry.load(&scripts, code, false), Err(err.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_call_function() {
+        let backend = Backend::new();
+        backend
+            .functions
+            .load(&backend.scripts, LIBRARY, false)
+            .unwrap();
+
+        let ret = call_function(&backend, "myadd", keys(&["myset"]), keys(&["A"]), false);
+        assert_eq!(ret, Ok(1.into()));
+        let ret = call_function(&backend, "mycheck", keys(&["myset"]), keys(&["A"]), true);
+        assert_eq!(ret, Ok(1.into()));
+
+        let ret = call_function(&backend, "myadd", keys(&["myset"]), keys(&["B"]), true);
+        assert_eq!(
+            ret,
+            Err(ExecError::err(
+                "Can not execute a script with write flag using *_ro command."
+            ))
+        );
+        let ret = call_function(&backend, "nofunc", vec![], vec![], false);
+        assert_eq!(ret, Err(ExecError::err("Function not found")));
+    }
+
+    #[test]
+    fn test_library_loaded_once() {
+        let backend = Backend::new();
+        let code = "#!lua name=lib
+local calls = 0
+redis.register_function('f', function(keys, args) calls = calls + 1 return calls end)";
+        backend
+            .functions
+            .load(&backend.scripts, code, false)
+            .unwrap();
+        let ret = call_function(&backend, "f", vec![], vec![], false);
+        assert_eq!(ret, Ok(1.into()));
+        let ret = call_function(&backend, "f", vec![], vec![], false);
+        assert_eq!(ret, Ok(2.into()));
+    }
+
+    #[test]
+    fn test_kill_library_load() {
+        let backend = Backend::new();
+        backend
+            .scripts
+            .set_time_limit(std::time::Duration::from_millis(10));
+        let b = backend.clone();
+        let handle = std::thread::spawn(move || {
+            b.functions
+                .load(&b.scripts, "#!lua name=lib\nwhile true do end", false)
+        });
+
+        while !backend.scripts.is_busy() {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(backend.scripts.kill(), Ok(crate::cmd::RESP_OK.clone()));
+        let ret = handle.join().unwrap();
+        assert!(matches!(ret, Err(e) if e.starts_with("ERR Script killed by user")));
+        assert!(backend.functions.list().is_empty());
+    }
+
+    #[test]
+    fn test_no_writes_function() {
+        let backend = Backend::new();
+        let code = "#!lua name=lib
+redis.register_function{function_name='f', callback=function(keys, args) return redis.call('sadd', keys[1], 'A') end, flags={'no-writes'}}";
+        backend
+            .functions
+            .load(&backend.scripts, code, false)
+            .unwrap();
+        let ret = call_function(&backend, "f", keys(&["myset"]), vec![], false);
+        assert!(
+            matches!(ret, Err(ExecError::Reply(e)) if e.starts_with("ERR Write commands are not allowed from read-only scripts."))
+        );
+    }
+
+    #[test]
+    fn test_dump_and_restore() {
+        let scripts = ScriptRegistry::default();
+        let registry = FunctionRegistry::default();
+        registry.load(&scripts, LIBRARY, false).unwrap();
+        let payload = registry.dump();
+
+        assert_eq!(
+            registry.restore(&scripts, &payload, RestorePolicy::Append),
+            Err("ERR Library 'mylib' already exists".to_string())
+        );
+        assert!(registry
+            .restore(&scripts, &payload, RestorePolicy::Replace)
+            .is_ok());
+        assert!(registry
+            .restore(&scripts, &payload, RestorePolicy::Flush)
+            .is_ok());
+
+        let restored = FunctionRegistry::default();
+        assert!(restored
+            .restore(&scripts, &payload, RestorePolicy::Append)
+            .is_ok());
+        assert_eq!(restored.list(), registry.list());
+
+        assert_eq!(
+            restored.restore(&scripts, &payload[1..], RestorePolicy::Append),
+            Err("ERR payload version or checksum are wrong".to_string())
+        );
+    }
+}
@@ -0,0 +1,557 @@
+mod convert;
+mod function;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use mlua::{
+    HookTriggers, Lua, LuaOptions, MultiValue, Result as LuaResult, StdLib, Table, Value, Variadic,
+};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::{
+    aof, command_spec, Backend, BulkString, Command, CommandError, CommandSpec, ExecError,
+    RespArray, RespFrame, SimpleError,
+};
+
+pub use self::function::{call_function, FunctionInfo, FunctionRegistry, Library, RestorePolicy};
+
+use self::convert::{lua_to_resp, resp_to_lua, single_field_table};
+
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_millis(5000);
+// how often the hook checks whether the script was killed
+const HOOK_INSTRUCTIONS: u32 = 100_000;
+
+// an error reply that is raised by `redis.call` and returned to the client as is
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct ReplyError(String);
+
+#[derive(Debug)]
+struct RunningScript {
+    started: Instant,
+    killed: Arc<AtomicBool>,
+    wrote: Arc<AtomicBool>,
+}
+
+/// Cache of the scripts loaded with EVAL or SCRIPT LOAD, keyed by their SHA1,
+/// and the state of the script currently being executed.
+#[derive(Debug)]
+pub struct ScriptRegistry {
+    scripts: DashMap<String, String>,
+    running: Mutex<Option<RunningScript>>,
+    time_limit: AtomicU64,
+}
+
+impl Default for ScriptRegistry {
+    fn default() -> Self {
+        Self {
+            scripts: DashMap::new(),
+            running: Mutex::new(None),
+            time_limit: AtomicU64::new(DEFAULT_TIME_LIMIT.as_millis() as u64),
+        }
+    }
+}
+
+impl ScriptRegistry {
+    pub fn load(&self, body: impl Into<String>) -> String {
+        let body = body.into();
+        let sha = sha1_hex(body.as_bytes());
+        self.scripts.insert(sha.clone(), body);
+        sha
+    }
+
+    pub fn get(&self, sha: &str) -> Option<String> {
+        self.scripts
+            .get(&sha.to_ascii_lowercase())
+            .map(|body| body.value().clone())
+    }
+
+    pub fn exists(&self, sha: &str) -> bool {
+        self.scripts.contains_key(&sha.to_ascii_lowercase())
+    }
+
+    pub fn flush(&self) {
+        self.scripts.clear();
+    }
+
+    /// Once a script runs longer than this, other clients get a BUSY error and
+    /// the script may be stopped with SCRIPT KILL.
+    pub fn time_limit(&self) -> Duration {
+        Duration::from_millis(self.time_limit.load(Ordering::Relaxed))
+    }
+
+    pub fn set_time_limit(&self, limit: Duration) {
+        self.time_limit
+            .store(limit.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn is_busy(&self) -> bool {
+        let running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        match *running {
+            Some(ref script) => script.started.elapsed() > self.time_limit(),
+            None => false,
+        }
+    }
+
+    pub fn kill(&self) -> Result<RespFrame, ExecError> {
+        let running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        match *running {
+            None => Err(ExecError::NotBusy),
+            Some(ref script) if script.wrote.load(Ordering::Relaxed) => Err(ExecError::Unkillable),
+            Some(ref script) => {
+                script.killed.store(true, Ordering::Relaxed);
+                Ok(crate::cmd::RESP_OK.clone())
+            }
+        }
+    }
+
+    fn begin(&self) -> RunningGuard<'_> {
+        let script = RunningScript {
+            started: Instant::now(),
+            killed: Arc::new(AtomicBool::new(false)),
+            wrote: Arc::new(AtomicBool::new(false)),
+        };
+        let killed = script.killed.clone();
+        let wrote = script.wrote.clone();
+        *self.running.lock().unwrap_or_else(PoisonError::into_inner) = Some(script);
+        RunningGuard {
+            registry: self,
+            killed,
+            wrote,
+        }
+    }
+}
+
+// clears the running script when the execution ends
+struct RunningGuard<'a> {
+    registry: &'a ScriptRegistry,
+    killed: Arc<AtomicBool>,
+    wrote: Arc<AtomicBool>,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        *self
+            .registry
+            .running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
+
+/// Runs a script body with the given KEYS and ARGV. The caller must hold the
+/// exclusive lock of the backend, so the script is executed atomically.
+pub fn eval_script(
+    backend: &Backend,
+    sha: &str,
+    body: &str,
+    keys: Vec<BulkString>,
+    args: Vec<BulkString>,
+) -> Result<RespFrame, ExecError> {
+    let running = backend.scripts.begin();
+    // the writes of the script are propagated as a transaction
+    let _batch = backend.aof.batch();
+    let ret = create_lua().and_then(|lua| {
+        enable_data_access(&lua, backend, &running, false)?;
+        lua.globals().set("KEYS", create_array(&lua, keys)?)?;
+        lua.globals().set("ARGV", create_array(&lua, args)?)?;
+        let value: Value = lua.load(body).set_name("@user_script").eval()?;
+        Ok(lua_to_resp(value))
+    });
+
+    match ret {
+        Ok(RespFrame::Error(e)) => Err(e.into()),
+        Ok(frame) => Ok(frame),
+        Err(e) => Err(ExecError::Reply(format!(
+            "{} script: {}",
+            error_reply(&e),
+            sha
+        ))),
+    }
+}
+
+// the functions of the base library loading code from the files
+const LUA_DENIED_GLOBALS: [&str; 2] = ["dofile", "loadfile"];
+
+// a Lua state with the `redis` library, except the functions accessing the data.
+// As in Redis, scripts have no access to the files, processes or modules of the host.
+fn create_lua() -> LuaResult<Lua> {
+    let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH;
+    let lua = Lua::new_with(libs, LuaOptions::default())?;
+    for name in LUA_DENIED_GLOBALS {
+        lua.globals().set(name, Value::Nil)?;
+    }
+    let redis = lua.create_table()?;
+    redis.set(
+        "sha1hex",
+        lua.create_function(|_, s: mlua::String| Ok(sha1_hex(s.as_bytes())))?,
+    )?;
+    redis.set(
+        "error_reply",
+        lua.create_function(|lua, msg: String| single_field_table(lua, "err", &msg))?,
+    )?;
+    redis.set(
+        "status_reply",
+        lua.create_function(|lua, msg: String| single_field_table(lua, "ok", &msg))?,
+    )?;
+    redis.set(
+        "log",
+        lua.create_function(|_, (level, msgs): (i64, Variadic<String>)| {
+            let msg = msgs.join(" ");
+            match level {
+                0 => debug!("{}", msg),
+                1 | 2 => info!("{}", msg),
+                _ => warn!("{}", msg),
+            }
+            Ok(())
+        })?,
+    )?;
+    for (i, name) in ["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"]
+        .iter()
+        .enumerate()
+    {
+        redis.set(*name, i)?;
+    }
+    lua.globals().set("redis", redis)?;
+    Ok(lua)
+}
+
+// adds redis.call and redis.pcall, and lets SCRIPT KILL interrupt the script
+fn enable_data_access(
+    lua: &Lua,
+    backend: &Backend,
+    running: &RunningGuard,
+    read_only: bool,
+) -> LuaResult<()> {
+    let redis: Table = lua.globals().get("redis")?;
+    let (b, wrote) = (backend.clone(), running.wrote.clone());
+    redis.set(
+        "call",
+        lua.create_function(move |lua, args: MultiValue| {
+            call(lua, &b, &wrote, args, true, read_only)
+        })?,
+    )?;
+    let (b, wrote) = (backend.clone(), running.wrote.clone());
+    redis.set(
+        "pcall",
+        lua.create_function(move |lua, args: MultiValue| {
+            call(lua, &b, &wrote, args, false, read_only)
+        })?,
+    )?;
+
+    set_kill_hook(lua, running);
+    Ok(())
+}
+
+// removes the functions of `enable_data_access` from a Lua state that is kept
+// after the call, they hold the backend
+fn disable_data_access(lua: &Lua) -> LuaResult<()> {
+    lua.remove_hook();
+    let redis: Table = lua.globals().get("redis")?;
+    redis.set("call", Value::Nil)?;
+    redis.set("pcall", Value::Nil)
+}
+
+// lets SCRIPT KILL and FUNCTION KILL interrupt the running code
+fn set_kill_hook(lua: &Lua, running: &RunningGuard) {
+    let killed = running.killed.clone();
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+        move |_, _| {
+            if killed.load(Ordering::Relaxed) {
+                return Err(mlua::Error::external(ReplyError(
+                    "ERR Script killed by user with SCRIPT KILL...".to_string(),
+                )));
+            }
+            Ok(())
+        },
+    );
+}
+
+fn create_array<'lua>(lua: &'lua Lua, values: Vec<BulkString>) -> LuaResult<Table<'lua>> {
+    let table = lua.create_table_with_capacity(values.len(), 0)?;
+    for (i, value) in values.into_iter().enumerate() {
+        table.raw_set(i + 1, lua.create_string(value.as_ref())?)?;
+    }
+    Ok(table)
+}
+
+// redis.call raises the error replies, redis.pcall returns them as a table
+fn call<'lua>(
+    lua: &'lua Lua,
+    backend: &Backend,
+    wrote: &AtomicBool,
+    args: MultiValue<'lua>,
+    raise: bool,
+    read_only: bool,
+) -> LuaResult<Value<'lua>> {
+    let reply = match build_command(args, backend.aof.is_enabled()) {
+        Ok((_, spec, _)) if read_only && spec.has_flag("write") => {
+            SimpleError::new("ERR Write commands are not allowed from read-only scripts.").into()
+        }
+        Ok((_, spec, _)) if spec.has_flag("denyoom") && backend.is_oom() => ExecError::Oom.into(),
+        Ok((cmd, spec, argv)) => {
+            if spec.has_flag("write") {
+                wrote.store(true, Ordering::Relaxed);
+            }
+            aof::call(backend, cmd, argv).unwrap_or_else(RespFrame::from)
+        }
+        Err(e) => e.into(),
+    };
+
+    match reply {
+        RespFrame::Error(e) if raise => Err(mlua::Error::external(ReplyError(e.0))),
+        // scripts use RESP2, as in Redis before redis.setresp(3)
+        reply => resp_to_lua(lua, reply.into_resp2()),
+    }
+}
+
+// also returns the arguments when `keep_argv` is set, to propagate the command
+fn build_command(
+    args: MultiValue,
+    keep_argv: bool,
+) -> Result<(Command, &'static CommandSpec, Option<RespArray>), SimpleError> {
+    if args.is_empty() {
+        return Err(SimpleError::new(
+            "ERR Please specify at least one argument for this redis lib call",
+        ));
+    }
+
+    let mut frames = Vec::with_capacity(args.len());
+    for arg in args {
+        let arg = match arg {
+            Value::String(s) => BulkString::new(s.as_bytes().to_vec()),
+            Value::Integer(n) => BulkString::from(n.to_string()),
+            Value::Number(n) => BulkString::from(n.to_string()),
+            _ => {
+                return Err(SimpleError::new(
+                    "ERR Lua redis lib command arguments must be strings or integers",
+                ))
+            }
+        };
+        frames.push(Some(arg).into());
+    }
+
+    let argv = RespArray::new(frames);
+    let spec = command_spec(&argv);
+    let kept = keep_argv.then(|| argv.clone());
+    match Command::try_from(argv) {
+        Ok(Command::Unrecognized(_)) => Err(SimpleError::new(
+            "ERR Unknown Redis command called from script",
+        )),
+        Ok(cmd) => match spec {
+            Some(spec) if spec.has_flag("noscript") => Err(SimpleError::new(
+                "ERR This Redis command is not allowed from script",
+            )),
+            Some(spec) => Ok((cmd, spec, kept)),
+            None => Err(SimpleError::new(
+                "ERR Unknown Redis command called from script",
+            )),
+        },
+        Err(CommandError::WrongArity(_)) => Err(SimpleError::new(
+            "ERR Wrong number of args calling Redis command from script",
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// the reply of a Lua error, its newlines replaced by spaces as Redis does
+fn error_reply(e: &mlua::Error) -> String {
+    let reply = match e {
+        mlua::Error::CallbackError { cause, .. } => error_reply(cause),
+        mlua::Error::WithContext { cause, .. } => error_reply(cause),
+        mlua::Error::ExternalError(e) => match e.downcast_ref::<ReplyError>() {
+            Some(reply) => reply.0.clone(),
+            None => format!("ERR {}", e),
+        },
+        mlua::Error::SyntaxError { message, .. } => {
+            format!("ERR Error compiling script (new function): {}", message)
+        }
+        mlua::Error::RuntimeError(msg) => format!("ERR {}", msg),
+        e => format!("ERR {}", e),
+    };
+    reply.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(
+        backend: &Backend,
+        body: &str,
+        keys: &[&str],
+        args: &[&str],
+    ) -> Result<RespFrame, ExecError> {
+        let sha = backend.scripts.load(body);
+        eval_script(
+            backend,
+            &sha,
+            body,
+            keys.iter().map(|&k| k.into()).collect(),
+            args.iter().map(|&a| a.into()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_sha1_hex() {
+        assert_eq!(
+            sha1_hex(b"return 1"),
+            "e0e1f9fabfc9d4800c877a703b823ac0578ff8db"
+        );
+    }
+
+    #[test]
+    fn test_eval_keys_and_argv() {
+        let backend = Backend::new();
+        let ret = eval(
+            &backend,
+            "return {KEYS[1], ARGV[1], ARGV[2]}",
+            &["key"],
+            &["a", "b"],
+        );
+        assert_eq!(
+            ret,
+            Ok(Some(RespArray::new(vec![
+                b"key".into(),
+                b"a".into(),
+                b"b".into()
+            ]))
+            .into())
+        );
+    }
+
+    #[test]
+    fn test_eval_redis_call() {
+        let backend = Backend::new();
+        let ret = eval(
+            &backend,
+            "redis.call('sadd', KEYS[1], ARGV[1], ARGV[2]) \
+             return redis.call('sismember', KEYS[1], ARGV[2])",
+            &["myset"],
+            &["A", "B"],
+        );
+        assert_eq!(ret, Ok(1.into()));
+        assert!(backend.hset.get("myset").unwrap().contains(&b"A".into()));
+
+        // a map reply is a flat array in scripts
+        let ret = eval(
+            &backend,
+            "redis.call('hset', KEYS[1], 'field', 'value') \
+             return redis.call('hgetall', KEYS[1])",
+            &["myhash"],
+            &[],
+        );
+        assert_eq!(
+            ret,
+            Ok(Some(RespArray::new(vec![b"field".into(), b"value".into()])).into())
+        );
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let backend = Backend::new();
+
+        let ret = eval(
+            &backend,
+            "redis.call('sadd', 'myset', 'a') return redis.call('hmget', 'myset', 'f')",
+            &[],
+            &[],
+        );
+        assert!(matches!(ret, Err(ExecError::Reply(e)) if e.starts_with("WRONGTYPE")));
+
+        let ret = eval(&backend, "return redis.pcall('nocmd')", &[], &[]);
+        assert_eq!(
+            ret,
+            Err(ExecError::Reply(
+                "ERR Unknown Redis command called from script".to_string()
+            ))
+        );
+
+        for call in [
+            "redis.call('multi')",
+            "redis.call('config', 'set', 'dir', '/tmp')",
+            "redis.call('client', 'id')",
+            "redis.call('function', 'flush')",
+        ] {
+            let ret = eval(&backend, &format!("return {}", call), &[], &[]);
+            assert!(
+                matches!(ret, Err(ExecError::Reply(e)) if e.starts_with("ERR This Redis command is not allowed from script")),
+                "{}",
+                call
+            );
+        }
+
+        let ret = eval(&backend, "return redis.error_reply('MY error')", &[], &[]);
+        assert_eq!(ret, Err(ExecError::Reply("MY error".to_string())));
+        assert_eq!(ret.unwrap_err().code(), "MY");
+
+        let ret = eval(&backend, "error('x\\r\\n+OK')", &[], &[]);
+        assert!(
+            matches!(ret, Err(ExecError::Reply(e)) if e.contains("x  +OK") && !e.contains(['\r', '\n']))
+        );
+
+        let ret = eval(&backend, "return +", &[], &[]);
+        assert!(
+            matches!(ret, Err(ExecError::Reply(e)) if e.starts_with("ERR Error compiling script"))
+        );
+    }
+
+    #[test]
+    fn test_eval_sandbox() {
+        let backend = Backend::new();
+        for global in [
+            "os", "io", "package", "require", "dofile", "loadfile", "debug",
+        ] {
+            let body = format!("return type({})", global);
+            let ret = eval(&backend, &body, &[], &[]);
+            assert_eq!(ret, Ok(Some(BulkString::from("nil")).into()), "{}", global);
+        }
+        for global in ["string", "table", "math"] {
+            let body = format!("return type({})", global);
+            let ret = eval(&backend, &body, &[], &[]);
+            assert_eq!(
+                ret,
+                Ok(Some(BulkString::from("table")).into()),
+                "{}",
+                global
+            );
+        }
+
+        let ret = eval(&backend, "os.execute('true')", &[], &[]);
+        assert!(matches!(ret, Err(ExecError::Reply(e)) if e.starts_with("ERR")));
+    }
+
+    #[test]
+    fn test_script_kill() {
+        let backend = Backend::new();
+        assert_eq!(backend.scripts.kill(), Err(ExecError::NotBusy));
+
+        backend.scripts.set_time_limit(Duration::from_millis(10));
+        let b = backend.clone();
+        let handle = std::thread::spawn(move || eval(&b, "while true do end", &[], &[]));
+
+        while !backend.scripts.is_busy() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(backend.scripts.kill(), Ok(crate::cmd::RESP_OK.clone()));
+
+        let ret = handle.join().unwrap();
+        assert!(
+            matches!(ret, Err(ExecError::Reply(e)) if e.starts_with("ERR Script killed by user"))
+        );
+        assert!(!backend.scripts.is_busy());
+    }
+}
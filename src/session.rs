@@ -0,0 +1,998 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use lazy_static::lazy_static;
+
+use crate::util::block_in_place;
+use crate::{
+    aof,
+    cmd::{check_name, RESP_OK},
+    command_name, command_spec, read_keys, Backend, BulkString, Client, Command, CommandError,
+    CommandExecutor, ConfigCmd, CurrentClient, ExecError, Function, Hello, RespArray, RespFrame,
+    RespPush, Script, SimpleError, SimpleString,
+};
+
+// the version of Redis whose commands and replies are implemented
+pub(crate) const REDIS_VERSION: &str = "7.0.0";
+
+lazy_static! {
+    static ref RESP_QUEUED: RespFrame = SimpleString::new("QUEUED").into();
+}
+
+// commands queued between MULTI and EXEC
+#[derive(Debug, Default)]
+struct Transaction {
+    queue: Vec<Queued>,
+    // set when a queued command failed to parse, EXEC then aborts
+    aborted: bool,
+}
+
+#[derive(Debug)]
+struct Queued {
+    cmd: Command,
+    // the arguments, kept to be propagated to the AOF
+    argv: Option<RespArray>,
+    // the name in the statistics
+    name: Option<String>,
+    // the keys read, for CLIENT TRACKING
+    keys: Vec<String>,
+}
+
+/// Per-connection state, every frame received on a connection goes through `process`.
+#[derive(Debug)]
+pub struct Session {
+    // registered in the backend while the session lives
+    client: Arc<Client>,
+    backend: Backend,
+    transaction: Option<Transaction>,
+    watched_keys: Vec<String>,
+    // set by the backend when one of the watched keys is modified
+    dirty: Arc<AtomicBool>,
+    // 2 or 3, switched by HELLO
+    protover: i64,
+}
+
+impl Session {
+    /// A session without connection, as when loading the AOF.
+    pub fn new(backend: Backend) -> Self {
+        Self::with_addrs(backend, String::new(), String::new())
+    }
+
+    /// The session of a connection from `addr` to the local address `laddr`.
+    pub fn connect(backend: Backend, addr: SocketAddr, laddr: SocketAddr) -> Self {
+        Self::with_addrs(backend, addr.to_string(), laddr.to_string())
+    }
+
+    fn with_addrs(backend: Backend, addr: String, laddr: String) -> Self {
+        backend.stats.incr_connections_received();
+        let client = Arc::new(Client::new(backend.next_client_id(), addr, laddr));
+        backend.register_client(client.clone());
+        Self {
+            client,
+            backend,
+            transaction: None,
+            watched_keys: Vec::new(),
+            dirty: Arc::new(AtomicBool::new(false)),
+            protover: 2,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.client.id()
+    }
+
+    /// The entry of the session in the client registry.
+    pub fn client(&self) -> &Arc<Client> {
+        &self.client
+    }
+
+    pub fn in_multi(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// The protocol of the replies, RESP3 frames are downgraded for RESP2 clients.
+    pub fn protover(&self) -> i64 {
+        self.protover
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.client.name()
+    }
+
+    pub fn process(&mut self, frame: RespFrame) -> Result<RespFrame, CommandError> {
+        let _current = CurrentClient::set(self.id());
+        self.client.touch();
+        let ret = self.dispatch(frame);
+        let queued = self.transaction.as_ref().map(|tx| tx.queue.len() as i64);
+        self.client.set_multi(queued.unwrap_or(-1));
+        match ret {
+            Ok(ref frame) => self.record_reply(frame),
+            // the command is rejected with an ERR reply
+            Err(_) => self.backend.stats.incr_error_reply("ERR"),
+        }
+        ret
+    }
+
+    fn dispatch(&mut self, frame: RespFrame) -> Result<RespFrame, CommandError> {
+        let argv = match frame {
+            RespFrame::Array(Some(ref argv)) if self.backend.aof.is_enabled() => Some(argv.clone()),
+            _ => None,
+        };
+        let name = match frame {
+            RespFrame::Array(Some(ref argv)) => command_name(argv),
+            _ => None,
+        };
+        if let Some(ref name) = name {
+            self.client.set_last_command(name);
+        }
+        // CLIENT CACHING applies to the next command only
+        let caching = self.client.take_caching();
+        let denyoom = match frame {
+            RespFrame::Array(Some(ref argv)) => {
+                command_spec(argv).is_some_and(|spec| spec.has_flag("denyoom"))
+            }
+            _ => false,
+        };
+        let keys = match (&frame, self.client.tracking()) {
+            (RespFrame::Array(Some(argv)), Some(tracking)) if tracking.tracks_reads(caching) => {
+                read_keys(argv)
+            }
+            _ => vec![],
+        };
+        let cmd = match Command::try_from(frame) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                if let Some(ref name) = name {
+                    self.backend.stats.incr_rejected_call(name);
+                }
+                match self.transaction {
+                    Some(ref mut tx) => {
+                        tx.aborted = true;
+                        return Ok(SimpleError::from(e).into());
+                    }
+                    None => return Err(e),
+                }
+            }
+        };
+
+        // the commands which may add data are rejected over maxmemory, also
+        // when queued in a transaction, which is then aborted
+        if denyoom && self.backend.is_oom() {
+            if let Some(ref name) = name {
+                self.backend.stats.incr_rejected_call(name);
+            }
+            if let Some(ref mut tx) = self.transaction {
+                tx.aborted = true;
+            }
+            return Ok(ExecError::Oom.into());
+        }
+
+        // as Redis, a RESP2 client in the subscribed state can only change its channels
+        if self.protover == 2
+            && self.client.subscriptions() > 0
+            && !matches!(cmd, Command::Subscribe(_) | Command::Unsubscribe(_))
+        {
+            return Ok(ExecError::err(format!(
+                "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                name.unwrap_or_default()
+            ))
+            .into());
+        }
+
+        let start = Instant::now();
+        let mut queued = false;
+        let frame = match cmd {
+            Command::Multi(_) => self.multi(),
+            // EXEC may run scripts
+            Command::Exec(_) => block_in_place(|| self.exec()),
+            Command::Discard(_) => self.discard(),
+            Command::Watch(watch) => self.watch(watch.keys),
+            // the replies of the channels are not the replies of EXEC
+            Command::Subscribe(_) | Command::Unsubscribe(_) if self.transaction.is_some() => {
+                if let Some(ref mut tx) = self.transaction {
+                    tx.aborted = true;
+                }
+                ExecError::err("Command not allowed inside a transaction").into()
+            }
+            Command::Unwatch(_) if self.transaction.is_none() => {
+                self.unwatch();
+                RESP_OK.clone()
+            }
+            cmd => match self.transaction {
+                Some(ref mut tx) => match cmd {
+                    Command::Unrecognized(_) => {
+                        tx.aborted = true;
+                        cmd.execute(&self.backend).unwrap_or_else(RespFrame::from)
+                    }
+                    cmd => {
+                        tx.queue.push(Queued {
+                            cmd,
+                            argv,
+                            name: name.clone(),
+                            keys,
+                        });
+                        queued = true;
+                        RESP_QUEUED.clone()
+                    }
+                },
+                None => {
+                    let frame = self.execute(cmd, argv);
+                    self.backend.track_keys(self.id(), keys);
+                    frame
+                }
+            },
+        };
+        // the queued commands are recorded when executed by EXEC
+        if !queued {
+            self.record_call(name.as_deref(), start, &frame);
+        }
+        self.backend.stats.incr_commands_processed();
+        Ok(frame)
+    }
+
+    fn execute(&mut self, cmd: Command, argv: Option<RespArray>) -> RespFrame {
+        match cmd {
+            // the commands of the connection do not access the dataset
+            Command::Hello(_)
+            | Command::ClientCmd(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_) => self.call(cmd, argv),
+            // SCRIPT KILL and FUNCTION KILL must not wait for the running script
+            Command::Script(Script::Kill) | Command::Function(Function::Kill) => {
+                cmd.execute(&self.backend).unwrap_or_else(RespFrame::from)
+            }
+            // scripts and functions are executed atomically, snapshots are
+            // taken while no other command runs, as when CONFIG SET enables the AOF
+            Command::Eval(_)
+            | Command::EvalSha(_)
+            | Command::FCall(_)
+            | Command::Function(Function::Load { .. } | Function::Restore { .. })
+            | Command::Save(_)
+            | Command::BgSave(_)
+            | Command::BgRewriteAof(_)
+            | Command::ConfigCmd(ConfigCmd::Set(_)) => {
+                // SCRIPT KILL must be able to run on the runtime meanwhile
+                block_in_place(|| match self.backend.lock_exclusive() {
+                    Some(_guard) => {
+                        aof::call(&self.backend, cmd, argv).unwrap_or_else(RespFrame::from)
+                    }
+                    None => busy_error(),
+                })
+            }
+            cmd => match self.backend.lock_shared() {
+                Some(_guard) => aof::call(&self.backend, cmd, argv).unwrap_or_else(RespFrame::from),
+                None => busy_error(),
+            },
+        }
+    }
+
+    // executes a command, those of the connection are applied to this session
+    fn call(&mut self, cmd: Command, argv: Option<RespArray>) -> RespFrame {
+        match cmd {
+            Command::Hello(hello) => self.hello(hello),
+            Command::ClientCmd(cmd) => cmd
+                .execute_for(&self.backend, &self.client)
+                .unwrap_or_else(RespFrame::from),
+            Command::Subscribe(subscribe) => self.subscribe(subscribe.channels),
+            Command::Unsubscribe(unsubscribe) => self.unsubscribe(unsubscribe.channels),
+            cmd => aof::call(&self.backend, cmd, argv).unwrap_or_else(RespFrame::from),
+        }
+    }
+
+    fn multi(&mut self) -> RespFrame {
+        if self.transaction.is_some() {
+            return SimpleError::new("ERR MULTI calls can not be nested").into();
+        }
+        self.transaction = Some(Transaction::default());
+        RESP_OK.clone()
+    }
+
+    fn exec(&mut self) -> RespFrame {
+        let tx = match self.transaction.take() {
+            Some(tx) => tx,
+            None => return SimpleError::new("ERR EXEC without MULTI").into(),
+        };
+        if tx.aborted {
+            self.unwatch();
+            return SimpleError::new("EXECABORT Transaction discarded because of previous errors.")
+                .into();
+        }
+
+        let backend = self.backend.clone();
+        let _guard = match backend.lock_exclusive() {
+            Some(guard) => guard,
+            None => {
+                self.unwatch();
+                return busy_error();
+            }
+        };
+        let dirty = self.dirty.load(Ordering::Relaxed);
+        self.unwatch();
+        if dirty {
+            return RespFrame::Array(None);
+        }
+
+        let _batch = backend.aof.batch();
+        let mut result = RespArray::new(Vec::with_capacity(tx.queue.len()));
+        for queued in tx.queue {
+            let start = Instant::now();
+            let reply = self.call(queued.cmd, queued.argv);
+            if self.client.is_tracking() {
+                backend.track_keys(self.id(), queued.keys);
+            }
+            self.record_call(queued.name.as_deref(), start, &reply);
+            self.record_reply(&reply);
+            result.push(reply);
+        }
+        RespFrame::Array(Some(result))
+    }
+
+    fn discard(&mut self) -> RespFrame {
+        match self.transaction.take() {
+            Some(_) => {
+                self.unwatch();
+                RESP_OK.clone()
+            }
+            None => SimpleError::new("ERR DISCARD without MULTI").into(),
+        }
+    }
+
+    fn watch(&mut self, keys: Vec<String>) -> RespFrame {
+        if self.transaction.is_some() {
+            return SimpleError::new("ERR WATCH inside MULTI is not allowed").into();
+        }
+        for key in keys {
+            if !self.watched_keys.contains(&key) {
+                self.backend
+                    .watch_key(key.clone(), self.id(), self.dirty.clone());
+                self.watched_keys.push(key);
+            }
+        }
+        RESP_OK.clone()
+    }
+
+    fn hello(&mut self, hello: Hello) -> RespFrame {
+        if let Some(protover) = hello.protover {
+            if !(2..=3).contains(&protover) {
+                return SimpleError::new("NOPROTO unsupported protocol version").into();
+            }
+        }
+        // there are no ACL users, the default user has no password
+        if let Some((username, _)) = hello.auth {
+            if username != "default" {
+                return SimpleError::new(
+                    "WRONGPASS invalid username-password pair or user is disabled.",
+                )
+                .into();
+            }
+        }
+        if let Some(name) = hello.setname {
+            if let Err(e) = check_name(&name) {
+                return e.into();
+            }
+            self.client.set_name((!name.is_empty()).then_some(name));
+        }
+        if let Some(protover) = hello.protover {
+            self.protover = protover;
+            self.client.set_protover(protover);
+        }
+
+        let mut map = BTreeMap::new();
+        let mut insert = |key: &str, value: RespFrame| {
+            map.insert(Some(BulkString::from(key)).into(), value);
+        };
+        insert("server", Some(BulkString::from("redis")).into());
+        insert("version", Some(BulkString::from(REDIS_VERSION)).into());
+        insert("proto", self.protover.into());
+        insert("id", (self.id() as i64).into());
+        insert("mode", Some(BulkString::from("standalone")).into());
+        insert("role", Some(BulkString::from("master")).into());
+        insert("modules", Some(RespArray::new(vec![])).into());
+        RespFrame::Map(map)
+    }
+
+    fn subscribe(&mut self, channels: Vec<String>) -> RespFrame {
+        let replies = channels
+            .into_iter()
+            .map(|channel| {
+                let count = self.client.subscribe(channel.clone());
+                subscription_reply("subscribe", Some(channel), count)
+            })
+            .collect();
+        self.reply_all(replies)
+    }
+
+    // without channels, from all the channels
+    fn unsubscribe(&mut self, channels: Vec<String>) -> RespFrame {
+        let channels = match channels.is_empty() {
+            true => self.client.channels().into_iter().collect(),
+            false => channels,
+        };
+        if channels.is_empty() {
+            return subscription_reply("unsubscribe", None, 0);
+        }
+        let replies = channels
+            .into_iter()
+            .map(|channel| {
+                let count = self.client.unsubscribe(&channel);
+                subscription_reply("unsubscribe", Some(channel), count)
+            })
+            .collect();
+        self.reply_all(replies)
+    }
+
+    // a reply for each argument, all but the last are pushed ahead of the reply
+    fn reply_all(&self, mut replies: Vec<RespFrame>) -> RespFrame {
+        let last = replies.pop().unwrap_or(RespFrame::Array(None));
+        for reply in replies {
+            self.client.push(reply);
+        }
+        last
+    }
+
+    // the error replies are counted by their code
+    fn record_reply(&self, frame: &RespFrame) {
+        if let RespFrame::Error(e) = frame {
+            let code = e.split(' ').next().unwrap_or_default();
+            self.backend.stats.incr_error_reply(code);
+        }
+    }
+
+    fn record_call(&self, name: Option<&str>, start: Instant, reply: &RespFrame) {
+        if let Some(name) = name {
+            let usec = start.elapsed().as_micros() as u64;
+            let failed = matches!(reply, RespFrame::Error(_));
+            self.backend.stats.record_call(name, usec, failed);
+        }
+    }
+
+    fn unwatch(&mut self) {
+        for key in self.watched_keys.drain(..) {
+            self.backend.unwatch_key(&key, self.client.id());
+        }
+        self.dirty.store(false, Ordering::Relaxed);
+    }
+}
+
+// [kind, channel, number of channels subscribed]
+fn subscription_reply(kind: &str, channel: Option<String>, count: usize) -> RespFrame {
+    RespPush::new(vec![
+        Some(BulkString::from(kind)).into(),
+        channel.map(BulkString::from).into(),
+        (count as i64).into(),
+    ])
+    .into()
+}
+
+fn busy_error() -> RespFrame {
+    ExecError::Busy.into()
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.unwatch();
+        self.backend.unregister_client(self.id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BulkString;
+
+    use super::*;
+    use crate::test_util::frame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_multi_exec() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+
+        assert_eq!(session.process(frame(&["multi"]))?, RESP_OK.clone());
+        let ret = session.process(frame(&["sadd", "myset", "A", "B"]))?;
+        assert_eq!(ret, RESP_QUEUED.clone());
+        let ret = session.process(frame(&["sismember", "myset", "A"]))?;
+        assert_eq!(ret, RESP_QUEUED.clone());
+
+        // nothing is executed before EXEC
+        assert!(backend.hset.get("myset").is_none());
+
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(ret, Some(RespArray::new(vec![2.into(), 1.into()])).into());
+        assert!(!session.in_multi());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_discard() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sadd", "myset", "A"]))?;
+        assert_eq!(session.process(frame(&["discard"]))?, RESP_OK.clone());
+        assert!(backend.hset.get("myset").is_none());
+
+        let ret = session.process(frame(&["discard"]))?;
+        assert_eq!(ret, SimpleError::new("ERR DISCARD without MULTI").into());
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(ret, SimpleError::new("ERR EXEC without MULTI").into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_nested() -> Result<()> {
+        let mut session = Session::new(Backend::new());
+
+        session.process(frame(&["multi"]))?;
+        let ret = session.process(frame(&["multi"]))?;
+        assert_eq!(
+            ret,
+            SimpleError::new("ERR MULTI calls can not be nested").into()
+        );
+        assert!(session.in_multi());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_execabort() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sadd", "myset", "A"]))?;
+        // wrong number of arguments
+        let ret = session.process(frame(&["hset", "myhash", "field"]))?;
+        assert!(matches!(ret, RespFrame::Error(_)));
+
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(
+            ret,
+            SimpleError::new("EXECABORT Transaction discarded because of previous errors.").into()
+        );
+        assert!(backend.hset.get("myset").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_modified_key() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let mut other = Session::new(backend.clone());
+
+        assert_eq!(
+            session.process(frame(&["watch", "myset"]))?,
+            RESP_OK.clone()
+        );
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sadd", "myset", "A"]))?;
+
+        other.process(frame(&["sadd", "myset", "B"]))?;
+
+        assert_eq!(session.process(frame(&["exec"]))?, RespFrame::Array(None));
+        // the transaction was not executed
+        assert!(!backend.hset.get("myset").unwrap().contains(&b"A".into()));
+
+        // watched keys are forgotten after EXEC
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sadd", "myset", "A"]))?;
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(ret, Some(RespArray::new(vec![1.into()])).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_unmodified_key() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let mut other = Session::new(backend.clone());
+
+        session.process(frame(&["sadd", "myset", "A"]))?;
+        session.process(frame(&["watch", "myset"]))?;
+        other.process(frame(&["sadd", "otherset", "B"]))?;
+        // SADD of an existing member does not modify the key
+        other.process(frame(&["sadd", "myset", "A"]))?;
+
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sismember", "myset", "A"]))?;
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(ret, Some(RespArray::new(vec![1.into()])).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unwatch() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let mut other = Session::new(backend.clone());
+
+        session.process(frame(&["watch", "myset"]))?;
+        assert_eq!(session.process(frame(&["unwatch"]))?, RESP_OK.clone());
+        other.process(frame(&["sadd", "myset", "A"]))?;
+
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sismember", "myset", "A"]))?;
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(ret, Some(RespArray::new(vec![1.into()])).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_inside_multi() -> Result<()> {
+        let mut session = Session::new(Backend::new());
+
+        session.process(frame(&["multi"]))?;
+        let ret = session.process(frame(&["watch", "myset"]))?;
+        assert_eq!(
+            ret,
+            SimpleError::new("ERR WATCH inside MULTI is not allowed").into()
+        );
+        assert_eq!(session.process(frame(&["unwatch"]))?, RESP_QUEUED.clone());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unwatch_on_drop() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        session.process(frame(&["watch", "myset"]))?;
+        assert!(backend.watched_keys.contains_key("myset"));
+        drop(session);
+        assert!(!backend.watched_keys.contains_key("myset"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello() -> Result<()> {
+        let mut session = Session::new(Backend::new());
+        assert_eq!(session.protover(), 2);
+
+        let RespFrame::Map(map) = session.process(frame(&["hello", "3", "setname", "app"]))? else {
+            panic!("HELLO replies a map");
+        };
+        assert_eq!(map.get(&b"proto".into()), Some(&3.into()));
+        assert_eq!(map.get(&b"id".into()), Some(&(session.id() as i64).into()));
+        assert_eq!(session.protover(), 3);
+        assert_eq!(session.name().as_deref(), Some("app"));
+
+        // without a protocol version, only the reply is sent
+        session.process(frame(&["hello"]))?;
+        assert_eq!(session.protover(), 3);
+        session.process(frame(&["hello", "2", "auth", "default", "secret"]))?;
+        assert_eq!(session.protover(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_errors() -> Result<()> {
+        let mut session = Session::new(Backend::new());
+        let ret = session.process(frame(&["hello", "4"]))?;
+        assert_eq!(
+            ret,
+            SimpleError::new("NOPROTO unsupported protocol version").into()
+        );
+        let ret = session.process(frame(&["hello", "3", "auth", "admin", "secret"]))?;
+        assert_eq!(
+            ret,
+            SimpleError::new("WRONGPASS invalid username-password pair or user is disabled.")
+                .into()
+        );
+        let ret = session.process(frame(&["hello", "3", "setname", "my app"]))?;
+        assert!(matches!(ret, RespFrame::Error(_)));
+        // nothing changed on an error
+        assert_eq!(session.protover(), 2);
+        assert_eq!(session.name(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_in_multi() -> Result<()> {
+        let mut session = Session::new(Backend::new());
+
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sadd", "myset", "A"]))?;
+        let ret = session.process(frame(&[
+            "eval",
+            "return redis.call('sismember', KEYS[1], ARGV[1])",
+            "1",
+            "myset",
+            "A",
+        ]))?;
+        assert_eq!(ret, RESP_QUEUED.clone());
+        let ret = session.process(frame(&["exec"]))?;
+        assert_eq!(ret, Some(RespArray::new(vec![1.into(), 1.into()])).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_busy_script() -> Result<()> {
+        let backend = Backend::new();
+        backend
+            .scripts
+            .set_time_limit(std::time::Duration::from_millis(10));
+        let mut other = Session::new(backend.clone());
+        let handle = std::thread::spawn(move || {
+            let mut session = Session::new(backend);
+            session.process(frame(&["eval", "while true do end", "0"]))
+        });
+
+        loop {
+            match other.process(frame(&["sismember", "myset", "A"]))? {
+                RespFrame::Error(e) if e.starts_with("BUSY") => break,
+                _ => std::thread::sleep(std::time::Duration::from_millis(5)),
+            }
+        }
+        assert_eq!(other.process(frame(&["script", "kill"]))?, RESP_OK.clone());
+        let ret = handle.join().unwrap()?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.starts_with("ERR Script killed")));
+        Ok(())
+    }
+
+    // the connections keep being served while a script blocks the only worker
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_busy_script_single_worker() -> Result<()> {
+        let backend = Backend::new();
+        backend
+            .scripts
+            .set_time_limit(std::time::Duration::from_millis(10));
+        let mut session = Session::new(backend.clone());
+        let script =
+            tokio::spawn(
+                async move { session.process(frame(&["eval", "while true do end", "0"])) },
+            );
+
+        let mut other = Session::new(backend);
+        let kill = tokio::spawn(async move {
+            loop {
+                match other.process(frame(&["script", "kill"]))? {
+                    RespFrame::Error(e) if e.starts_with("NOTBUSY") => {
+                        tokio::time::sleep(std::time::Duration::from_millis(5)).await
+                    }
+                    ret => return Ok::<_, CommandError>(ret),
+                }
+            }
+        });
+
+        assert_eq!(kill.await??, RESP_OK.clone());
+        let ret = script.await??;
+        assert!(matches!(ret, RespFrame::Error(e) if e.starts_with("ERR Script killed")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_maxmemory() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let oom = || RespFrame::from(ExecError::Oom);
+
+        session.process(frame(&["sadd", "myset", "a"]))?;
+        session.process(frame(&["config", "set", "maxmemory", "1"]))?;
+        assert_eq!(session.process(frame(&["sadd", "myset", "b"]))?, oom());
+        let ret = session.process(frame(&["sismember", "myset", "a"]))?;
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = session.process(frame(&[
+            "eval",
+            "return redis.call('sadd', KEYS[1], 'b')",
+            "1",
+            "myset",
+        ]))?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.starts_with("OOM")));
+
+        session.process(frame(&["multi"]))?;
+        assert_eq!(session.process(frame(&["sadd", "myset", "b"]))?, oom());
+        let ret = session.process(frame(&["exec"]))?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.starts_with("EXECABORT")));
+
+        session.process(frame(&["config", "set", "maxmemory", "0"]))?;
+        let ret = session.process(frame(&["sadd", "myset", "b"]))?;
+        assert_eq!(ret, RespFrame::Integer(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_error_outside_multi() {
+        let mut session = Session::new(Backend::new());
+        let err = session
+            .process(frame(&["HSET", "myhash", "field"]))
+            .unwrap_err();
+        assert_eq!(
+            SimpleError::from(err),
+            SimpleError::new("ERR wrong number of arguments for 'hset' command")
+        );
+    }
+
+    fn drain(pushes: &mut tokio::sync::mpsc::UnboundedReceiver<RespFrame>) -> Vec<RespFrame> {
+        std::iter::from_fn(|| pushes.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn test_tracking() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let mut other = Session::new(backend.clone());
+        let mut pushes = session.client().take_pushes().unwrap();
+        let invalidate = || vec![crate::invalidate_message("myset")];
+
+        session.process(frame(&["hello", "3"]))?;
+        let ret = session.process(frame(&["client", "tracking", "on"]))?;
+        assert_eq!(ret, RESP_OK.clone());
+        session.process(frame(&["sadd", "myset", "a"]))?;
+        session.process(frame(&["sismember", "myset", "a"]))?;
+        other.process(frame(&["sadd", "myset", "b"]))?;
+        assert_eq!(drain(&mut pushes), invalidate());
+        // not tracked until read again
+        other.process(frame(&["sadd", "myset", "c"]))?;
+        assert!(drain(&mut pushes).is_empty());
+
+        // the client is invalidated by its own writes, unless NOLOOP
+        session.process(frame(&["sismember", "myset", "a"]))?;
+        session.process(frame(&["sadd", "myset", "d"]))?;
+        assert_eq!(drain(&mut pushes), invalidate());
+        session.process(frame(&["client", "tracking", "on", "noloop"]))?;
+        session.process(frame(&["sismember", "myset", "a"]))?;
+        session.process(frame(&["sadd", "myset", "e"]))?;
+        assert!(drain(&mut pushes).is_empty());
+
+        // OPTIN tracks the next command after CLIENT CACHING yes
+        session.process(frame(&["client", "tracking", "off"]))?;
+        session.process(frame(&["client", "tracking", "on", "optin"]))?;
+        session.process(frame(&["sismember", "myset", "a"]))?;
+        other.process(frame(&["sadd", "myset", "f"]))?;
+        assert!(drain(&mut pushes).is_empty());
+        session.process(frame(&["client", "caching", "yes"]))?;
+        session.process(frame(&["sismember", "myset", "a"]))?;
+        other.process(frame(&["sadd", "myset", "g"]))?;
+        assert_eq!(drain(&mut pushes), invalidate());
+
+        let ret = session.process(frame(&["client", "caching", "no"]))?;
+        assert!(matches!(ret, RespFrame::Error(_)));
+        let ret = session.process(frame(&["client", "tracking", "on", "bcast"]))?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("BCAST mode")));
+        let ret = session.process(frame(&["client", "tracking", "on", "optout"]))?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("OPTIN/OPTOUT mode")));
+        assert!(session.client().tracking().is_some_and(|t| t.optin));
+        session.process(frame(&["client", "tracking", "off"]))?;
+        let ret = session.process(frame(&["client", "tracking", "on", "optout"]))?;
+        assert_eq!(ret, RESP_OK.clone());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracking_redirect() -> Result<()> {
+        let backend = Backend::new();
+        let mut listener = Session::new(backend.clone());
+        let mut session = Session::new(backend.clone());
+        let mut pushes = listener.client().take_pushes().unwrap();
+
+        let ret = listener.process(frame(&["subscribe", "__redis__:invalidate", "news"]))?;
+        let subscribed = |channel: &str, count: i64| -> RespFrame {
+            RespPush::new(vec![
+                Some(BulkString::from("subscribe")).into(),
+                Some(BulkString::from(channel)).into(),
+                count.into(),
+            ])
+            .into()
+        };
+        assert_eq!(
+            drain(&mut pushes),
+            vec![subscribed("__redis__:invalidate", 1)]
+        );
+        assert_eq!(ret, subscribed("news", 2));
+
+        let redirect = listener.id().to_string();
+        let args = [
+            "client", "tracking", "on", "redirect", &redirect, "bcast", "prefix", "user:",
+        ];
+        assert_eq!(session.process(frame(&args))?, RESP_OK.clone());
+        session.process(frame(&["sadd", "user:1", "a"]))?;
+        session.process(frame(&["sadd", "group:1", "a"]))?;
+        assert_eq!(
+            drain(&mut pushes),
+            vec![crate::invalidate_channel_message("user:1")]
+        );
+
+        // a RESP2 client in the subscribed state can only change its channels
+        let ret = listener.process(frame(&["sismember", "user:1", "a"]))?;
+        assert!(
+            matches!(ret, RespFrame::Error(e) if e.starts_with("ERR Can't execute 'sismember'"))
+        );
+        listener.process(frame(&["unsubscribe"]))?;
+        drain(&mut pushes);
+        session.process(frame(&["sadd", "user:1", "b"]))?;
+        assert!(drain(&mut pushes).is_empty());
+        assert_eq!(
+            listener.process(frame(&["unsubscribe"]))?,
+            RespPush::new(vec![
+                Some(BulkString::from("unsubscribe")).into(),
+                RespFrame::BulkString(None),
+                0.into(),
+            ])
+            .into()
+        );
+
+        let ret = session.process(frame(&["client", "tracking", "on", "redirect", "999"]))?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("does not exist")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_commands_in_multi() -> Result<()> {
+        let mut session = Session::new(Backend::new());
+
+        session.process(frame(&["multi"]))?;
+        let ret = session.process(frame(&["client", "setname", "x"]))?;
+        assert_eq!(ret, RESP_QUEUED.clone());
+        session.process(frame(&["hello", "3"]))?;
+        assert_eq!(session.name(), None);
+        assert_eq!(session.protover, 2);
+        let ret = session.process(frame(&["exec"]))?;
+        assert!(matches!(ret, RespFrame::Array(Some(ref replies)) if replies.len() == 2));
+        assert_eq!(session.name(), Some("x".to_string()));
+        assert_eq!(session.protover, 3);
+
+        session.process(frame(&["multi"]))?;
+        let ret = session.process(frame(&["subscribe", "news"]))?;
+        assert_eq!(
+            ret,
+            ExecError::err("Command not allowed inside a transaction").into()
+        );
+        let ret = session.process(frame(&["exec"]))?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.starts_with("EXECABORT")));
+        assert_eq!(session.client().subscriptions(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_registry() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let other = Session::new(backend.clone());
+
+        let ret = session.process(frame(&["client", "id"]))?;
+        assert_eq!(ret, (session.id() as i64).into());
+        session.process(frame(&["client", "setname", "app"]))?;
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["sadd", "myset", "a"]))?;
+        assert_eq!(session.name().as_deref(), Some("app"));
+        assert_eq!(session.client().multi(), 1);
+        assert_eq!(session.client().last_command().as_deref(), Some("sadd"));
+        assert_eq!(backend.client_count(), 2);
+
+        drop(other);
+        assert_eq!(backend.clients().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        session.process(frame(&["sadd", "myset", "a"]))?;
+        session.process(frame(&["hgetall", "myset"]))?;
+        let _ = session.process(frame(&["hset", "myhash"]));
+        session.process(frame(&["multi"]))?;
+        session.process(frame(&["hmget", "myset", "a"]))?;
+        session.process(frame(&["exec"]))?;
+
+        let stats = backend.stats();
+        assert_eq!(stats.connections_received(), 1);
+        assert_eq!(stats.commands_processed(), 5);
+        assert_eq!(
+            stats.errors(),
+            BTreeMap::from([("ERR".to_string(), 1), ("WRONGTYPE".to_string(), 2)])
+        );
+        assert_eq!(stats.error_replies(), 3);
+
+        session.process(frame(&["config", "resetstat"]))?;
+        assert_eq!(stats.commands_processed(), 1);
+        assert!(stats.errors().is_empty());
+        Ok(())
+    }
+}
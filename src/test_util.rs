@@ -0,0 +1,36 @@
+use std::{fs, path::PathBuf};
+
+use crate::{BulkString, Command, CommandError, RespArray, RespFrame};
+
+/// The arguments of a command, each one a bulk string.
+pub fn array<A: AsRef<[u8]>>(args: &[A]) -> RespArray {
+    RespArray::new(
+        args.iter()
+            .map(|arg| Some(BulkString::new(arg.as_ref())).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+}
+
+/// The request frame of a command, as sent by the clients.
+pub fn frame<A: AsRef<[u8]>>(args: &[A]) -> RespFrame {
+    Some(array(args)).into()
+}
+
+/// The command parsed from the request frame of its arguments.
+pub fn cmd(args: &[&str]) -> Result<Command, CommandError> {
+    Command::try_from(frame(args))
+}
+
+/// The command of binary arguments, as payloads and library code.
+pub fn bytes_cmd(args: &[&[u8]]) -> Result<Command, CommandError> {
+    Command::try_from(frame(args))
+}
+
+/// A path in the temp dir unique to the test process, with any leftover of a
+/// previous run removed.
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    let _ = fs::remove_file(&path);
+    path
+}
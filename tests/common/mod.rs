@@ -0,0 +1,41 @@
+// shared by the integration tests, each of them uses a part of it
+#![allow(dead_code)]
+
+use bytes::BytesMut;
+use simple_redis::{RespDecoderV4, RespError, RespFrame};
+
+// xorshift64, deterministic so that a failure can be replayed
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // any byte, CR and LF are more likely to be picked
+    pub fn byte(&mut self) -> u8 {
+        match self.below(8) {
+            0 => b'\r',
+            1 => b'\n',
+            _ => self.next() as u8,
+        }
+    }
+
+    pub fn bytes(&mut self, max_len: usize) -> Vec<u8> {
+        (0..self.below(max_len + 1)).map(|_| self.byte()).collect()
+    }
+}
+
+// the resumable decoder as the others, with a new decoder for each frame
+pub fn v4_decode(buf: &mut BytesMut) -> Result<RespFrame, RespError> {
+    RespDecoderV4::default()
+        .decode(buf)?
+        .ok_or(RespError::NotComplete)
+}
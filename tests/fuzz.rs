@@ -0,0 +1,158 @@
+// A fuzzing harness for the decoders: mutated and random inputs must be
+// decoded into frames or errors, a panic fails the test.
+
+mod common;
+
+use bytes::BytesMut;
+use common::{v4_decode, Rng};
+use simple_redis::{RespDecode, RespDecodeV2, RespDecodeV3, RespDecoderV4, RespError, RespFrame};
+
+const ITERATIONS: usize = 20_000;
+
+// valid frames of every type, the seeds of the mutations
+const CORPUS: &[&[u8]] = &[
+    b"+OK\r\n",
+    b"-ERR unknown command\r\n",
+    b":-123\r\n",
+    b"$5\r\nhello\r\n",
+    b"$0\r\n\r\n",
+    b"$-1\r\n",
+    b"*-1\r\n",
+    b"*0\r\n",
+    b"*3\r\n$4\r\necho\r\n$5\r\nhello\r\n+OK\r\n",
+    b"*2\r\n*1\r\n:1\r\n*1\r\n$1\r\na\r\n",
+    b"_\r\n",
+    b"#t\r\n",
+    b",1.5\r\n",
+    b",nan\r\n",
+    b"(3492890328409238509324850943850943825024385\r\n",
+    b"!21\r\nSYNTAX invalid syntax\r\n",
+    b"=15\r\ntxt:Some string\r\n",
+    b"%2\r\n+first\r\n:1\r\n,nan\r\n,inf\r\n",
+    b"~2\r\n+a\r\n:1\r\n",
+    b">2\r\n+message\r\n$7\r\nchannel\r\n",
+    b"|1\r\n+ttl\r\n:3600\r\n*2\r\n:1\r\n:2\r\n",
+];
+
+// the bytes most likely to reach the edge cases of a decoder
+const INTERESTING: &[u8] = b"+-:$*_#,(!=%~>|\r\n0123456789-tfx";
+
+fn interesting_byte(rng: &mut Rng) -> u8 {
+    if rng.below(4) == 0 {
+        rng.next() as u8
+    } else {
+        INTERESTING[rng.below(INTERESTING.len())]
+    }
+}
+
+fn mutate(rng: &mut Rng) -> Vec<u8> {
+    let mut data = CORPUS[rng.below(CORPUS.len())].to_vec();
+    for _ in 0..=rng.below(4) {
+        match rng.below(6) {
+            0 if !data.is_empty() => {
+                let i = rng.below(data.len());
+                data[i] = interesting_byte(rng);
+            }
+            1 => {
+                let i = rng.below(data.len() + 1);
+                data.insert(i, interesting_byte(rng));
+            }
+            2 if !data.is_empty() => {
+                let i = rng.below(data.len());
+                data.remove(i);
+            }
+            3 => {
+                let len = rng.below(data.len() + 1);
+                data.truncate(len);
+            }
+            4 => data.extend_from_slice(CORPUS[rng.below(CORPUS.len())]),
+            _ => data = (0..rng.below(32)).map(|_| interesting_byte(rng)).collect(),
+        }
+    }
+    data
+}
+
+// decodes all the frames of the input, as a connection does
+fn decode_all(data: &[u8], decode: fn(&mut BytesMut) -> Result<RespFrame, RespError>) {
+    let mut buf = BytesMut::from(data);
+    while !buf.is_empty() {
+        let len = buf.len();
+        match decode(&mut buf) {
+            Ok(_) => assert!(buf.len() < len, "a frame must consume the input"),
+            Err(_) => break,
+        }
+    }
+}
+
+fn decode_with_all_decoders(data: &[u8]) {
+    decode_all(data, <RespFrame as RespDecode>::decode);
+    decode_all(data, <RespFrame as RespDecodeV2>::decode);
+    decode_all(data, <RespFrame as RespDecodeV3>::decode);
+    decode_all(data, v4_decode);
+    let _ = <RespFrame as RespDecode>::expect_length(data);
+    let _ = <RespFrame as RespDecodeV2>::expect_length(data);
+    let _ = <RespFrame as RespDecodeV3>::expect_length(data);
+
+    // the resumable decoders are fed by small reads as well
+    for mut decoder in [RespDecoderV4::default(), RespDecoderV4::for_requests()] {
+        let mut buf = BytesMut::new();
+        for chunk in data.chunks(3) {
+            buf.extend_from_slice(chunk);
+            while let Ok(Some(_)) = decoder.decode(&mut buf) {}
+        }
+    }
+}
+
+#[test]
+fn fuzz_decoders_never_panic() {
+    let mut rng = Rng(0x5eed_1234_abcd_ef01);
+    for _ in 0..ITERATIONS {
+        decode_with_all_decoders(&mutate(&mut rng));
+    }
+}
+
+#[test]
+fn malformed_frames_are_errors() {
+    let cases: &[&[u8]] = &[
+        b"",
+        b"x",
+        b"xyz\r\n",
+        b"*-5\r\n",
+        b"$-5\r\n",
+        b"*1\r\n$-5\r\n",
+        b"~-1\r\n",
+        b"%-1\r\n",
+        b"$99999999999999999999\r\n",
+        b"*1\r\n$100\r\nab",
+        b"*2\r\n#t",
+        b"%2\r\n,nan\r\n:1\r\n,nan\r\n:2\r\n",
+        b"=2\r\nab\r\n",
+    ];
+    for case in cases {
+        decode_with_all_decoders(case);
+    }
+}
+
+#[test]
+fn limits_are_protocol_errors() {
+    let too_deep = [b"*1\r\n".repeat(1000), b":1\r\n".to_vec()].concat();
+    let cases: &[&[u8]] = &[
+        b"$536870913\r\n",
+        b"*2147483648\r\n",
+        b"%2147483648\r\n",
+        &too_deep,
+    ];
+    for case in cases {
+        let err = <RespFrame as RespDecode>::decode(&mut BytesMut::from(*case)).unwrap_err();
+        assert!(matches!(err, RespError::Protocol(_)), "v1: {:?}", err);
+        let err = <RespFrame as RespDecodeV2>::decode(&mut BytesMut::from(*case)).unwrap_err();
+        assert!(matches!(err, RespError::Protocol(_)), "v2: {:?}", err);
+        let err = <RespFrame as RespDecodeV3>::decode(&mut BytesMut::from(*case)).unwrap_err();
+        assert!(matches!(err, RespError::Protocol(_)), "v3: {:?}", err);
+        let err = v4_decode(&mut BytesMut::from(*case)).unwrap_err();
+        assert!(matches!(err, RespError::Protocol(_)), "v4: {:?}", err);
+    }
+
+    let err = <RespFrame as RespDecodeV2>::decode(&mut BytesMut::from("$-2\r\n")).unwrap_err();
+    assert_eq!(err, RespError::Protocol("invalid bulk length".to_string()));
+}
@@ -0,0 +1,169 @@
+// Property tests of the codecs: any frame carrying arbitrary bytes, CRLF and
+// invalid UTF-8 included, is decoded by every decoder into the frame encoded.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use bytes::BytesMut;
+use common::{v4_decode, Rng};
+use simple_redis::{
+    BlobError, BulkString, RespArray, RespDecode, RespDecodeV2, RespDecodeV3, RespDecoderV4,
+    RespEncode, RespError, RespFrame, RespPush, RespSet, VerbatimString,
+};
+
+const ITERATIONS: usize = 5_000;
+const MAX_PAYLOAD: usize = 64;
+const MAX_ELEMENTS: usize = 4;
+
+type Decode = fn(&mut BytesMut) -> Result<RespFrame, RespError>;
+
+const DECODERS: &[(&str, Decode)] = &[
+    ("v1", <RespFrame as RespDecode>::decode),
+    ("v2", <RespFrame as RespDecodeV2>::decode),
+    ("v3", <RespFrame as RespDecodeV3>::decode),
+    ("v4", v4_decode),
+];
+
+fn bulk_string(rng: &mut Rng) -> RespFrame {
+    Some(BulkString::new(rng.bytes(MAX_PAYLOAD))).into()
+}
+
+fn elements(rng: &mut Rng, depth: usize) -> Vec<RespFrame> {
+    (0..rng.below(MAX_ELEMENTS + 1))
+        .map(|_| frame(rng, depth + 1))
+        .collect()
+}
+
+// a frame whose payloads are arbitrary bytes, nested up to 3 aggregates deep
+fn frame(rng: &mut Rng, depth: usize) -> RespFrame {
+    let kinds = if depth < 3 { 8 } else { 3 };
+    match rng.below(kinds) {
+        0 => bulk_string(rng),
+        1 => RespFrame::BlobError(BlobError::new(rng.bytes(MAX_PAYLOAD))),
+        2 => RespFrame::Verbatim(VerbatimString::new(*b"txt", rng.bytes(MAX_PAYLOAD))),
+        3 => Some(RespArray::new(elements(rng, depth))).into(),
+        4 => RespFrame::Set(RespSet::new(elements(rng, depth))),
+        5 => RespFrame::Push(RespPush::new(elements(rng, depth))),
+        6 => {
+            let map = (0..rng.below(MAX_ELEMENTS + 1))
+                .map(|_| (bulk_string(rng), frame(rng, depth + 1)))
+                .collect::<BTreeMap<_, _>>();
+            RespFrame::Map(map)
+        }
+        _ => Some(RespArray::new(vec![
+            bulk_string(rng),
+            frame(rng, depth + 1),
+        ]))
+        .into(),
+    }
+}
+
+#[test]
+fn arbitrary_payloads_round_trip() {
+    let mut rng = Rng(0x0dd_ba11_cafe_f00d);
+    for _ in 0..ITERATIONS {
+        let frame = frame(&mut rng, 0);
+        let data = frame.clone().encode();
+        assert_eq!(frame.size_hint(), data.len(), "{:?}", data);
+        for (name, decode) in DECODERS {
+            let mut buf = BytesMut::from(&data[..]);
+            buf.extend_from_slice(b"+NEXT\r\n");
+            let decoded = decode(&mut buf);
+            assert_eq!(decoded.as_ref(), Ok(&frame), "{}: {:?}", name, data);
+            assert_eq!(&buf[..], b"+NEXT\r\n", "{}: {:?}", name, data);
+        }
+    }
+}
+
+#[test]
+fn incomplete_frames_wait_for_more_data() {
+    let mut rng = Rng(0xfeed_5eed_0123_4567);
+    for _ in 0..ITERATIONS / 10 {
+        let data = frame(&mut rng, 0).encode();
+        for (name, decode) in DECODERS {
+            for len in 0..data.len() {
+                let mut buf = BytesMut::from(&data[..len]);
+                let err = decode(&mut buf).unwrap_err();
+                assert_eq!(err, RespError::NotComplete, "{}: {:?}", name, &data[..len]);
+                // the resumable decoder consumes the bytes it decoded, the others keep them
+                if *name != "v4" {
+                    assert_eq!(buf.len(), len, "{}: {:?}", name, &data[..len]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn split_frames_are_resumed() {
+    let mut rng = Rng(0xabad_1dea_7777_0001);
+    for _ in 0..ITERATIONS / 10 {
+        let frames = (0..=rng.below(3))
+            .map(|_| frame(&mut rng, 0))
+            .collect::<Vec<_>>();
+        let data = frames
+            .iter()
+            .flat_map(|frame| frame.clone().encode())
+            .collect::<Vec<_>>();
+
+        let mut decoder = RespDecoderV4::default();
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        let mut data = &data[..];
+        while !data.is_empty() {
+            let (chunk, rest) = data.split_at(rng.below(data.len().min(16)) + 1);
+            buf.extend_from_slice(chunk);
+            data = rest;
+            while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+                decoded.push(frame);
+            }
+        }
+        assert_eq!(decoded, frames);
+        assert!(!decoder.is_pending());
+    }
+}
+
+#[test]
+fn bulk_strings_are_binary_safe() {
+    let payloads: &[&[u8]] = &[
+        b"",
+        b"\r\n",
+        b"\r\n\r\n",
+        b"a\r\nb",
+        b"$3\r\nfoo\r\n",
+        b"\xff\xfe\x00\x80",
+        &[b'x'; 100],
+    ];
+    for payload in payloads {
+        let frame: RespFrame = Some(BulkString::new(payload.to_vec())).into();
+        let data = frame.clone().encode();
+        for (name, decode) in DECODERS {
+            let decoded = decode(&mut BytesMut::from(&data[..]));
+            assert_eq!(decoded, Ok(frame.clone()), "{}: {:?}", name, payload);
+        }
+    }
+}
+
+#[test]
+fn bulk_strings_share_the_decoded_buffer() {
+    let data = [
+        b"*2\r\n$3\r\nset\r\n$100\r\n".as_ref(),
+        &[b'v'; 100],
+        b"\r\n",
+    ]
+    .concat();
+    for (name, decode) in DECODERS {
+        let mut buf = BytesMut::from(&data[..]);
+        let range = buf.as_ptr_range();
+        let Ok(RespFrame::Array(Some(frames))) = decode(&mut buf) else {
+            panic!("{}: expect an array", name);
+        };
+        for frame in frames.iter() {
+            let RespFrame::BulkString(Some(s)) = frame else {
+                panic!("{}: expect a bulk string", name);
+            };
+            assert!(range.contains(&s.as_ptr()), "{}: the data is copied", name);
+        }
+    }
+}